@@ -0,0 +1,387 @@
+//! Anonymous encryption (anoncrypt) of messages as JWE with `ECDH-ES+A256KW` key wrapping.
+//!
+//! The content is encrypted with a random content encryption key (CEK), that is wrapped with
+//! AES key wrap for each recipient. The key encryption key of a recipient is derived from an
+//! ECDH-ES key agreement between a new ephemeral X25519 key and the key of the recipient with the
+//! Concat KDF of RFC 7518 (section 4.6.2) without party infos. The ephemeral public key is added
+//! as `epk` to the protected header, which is used as associated data of the content encryption.
+
+use aes::Aes256;
+use aes_gcm::{
+    aead::{Aead as AesAead, KeyInit, Payload as AesPayload},
+    Aes256Gcm,
+};
+use aes_kw::KekAes256;
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    Key,
+    XChaCha20Poly1305,
+    XNonce,
+};
+use data_encoding::BASE64URL_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use rand_core::OsRng;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256, Sha512};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{datatypes::EncryptionAlgorithm, utils::vec_to_array};
+
+/// Key management algorithm of anoncrypt messages, set as `alg` in the protected header.
+pub const ANONCRYPT_KEY_ALGORITHM: &str = "ECDH-ES+A256KW";
+
+const ENCRYPTED_MESSAGE_TYPE: &str = "application/didcomm-encrypted+json";
+const TAG_LENGTH: usize = 16;
+const CBC_HMAC_TAG_LENGTH: usize = 32;
+
+/// Encrypts a message for multiple recipients without revealing the sender.
+///
+/// # Arguments
+/// * `plaintext` - message to encrypt
+/// * `recipients` - key id and X25519 public key of each recipient
+/// * `encryption_algorithm` - content encryption algorithm
+///
+/// # Returns
+/// * `String` - stringified JWE
+pub fn encrypt(
+    plaintext: &str,
+    recipients: &[(String, Vec<u8>)],
+    encryption_algorithm: EncryptionAlgorithm,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if recipients.is_empty() {
+        return Err(Box::from(
+            "at least one recipient is required for anoncrypt",
+        ));
+    }
+
+    let ephemeral_secret = StaticSecret::new(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral_secret);
+    let protected = json!({
+        "typ": ENCRYPTED_MESSAGE_TYPE,
+        "alg": ANONCRYPT_KEY_ALGORITHM,
+        "enc": encryption_algorithm.to_string(),
+        "epk": {
+            "kty": "OKP",
+            "crv": "X25519",
+            "x": BASE64URL_NOPAD.encode(ephemeral_public.as_bytes()),
+        },
+    });
+
+    let mut cek = vec![0u8; get_cek_length(encryption_algorithm)];
+    rand::thread_rng().fill_bytes(&mut cek);
+
+    let mut encrypted_keys = Vec::new();
+    for (kid, public_key) in recipients.iter() {
+        let public_key =
+            PublicKey::from(vec_to_array::<u8, 32>(public_key.to_vec()).map_err(|_| {
+                format!("invalid key of recipient '{kid}', only X25519 keys are supported")
+            })?);
+        let shared_secret = ephemeral_secret.diffie_hellman(&public_key);
+        let encrypted_key = KekAes256::from(derive_key_encryption_key(shared_secret.as_bytes()))
+            .wrap_vec(&cek)
+            .map_err(|err| format!("could not wrap content encryption key: {err}"))?;
+        encrypted_keys.push(json!({
+            "header": { "kid": kid },
+            "encrypted_key": BASE64URL_NOPAD.encode(&encrypted_key),
+        }));
+    }
+
+    let aad = get_additional_authenticated_data(&protected)?;
+    let (iv, ciphertext, tag) =
+        encrypt_content(encryption_algorithm, &cek, plaintext.as_bytes(), &aad)?;
+
+    Ok(serde_json::to_string(&json!({
+        "protected": protected,
+        "recipients": encrypted_keys,
+        "iv": BASE64URL_NOPAD.encode(&iv),
+        "ciphertext": BASE64URL_NOPAD.encode(&ciphertext),
+        "tag": BASE64URL_NOPAD.encode(&tag),
+    }))?)
+}
+
+/// Decrypts a message encrypted with `encrypt`.
+///
+/// # Arguments
+/// * `message` - stringified JWE
+/// * `secret` - X25519 secret key of one of the recipients
+///
+/// # Returns
+/// * `String` - decrypted message
+pub fn decrypt(message: &str, secret: &[u8; 32]) -> Result<String, Box<dyn std::error::Error>> {
+    let jwe: Value = serde_json::from_str(message)?;
+    let protected = &jwe["protected"];
+    if protected["alg"].as_str() != Some(ANONCRYPT_KEY_ALGORITHM) {
+        return Err(Box::from(format!(
+            "message is not encrypted with {ANONCRYPT_KEY_ALGORITHM}"
+        )));
+    }
+    let encryption_algorithm = protected["enc"]
+        .as_str()
+        .and_then(EncryptionAlgorithm::from_enc_header)
+        .ok_or("unsupported content encryption algorithm")?;
+    if protected["epk"]["crv"].as_str() != Some("X25519") {
+        return Err(Box::from("only X25519 ephemeral keys are supported"));
+    }
+    let ephemeral_public = protected["epk"]["x"]
+        .as_str()
+        .ok_or("ephemeral public key is missing")?;
+    let ephemeral_public = PublicKey::from(vec_to_array::<u8, 32>(
+        BASE64URL_NOPAD.decode(ephemeral_public.as_bytes())?,
+    )?);
+
+    let shared_secret = StaticSecret::from(*secret).diffie_hellman(&ephemeral_public);
+    let key_encryption_key = KekAes256::from(derive_key_encryption_key(shared_secret.as_bytes()));
+    // key wrapping is integrity protected, so only the key wrapped for us can be unwrapped
+    let cek = jwe["recipients"]
+        .as_array()
+        .ok_or("recipients are missing")?
+        .iter()
+        .filter_map(|recipient| recipient["encrypted_key"].as_str())
+        .filter_map(|encrypted_key| BASE64URL_NOPAD.decode(encrypted_key.as_bytes()).ok())
+        .find_map(|encrypted_key| key_encryption_key.unwrap_vec(&encrypted_key).ok())
+        .ok_or("could not unwrap content encryption key for any recipient")?;
+    if cek.len() != get_cek_length(encryption_algorithm) {
+        return Err(Box::from("invalid content encryption key length"));
+    }
+
+    let decode_field = |name: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let value = jwe[name].as_str().ok_or(format!("{name} is missing"))?;
+        Ok(BASE64URL_NOPAD.decode(value.as_bytes())?)
+    };
+    let aad = get_additional_authenticated_data(protected)?;
+    let plaintext = decrypt_content(
+        encryption_algorithm,
+        &cek,
+        &decode_field("iv")?,
+        &decode_field("ciphertext")?,
+        &decode_field("tag")?,
+        &aad,
+    )?;
+
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// Derives the key to wrap the content encryption key with from the shared secret of the key
+/// agreement, using the Concat KDF with SHA-256 and a key length of 256 bit.
+fn derive_key_encryption_key(shared_secret: &[u8]) -> [u8; 32] {
+    let algorithm_id = ANONCRYPT_KEY_ALGORITHM.as_bytes();
+    let mut hasher = Sha256::new();
+    // round counter, a single round is sufficient for 256 bit
+    hasher.update(1u32.to_be_bytes());
+    hasher.update(shared_secret);
+    hasher.update((algorithm_id.len() as u32).to_be_bytes());
+    hasher.update(algorithm_id);
+    // empty `apu` and `apv`
+    hasher.update(0u32.to_be_bytes());
+    hasher.update(0u32.to_be_bytes());
+    hasher.update(256u32.to_be_bytes());
+
+    hasher.finalize().into()
+}
+
+/// Gets the associated data of the content encryption, the base64url encoded protected header.
+fn get_additional_authenticated_data(
+    protected: &Value,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(BASE64URL_NOPAD
+        .encode(serde_json::to_string(protected)?.as_bytes())
+        .into_bytes())
+}
+
+fn get_cek_length(encryption_algorithm: EncryptionAlgorithm) -> usize {
+    match encryption_algorithm {
+        // first half is the HMAC key, second half the AES key
+        EncryptionAlgorithm::A256CbcHs512 => 64,
+        _ => 32,
+    }
+}
+
+/// Encrypts the content of a message.
+///
+/// # Returns
+/// * `(Vec<u8>, Vec<u8>, Vec<u8>)` - initialization vector, ciphertext and authentication tag
+#[allow(clippy::type_complexity)]
+fn encrypt_content(
+    encryption_algorithm: EncryptionAlgorithm,
+    cek: &[u8],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    match encryption_algorithm {
+        EncryptionAlgorithm::Xc20p => {
+            let mut iv = vec![0u8; 24];
+            rand::thread_rng().fill_bytes(&mut iv);
+            let mut ciphertext = XChaCha20Poly1305::new(Key::from_slice(cek))
+                .encrypt(
+                    XNonce::from_slice(&iv),
+                    Payload {
+                        msg: plaintext,
+                        aad,
+                    },
+                )
+                .map_err(|_| "could not encrypt message content")?;
+            let tag = ciphertext.split_off(ciphertext.len() - TAG_LENGTH);
+            Ok((iv, ciphertext, tag))
+        }
+        EncryptionAlgorithm::A256Gcm => {
+            let mut iv = vec![0u8; 12];
+            rand::thread_rng().fill_bytes(&mut iv);
+            let mut ciphertext = Aes256Gcm::new_from_slice(cek)?
+                .encrypt(
+                    aes_gcm::Nonce::from_slice(&iv),
+                    AesPayload {
+                        msg: plaintext,
+                        aad,
+                    },
+                )
+                .map_err(|_| "could not encrypt message content")?;
+            let tag = ciphertext.split_off(ciphertext.len() - TAG_LENGTH);
+            Ok((iv, ciphertext, tag))
+        }
+        EncryptionAlgorithm::A256CbcHs512 => {
+            let mut iv = vec![0u8; 16];
+            rand::thread_rng().fill_bytes(&mut iv);
+            let (mac_key, encryption_key) = cek.split_at(32);
+            let ciphertext = cbc::Encryptor::<Aes256>::new_from_slices(encryption_key, &iv)?
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+            let tag = get_cbc_hmac(mac_key, aad, &iv, &ciphertext)?
+                .finalize()
+                .into_bytes()[..CBC_HMAC_TAG_LENGTH]
+                .to_vec();
+            Ok((iv, ciphertext, tag))
+        }
+    }
+}
+
+/// Decrypts the content of a message and checks its authentication tag.
+fn decrypt_content(
+    encryption_algorithm: EncryptionAlgorithm,
+    cek: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let ciphertext_with_tag = [ciphertext, tag].concat();
+    match encryption_algorithm {
+        EncryptionAlgorithm::Xc20p if iv.len() == 24 => {
+            Ok(XChaCha20Poly1305::new(Key::from_slice(cek))
+                .decrypt(
+                    XNonce::from_slice(iv),
+                    Payload {
+                        msg: &ciphertext_with_tag,
+                        aad,
+                    },
+                )
+                .map_err(|_| "could not decrypt message content")?)
+        }
+        EncryptionAlgorithm::A256Gcm if iv.len() == 12 => Ok(Aes256Gcm::new_from_slice(cek)?
+            .decrypt(
+                aes_gcm::Nonce::from_slice(iv),
+                AesPayload {
+                    msg: &ciphertext_with_tag,
+                    aad,
+                },
+            )
+            .map_err(|_| "could not decrypt message content")?),
+        EncryptionAlgorithm::A256CbcHs512 if iv.len() == 16 => {
+            // MAC and padding failures share one error, so they can't be told apart
+            if tag.len() != 32 {
+                return Err(Box::from("could not decrypt message content"));
+            }
+            let (mac_key, encryption_key) = cek.split_at(32);
+            get_cbc_hmac(mac_key, aad, iv, ciphertext)?
+                .verify_truncated_left(tag)
+                .map_err(|_| "could not decrypt message content")?;
+            Ok(
+                cbc::Decryptor::<Aes256>::new_from_slices(encryption_key, iv)?
+                    .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+                    .map_err(|_| "could not decrypt message content")?,
+            )
+        }
+        _ => Err(Box::from("invalid initialization vector length")),
+    }
+}
+
+/// Gets the HMAC of `A256CBC-HS512` over the associated data, the initialization vector, the
+/// ciphertext and the bit length of the associated data.
+fn get_cbc_hmac(
+    mac_key: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+) -> Result<Hmac<Sha512>, Box<dyn std::error::Error>> {
+    let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(mac_key)?;
+    mac.update(aad);
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac.update(&((aad.len() as u64) * 8).to_be_bytes());
+
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_encrypt_and_decrypt_with_all_content_encryption_algorithms(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let secret = StaticSecret::from([3u8; 32]);
+        let other_secret = StaticSecret::from([4u8; 32]);
+        let recipients = vec![
+            (
+                "did:example:other".to_string(),
+                PublicKey::from(&other_secret).as_bytes().to_vec(),
+            ),
+            (
+                "did:example:receiver".to_string(),
+                PublicKey::from(&secret).as_bytes().to_vec(),
+            ),
+        ];
+
+        for encryption_algorithm in [
+            EncryptionAlgorithm::Xc20p,
+            EncryptionAlgorithm::A256Gcm,
+            EncryptionAlgorithm::A256CbcHs512,
+        ] {
+            let encrypted = encrypt("hello", &recipients, encryption_algorithm)?;
+            assert_eq!(decrypt(&encrypted, &secret.to_bytes())?, "hello");
+            assert_eq!(decrypt(&encrypted, &other_secret.to_bytes())?, "hello");
+            assert!(decrypt(&encrypted, &[5u8; 32]).is_err());
+
+            // the protected header is authenticated with the content
+            let mut jwe: Value = serde_json::from_str(&encrypted)?;
+            jwe["protected"]["typ"] = json!("application/didcomm-plain+json");
+            assert!(decrypt(&serde_json::to_string(&jwe)?, &secret.to_bytes()).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn will_reject_truncated_authentication_tags() -> Result<(), Box<dyn std::error::Error>> {
+        let secret = StaticSecret::from([3u8; 32]);
+        let recipients = vec![(
+            "did:example:receiver".to_string(),
+            PublicKey::from(&secret).as_bytes().to_vec(),
+        )];
+
+        let encrypted = encrypt("hello", &recipients, EncryptionAlgorithm::A256CbcHs512)?;
+        let mut jwe: Value = serde_json::from_str(&encrypted)?;
+        let tag =
+            BASE64URL_NOPAD.decode(jwe["tag"].as_str().ok_or("tag is missing")?.as_bytes())?;
+        for length in [0, 1, 16] {
+            jwe["tag"] = json!(BASE64URL_NOPAD.encode(&tag[..length]));
+            let result = decrypt(&serde_json::to_string(&jwe)?, &secret.to_bytes());
+            assert_eq!(
+                result.err().map(|err| err.to_string()),
+                Some("could not decrypt message content".to_string()),
+            );
+        }
+
+        Ok(())
+    }
+}
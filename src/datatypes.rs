@@ -3,7 +3,11 @@ use didcomm_rs::Attachment;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::{get_from_to_from_message, utils::hex_option};
+use crate::{
+    get_from_to_from_message,
+    protocols::out_of_band::datatypes::Invitation,
+    utils::{hex_option, hex_option_bytes, hex_vec, json_string_vec},
+};
 
 pub trait HasFromAndTo {
     fn get_from_to(&self) -> Result<FromTo, Box<dyn std::error::Error>>;
@@ -27,6 +31,10 @@ pub struct DidCommService {
     pub priority: u8,
     pub service_endpoint: String,
     pub recipient_keys: Vec<String>,
+    /// keys of the mediators, that messages to the DID have to be forwarded by
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub routing_keys: Vec<String>,
 }
 
 /// Communication DIDComm object that will be sent to the target user during DID exchange.
@@ -58,6 +66,7 @@ pub struct ExchangeInfo {
     pub did_id: String,
     pub pub_key_hex: String,
     pub service_endpoint: String,
+    pub routing_keys: Vec<String>,
 }
 
 /// Communication keypair with the complete information to encrypt and decrypt a message from a
@@ -72,6 +81,12 @@ pub struct CommKeyPair {
     pub target_key_agreement_key: String,
     pub target_pub_key: String,
     pub target_service_endpoint: String,
+    /// keys of the mediators, that messages to the target have to be forwarded by, stored as
+    /// stringified JSON array, as the keypair is returned as string metadata during DID exchange
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(with = "json_string_vec")]
+    pub target_routing_keys: Vec<String>,
 }
 
 /// Specifies all possible message directions.
@@ -121,6 +136,9 @@ pub struct ExtendedMessage {
     pub to: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub attachments: Vec<Attachment>,
+    /// JWT signed by the prior DID of the sender, if the sender rotated its DID
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub from_prior: Option<String>,
     #[serde(flatten, skip_serializing_if = "HashMap::is_empty")]
     pub other: HashMap<String, String>,
 }
@@ -195,6 +213,47 @@ pub struct EncryptionKeyPair {
     pub public: [u8; 32],
 }
 
+/// Generated KeyPair of a specific key type, `X25519` key pairs can be read as `EncryptionKeyPair`.
+/// Public keys of `secp256k1` and `P-256` key pairs are SEC1 compressed (33 bytes).
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyPair {
+    pub key_type: KeyType,
+    #[serde(with = "hex")]
+    pub secret: [u8; 32],
+    #[serde(with = "hex")]
+    pub public: Vec<u8>,
+}
+
+/// Payload of the `create_keys` custom function.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateKeysPayload {
+    pub key_type: Option<KeyType>,
+}
+
+/// Type of a key, used for key agreement (`X25519`) or signing (`Ed25519`, `secp256k1`, `P-256`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum KeyType {
+    X25519,
+    Ed25519,
+    #[serde(rename = "secp256k1")]
+    Secp256k1,
+    #[serde(rename = "P-256")]
+    P256,
+}
+impl std::fmt::Display for KeyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            KeyType::X25519 => "X25519",
+            KeyType::Ed25519 => "Ed25519",
+            KeyType::Secp256k1 => "secp256k1",
+            KeyType::P256 => "P-256",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// Either a computed shared secret or a (local) private key plus a contacts public key
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -205,6 +264,16 @@ pub struct EncryptionKeys {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "hex_option")]
     pub encryption_others_public: Option<[u8; 32]>,
+    /// public keys of all recipients in the order of the `to` DIDs, used instead of
+    /// `encryption_others_public` to send a message to multiple recipients
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(with = "hex_vec")]
+    pub encryption_others_public_keys: Vec<[u8; 32]>,
+    /// type of the keys, defaults to `X25519`, which is the only type supported for key agreement
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption_key_type: Option<KeyType>,
 }
 
 /// Either a computed shared secret or a (local) private key plus a contacts public key
@@ -217,8 +286,12 @@ pub struct SigningKeys {
     pub signing_my_secret: Option<[u8; 32]>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(with = "hex_option")]
-    pub signing_others_public: Option<[u8; 32]>,
+    #[serde(with = "hex_option_bytes")]
+    pub signing_others_public: Option<Vec<u8>>,
+    /// type of the keys, defaults to `Ed25519`, `secp256k1` signs with ES256K and `P-256` with ES256
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_key_type: Option<KeyType>,
 }
 
 /// Data contains optional field which have to filled as per
@@ -233,13 +306,241 @@ pub struct Data {
 }
 
 /// Optional parameter that can be passed to vade DIDComm functions to enforce a specific encryption key
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct DidCommOptions {
     pub encryption_keys: Option<EncryptionKeys>,
     pub signing_keys: Option<SigningKeys>,
     pub skip_message_packaging: Option<bool>,
     pub skip_protocol_handling: Option<bool>,
+    pub tenant_id: Option<String>,
+    pub packing: Option<PackingMode>,
+    pub encryption_algorithm: Option<EncryptionAlgorithm>,
+    /// prior DID of the sender, adds a `from_prior` JWT to rotate it to the `from` of the message
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_prior: Option<FromPriorOptions>,
+    /// keys of the mediators of the receiver, the encrypted message is wrapped in a forward message
+    /// for each of them, the first key is the one of the mediator, that receives the message first
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing_keys: Option<Vec<String>>,
+}
+
+/// Prior DID of a sender and its Ed25519 key to sign a `from_prior` JWT with
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FromPriorOptions {
+    /// DID, that is rotated to the `from` of the message
+    pub prior_did: String,
+    /// id of the signing key as `kid` of the JWT, defaults to `prior_did`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prior_kid: Option<String>,
+    /// Ed25519 secret key of the prior DID
+    #[serde(with = "hex")]
+    pub prior_signing_secret: [u8; 32],
+}
+
+/// Content encryption algorithm of encrypted messages, defaults to `XC20P`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum EncryptionAlgorithm {
+    /// XChaCha20Poly1305
+    #[serde(rename = "XC20P")]
+    Xc20p,
+    /// AES-256 in GCM mode
+    #[serde(rename = "A256GCM")]
+    A256Gcm,
+    /// AES-256 in CBC mode with HMAC SHA-512
+    #[serde(rename = "A256CBC-HS512")]
+    A256CbcHs512,
+}
+impl EncryptionAlgorithm {
+    /// Gets the algorithm for the `enc` header of an encrypted message.
+    ///
+    /// # Arguments
+    /// * `enc` - value of the `enc` header
+    ///
+    /// # Returns
+    /// * `Option<EncryptionAlgorithm>` - algorithm, `None` if not supported
+    pub fn from_enc_header(enc: &str) -> Option<EncryptionAlgorithm> {
+        match enc {
+            "XC20P" => Some(EncryptionAlgorithm::Xc20p),
+            "A256GCM" => Some(EncryptionAlgorithm::A256Gcm),
+            // DIDComm rs names the algorithm without the HMAC part
+            "A256CBC-HS512" | "A256CBC" => Some(EncryptionAlgorithm::A256CbcHs512),
+            _ => None,
+        }
+    }
+}
+impl std::fmt::Display for EncryptionAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            EncryptionAlgorithm::Xc20p => "XC20P",
+            EncryptionAlgorithm::A256Gcm => "A256GCM",
+            EncryptionAlgorithm::A256CbcHs512 => "A256CBC-HS512",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Specifies how a message is packed for sending. If not set, messages are packed with `signed`
+/// if signing keys are given and with `authcrypt` otherwise.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PackingMode {
+    /// encrypted for the receiver, the sender stays anonymous
+    Anoncrypt,
+    /// encrypted for the receiver with the key of the sender, so the receiver can authenticate it
+    Authcrypt,
+    /// signed by the sender and encrypted with `authcrypt`
+    Signed,
+    /// signed by the sender without encryption (JWS), so third parties can verify it
+    Jws,
+    /// neither signed nor encrypted
+    Plaintext,
+}
+impl std::fmt::Display for PackingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PackingMode::Anoncrypt => "anoncrypt",
+            PackingMode::Authcrypt => "authcrypt",
+            PackingMode::Signed => "signed",
+            PackingMode::Jws => "jws",
+            PackingMode::Plaintext => "plaintext",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reason, why `didcomm_receive` rejected a message.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RejectionReason {
+    /// `expires_time` of the message has passed
+    Expired,
+    /// `created_time` of the message is further in the future than the allowed clock skew
+    CreatedInFuture,
+    /// a message with the same id has already been received from the same `from` DID
+    Replayed,
+}
+
+/// Error returned by `didcomm_receive` for rejected messages. It is formatted as json, so the
+/// reason can be read from the error message as well as by downcasting the error.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRejection {
+    pub reason: RejectionReason,
+    pub message_id: Option<String>,
+    pub description: String,
+}
+impl std::fmt::Display for MessageRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let formatted = serde_json::to_string(self).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", formatted)
+    }
+}
+impl std::error::Error for MessageRejection {}
+
+/// Tenant, that has its own keys, threads and messages within a plugin instance.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Tenant {
+    pub tenant_id: String,
+    pub created_at: u64,
+}
+
+/// Password encrypted export of all stored keys, protocol states and messages.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletArchive {
+    pub version: u32,
+    pub kdf: WalletArchiveKdf,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// Message held by a mediator until its recipient picks it up.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedMessage {
+    pub id: String,
+    /// DID or key id, that the message has been forwarded to
+    pub next: String,
+    /// stringified encrypted message
+    pub message: String,
+    pub received_time: u64,
+}
+
+/// Argon2id parameters used to derive the archive key from the password.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletArchiveKdf {
+    pub salt: String,
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+}
+
+/// Payload of the `export_wallet` custom function.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportWalletPayload {
+    pub password: String,
+}
+
+/// Payload of the `import_wallet` custom function.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportWalletPayload {
+    pub password: String,
+    pub archive: WalletArchive,
+}
+
+/// Payload of the `rotate_master_key` custom function.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateMasterKeyPayload {
+    /// id of the new master key, must not be empty or contain `:`
+    pub key_id: String,
+    /// hex encoded 32 byte secret of the new master key
+    pub key: String,
+}
+
+/// Payload of the `create_invitation` custom function.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateInvitationPayload {
+    /// DID of the inviter, that the DID exchange request has to be sent to
+    pub from: String,
+    pub goal_code: Option<String>,
+    pub goal: Option<String>,
+    /// accepted media types, defaults to `didcomm/v2`
+    pub accept: Option<Vec<String>>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// seconds after which the invitation expires, invitations don't expire if not set
+    pub expires_in: Option<u64>,
+    /// only accept a single DID exchange request for the invitation
+    #[serde(default)]
+    pub single_use: bool,
+    /// url, that the invitation is appended to as `_oob` parameter
+    pub base_url: String,
+}
+
+/// Payload of the `receive_invitation` custom function.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiveInvitationPayload {
+    pub invitation_url: String,
+}
+
+/// Output of the `create_invitation` and `receive_invitation` custom functions.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InvitationOutput {
+    pub invitation: Invitation,
+    pub invitation_url: String,
 }
 
 /// Output of didcomm_send.
@@ -0,0 +1,119 @@
+//! Buffering of writes, that have to be stored together.
+
+use std::{cell::RefCell, collections::BTreeMap};
+
+use super::{BatchOperation, DidCommStorage};
+
+/// Storage wrapper, that collects all writes and applies them with a single `write_batch` call on
+/// `commit`. Reads include the collected writes. Writes are discarded if the batch is dropped
+/// without being committed.
+pub struct BatchStorage<'a> {
+    storage: &'a dyn DidCommStorage,
+    // `None` marks a deleted entry
+    writes: RefCell<BTreeMap<String, Option<String>>>,
+}
+
+impl<'a> BatchStorage<'a> {
+    /// Creates a new, empty batch.
+    ///
+    /// # Arguments
+    /// * `storage` - storage to read from and to write the batch to
+    pub fn new(storage: &'a dyn DidCommStorage) -> BatchStorage<'a> {
+        BatchStorage {
+            storage,
+            writes: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Applies all collected writes to the wrapped storage.
+    pub fn commit(self) -> Result<(), Box<dyn std::error::Error>> {
+        let operations: Vec<BatchOperation> = self
+            .writes
+            .into_inner()
+            .into_iter()
+            .map(|(key, value)| match value {
+                Some(value) => BatchOperation::Put { key, value },
+                None => BatchOperation::Delete { key },
+            })
+            .collect();
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        self.storage.write_batch(&operations)
+    }
+}
+
+impl DidCommStorage for BatchStorage<'_> {
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        match self.writes.borrow().get(key) {
+            Some(Some(value)) => Ok(value.to_owned()),
+            Some(None) => Err(Box::from(format!("{key} not found"))),
+            None => self.storage.get(key),
+        }
+    }
+
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.writes
+            .borrow_mut()
+            .insert(key.to_string(), Some(value.to_string()));
+
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.writes.borrow_mut().insert(key.to_string(), None);
+
+        Ok(())
+    }
+
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let mut entries: BTreeMap<String, String> =
+            self.storage.scan_prefix(prefix)?.into_iter().collect();
+        for (key, value) in self
+            .writes
+            .borrow()
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+        {
+            match value {
+                Some(value) => entries.insert(key.to_owned(), value.to_owned()),
+                None => entries.remove(key),
+            };
+        }
+
+        Ok(entries.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryStorage;
+
+    #[test]
+    fn can_collect_and_commit_writes() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = MemoryStorage::new();
+        storage.put("test_1", "old")?;
+        storage.put("test_2", "deleted")?;
+
+        let batch = BatchStorage::new(&storage);
+        batch.put("test_1", "new")?;
+        batch.put("test_3", "added")?;
+        batch.delete("test_2")?;
+        assert_eq!(batch.get("test_1")?, "new");
+        assert!(batch.get("test_2").is_err());
+        assert_eq!(batch.scan_prefix("test_")?.len(), 2);
+        assert_eq!(storage.get("test_1")?, "old");
+
+        batch.commit()?;
+        assert_eq!(storage.get("test_1")?, "new");
+        assert_eq!(storage.get("test_3")?, "added");
+        assert!(storage.get("test_2").is_err());
+
+        Ok(())
+    }
+}
@@ -3,58 +3,142 @@
 //! Only intended for debugging as it does allow to inspect written data.
 //!
 //! Does NOT ensure thread safety, therefore parallel calls may overwrite the "database" file.
+//!
+//! Keys of a namespace are stored with a `{namespace}:` prefix.
 
 use std::{collections::HashMap, fs};
 
 use serde_json::json;
 
+use super::{BatchOperation, DidCommStorage, StorageConfig};
+
 const DEBUG_DB_PATH: &str = "./.didcomm_debug_db.json";
 
-fn get_storage() -> Result<serde_json::Value, Box<dyn std::error::Error>> {
-    let json_text: String;
-    if !std::path::Path::new(DEBUG_DB_PATH).exists() {
-        fs::write(DEBUG_DB_PATH, "{}")?;
-        json_text = "{}".to_string();
-    } else {
-        json_text = fs::read_to_string(DEBUG_DB_PATH)?;
-    }
-    serde_json::from_str(&json_text).map_err(|e| Box::from(e.to_string()))
+pub struct DebugStorage {
+    path: String,
+    key_prefix: String,
 }
 
-pub fn write_db(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let mut storage = get_storage()?;
-    storage[key] = json!(value);
-    fs::write(DEBUG_DB_PATH, serde_json::to_string_pretty(&storage)?)?;
+impl DebugStorage {
+    /// Creates a new debug storage, located at the default path.
+    pub fn new() -> DebugStorage {
+        DebugStorage::from_config(&StorageConfig::default())
+    }
+
+    /// Creates a new debug storage, located at the configured path.
+    ///
+    /// # Arguments
+    /// * `config` - database file and namespace, defaults are used if not set
+    pub fn from_config(config: &StorageConfig) -> DebugStorage {
+        DebugStorage {
+            path: config
+                .path
+                .clone()
+                .unwrap_or_else(|| DEBUG_DB_PATH.to_string()),
+            key_prefix: config
+                .namespace
+                .as_ref()
+                .map(|namespace| format!("{namespace}:"))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn get_storage(&self) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let json_text: String;
+        if !std::path::Path::new(&self.path).exists() {
+            fs::write(&self.path, "{}")?;
+            json_text = "{}".to_string();
+        } else {
+            json_text = fs::read_to_string(&self.path)?;
+        }
+        serde_json::from_str(&json_text).map_err(|e| Box::from(e.to_string()))
+    }
+
+    fn write_storage(&self, storage: &serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(&self.path, serde_json::to_string_pretty(storage)?)?;
+
+        Ok(())
+    }
 
-    Ok(())
+    fn get_prefixed_key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
 }
 
-pub fn read_db(key: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let storage = get_storage()?;
-    storage[key]
-        .as_str()
-        .ok_or(format!("key {} not found in debug db", key))
-        .map(|v| v.to_string())
-        .map_err(|e| Box::from(e.to_string()))
+impl Default for DebugStorage {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-/// Gets a list of values matching with key prefix from local file.
-///
-/// # Arguments
-/// * `prefix` - key prefix to match values for
-///
-/// # Returns
-/// * `Vec<String>` - stored values
-pub fn search_db_keys(prefix: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let mut values: Vec<String> = Vec::new();
-    let storage = &serde_json::to_string(&get_storage()?)?;
-    let storage_map: HashMap<&str, &str> = serde_json::from_str(storage)?;
-
-    for (key, value) in storage_map {
-        if key.starts_with(prefix) {
-            values.push(value.to_string());
+impl DidCommStorage for DebugStorage {
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let storage = self.get_storage()?;
+        storage[self.get_prefixed_key(key)]
+            .as_str()
+            .ok_or(format!("key {} not found in debug db", key))
+            .map(|v| v.to_string())
+            .map_err(|e| Box::from(e.to_string()))
+    }
+
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut storage = self.get_storage()?;
+        storage[self.get_prefixed_key(key)] = json!(value);
+        self.write_storage(&storage)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut storage = self.get_storage()?;
+        if let Some(entries) = storage.as_object_mut() {
+            entries.remove(&self.get_prefixed_key(key));
         }
+        self.write_storage(&storage)
     }
 
-    return Ok(values);
+    /// Gets a list of entries matching with key prefix from local file.
+    ///
+    /// # Arguments
+    /// * `prefix` - key prefix to match values for
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - stored key value pairs
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let mut entries: Vec<(String, String)> = Vec::new();
+        let storage = &serde_json::to_string(&self.get_storage()?)?;
+        let storage_map: HashMap<&str, &str> = serde_json::from_str(storage)?;
+
+        for (key, value) in storage_map {
+            if let Some(key) = key.strip_prefix(&self.key_prefix) {
+                if key.starts_with(prefix) {
+                    entries.push((key.to_string(), value.to_string()));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Applies multiple writes with a single write of the local file.
+    ///
+    /// # Arguments
+    /// * `operations` - writes to apply
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut storage = self.get_storage()?;
+        for operation in operations.iter() {
+            match operation {
+                BatchOperation::Put { key, value } => {
+                    storage[self.get_prefixed_key(key)] = json!(value);
+                }
+                BatchOperation::Delete { key } => {
+                    if let Some(entries) = storage.as_object_mut() {
+                        entries.remove(&self.get_prefixed_key(key));
+                    }
+                }
+            }
+        }
+        self.write_storage(&storage)
+    }
 }
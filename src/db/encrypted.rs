@@ -0,0 +1,375 @@
+//! Encryption at rest for any storage backend.
+//!
+//! Values are encrypted with XChaCha20Poly1305 before they are passed to the wrapped storage. Keys
+//! stay readable, so prefix scans keep working, and are used as associated data, so an encrypted
+//! value cannot be moved to another key unnoticed.
+//!
+//! Encrypted values are stored as `enc:{key_id}:{nonce}:{ciphertext}` with hex encoded nonce and
+//! ciphertext. The key id allows to decrypt values written with a previous master key, e.g. when a
+//! key rotation has been interrupted.
+
+use std::sync::RwLock;
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    Key,
+    XChaCha20Poly1305,
+    XNonce,
+};
+use rand::RngCore;
+
+use super::{BatchOperation, DidCommStorage};
+
+const ENCRYPTED_VALUE_PREFIX: &str = "enc";
+const NONCE_LENGTH: usize = 24;
+
+/// Key used to encrypt stored values, identified by an id that is stored with each value.
+#[derive(Clone)]
+pub struct MasterKey {
+    id: String,
+    key: [u8; 32],
+}
+
+impl MasterKey {
+    /// Creates a new master key.
+    ///
+    /// # Arguments
+    /// * `id` - identifier stored with every encrypted value, must not be empty or contain `:`
+    /// * `key` - 32 byte secret key
+    ///
+    /// # Returns
+    /// * `MasterKey` - new master key
+    pub fn new(id: &str, key: [u8; 32]) -> Result<MasterKey, Box<dyn std::error::Error>> {
+        if id.is_empty() || id.contains(':') {
+            return Err(Box::from(format!(
+                "invalid master key id '{id}', must not be empty or contain ':'"
+            )));
+        }
+
+        Ok(MasterKey {
+            id: id.to_string(),
+            key,
+        })
+    }
+
+    /// Gets the id of the master key.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Master key to encrypt new values with and keys, that are only used to decrypt values written
+/// before a rotation.
+struct MasterKeys {
+    current: MasterKey,
+    previous: Vec<MasterKey>,
+}
+
+/// Storage wrapper, that encrypts all values before writing them to another storage.
+pub struct EncryptedStorage {
+    storage: Box<dyn DidCommStorage>,
+    master_keys: RwLock<MasterKeys>,
+}
+
+impl EncryptedStorage {
+    /// Creates a new encrypted storage.
+    ///
+    /// # Arguments
+    /// * `storage` - storage to write encrypted values to
+    /// * `master_key` - key to encrypt new values with
+    /// * `previous_keys` - keys, that are only used to decrypt values written before a rotation
+    pub fn new(
+        storage: Box<dyn DidCommStorage>,
+        master_key: MasterKey,
+        previous_keys: Vec<MasterKey>,
+    ) -> EncryptedStorage {
+        EncryptedStorage {
+            storage,
+            master_keys: RwLock::new(MasterKeys {
+                current: master_key,
+                previous: previous_keys,
+            }),
+        }
+    }
+
+    /// Re-encrypts all stored values with a new master key. Values, that have been stored
+    /// without encryption, are encrypted as well, so this can be used to encrypt an existing
+    /// storage. All values are written with a single batch and the previous keys are only
+    /// dropped after the batch has been applied, so a failed rotation leaves all values readable
+    /// with the previous keys.
+    ///
+    /// # Arguments
+    /// * `master_key` - key to encrypt all values with
+    ///
+    /// # Returns
+    /// * `usize` - number of re-encrypted values
+    pub fn rotate_master_key(
+        &self,
+        master_key: MasterKey,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        // writes of other callers have to wait, so no value is written with the previous key
+        let mut master_keys = self
+            .master_keys
+            .write()
+            .map_err(|_| "could not lock master keys")?;
+        let operations = self
+            .storage
+            .scan_prefix("")?
+            .into_iter()
+            .map(|(key, value)| {
+                let plaintext = if is_encrypted(&value) {
+                    decrypt(&master_keys, &key, &value)?
+                } else {
+                    value
+                };
+                let value = encrypt(&master_key, &key, &plaintext)?;
+                Ok(BatchOperation::Put { key, value })
+            })
+            .collect::<Result<Vec<BatchOperation>, Box<dyn std::error::Error>>>()?;
+        self.storage.write_batch(&operations)?;
+
+        master_keys.current = master_key;
+        master_keys.previous.clear();
+
+        Ok(operations.len())
+    }
+
+    fn encrypt(&self, key: &str, value: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let master_keys = self
+            .master_keys
+            .read()
+            .map_err(|_| "could not lock master keys")?;
+        encrypt(&master_keys.current, key, value)
+    }
+
+    fn decrypt(&self, key: &str, value: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let master_keys = self
+            .master_keys
+            .read()
+            .map_err(|_| "could not lock master keys")?;
+        decrypt(&master_keys, key, value)
+    }
+}
+
+fn encrypt(
+    master_key: &MasterKey,
+    key: &str,
+    value: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&master_key.key))
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: value.as_bytes(),
+                aad: key.as_bytes(),
+            },
+        )
+        .map_err(|_| format!("could not encrypt value for {key}"))?;
+
+    Ok(format!(
+        "{}:{}:{}:{}",
+        ENCRYPTED_VALUE_PREFIX,
+        master_key.id,
+        hex::encode(nonce),
+        hex::encode(ciphertext),
+    ))
+}
+
+fn decrypt(
+    master_keys: &MasterKeys,
+    key: &str,
+    value: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut parts = value.splitn(4, ':');
+    let (key_id, nonce, ciphertext) = match (parts.next(), parts.next(), parts.next(), parts.next())
+    {
+        (Some(ENCRYPTED_VALUE_PREFIX), Some(key_id), Some(nonce), Some(ciphertext)) => {
+            (key_id, hex::decode(nonce)?, hex::decode(ciphertext)?)
+        }
+        _ => return Err(Box::from(format!("value for {key} is not encrypted"))),
+    };
+    if nonce.len() != NONCE_LENGTH {
+        return Err(Box::from(format!("invalid nonce in value for {key}")));
+    }
+    let master_key = std::iter::once(&master_keys.current)
+        .chain(master_keys.previous.iter())
+        .find(|master_key| master_key.id == key_id)
+        .ok_or_else(|| format!("unknown master key '{key_id}' for {key}"))?;
+    let plaintext = XChaCha20Poly1305::new(Key::from_slice(&master_key.key))
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: key.as_bytes(),
+            },
+        )
+        .map_err(|_| format!("could not decrypt value for {key}"))?;
+
+    Ok(String::from_utf8(plaintext)?)
+}
+
+pub(super) fn is_encrypted(value: &str) -> bool {
+    value.starts_with(&format!("{ENCRYPTED_VALUE_PREFIX}:"))
+}
+
+impl DidCommStorage for EncryptedStorage {
+    /// Gets a value from the wrapped storage and decrypts it.
+    ///
+    /// # Arguments
+    /// * `key` - key to load the value for
+    ///
+    /// # Returns
+    /// * `String` - decrypted value
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.decrypt(key, &self.storage.get(key)?)
+    }
+
+    /// Encrypts a value and writes it into the wrapped storage.
+    ///
+    /// # Arguments
+    /// * `key` - key to save the value for
+    /// * `value` - string value to encrypt and store
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.storage.put(key, &self.encrypt(key, value)?)
+    }
+
+    /// Deletes a value from the wrapped storage.
+    ///
+    /// # Arguments
+    /// * `key` - key to delete the value for
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.storage.delete(key)
+    }
+
+    /// Gets a list of entries matching with key prefix from the wrapped storage and decrypts
+    /// their values.
+    ///
+    /// # Arguments
+    /// * `prefix` - key prefix to match values for
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - stored key value pairs with decrypted values
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        self.storage
+            .scan_prefix(prefix)?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.decrypt(&key, &value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    /// Encrypts the values of multiple writes and applies them to the wrapped storage.
+    ///
+    /// # Arguments
+    /// * `operations` - writes to apply
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        let operations = operations
+            .iter()
+            .map(|operation| match operation {
+                BatchOperation::Put { key, value } => Ok(BatchOperation::Put {
+                    key: key.to_owned(),
+                    value: self.encrypt(key, value)?,
+                }),
+                BatchOperation::Delete { .. } => Ok(operation.clone()),
+            })
+            .collect::<Result<Vec<BatchOperation>, Box<dyn std::error::Error>>>()?;
+
+        self.storage.write_batch(&operations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryStorage;
+
+    #[test]
+    fn can_encrypt_and_rotate_values() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = EncryptedStorage::new(
+            Box::new(MemoryStorage::new()),
+            MasterKey::new("key1", [1; 32])?,
+            Vec::new(),
+        );
+        storage.put("test_1", "secret")?;
+        storage.storage.put("test_2", "plain")?;
+
+        assert_eq!(storage.get("test_1")?, "secret");
+        assert!(!storage.storage.get("test_1")?.contains("secret"));
+        assert!(storage.get("test_2").is_err());
+
+        // values must not be readable under another key
+        storage
+            .storage
+            .put("test_3", &storage.storage.get("test_1")?)?;
+        assert!(storage.get("test_3").is_err());
+        storage.delete("test_3")?;
+
+        assert_eq!(
+            storage.rotate_master_key(MasterKey::new("key2", [2; 32])?)?,
+            2
+        );
+        assert!(storage.storage.get("test_1")?.starts_with("enc:key2:"));
+        assert_eq!(storage.get("test_1")?, "secret");
+        assert_eq!(storage.scan_prefix("test_")?.len(), 2);
+
+        Ok(())
+    }
+
+    /// Storage, that fails to apply batches, like a backend crashing during a rotation.
+    struct FailingBatchStorage(MemoryStorage);
+
+    impl DidCommStorage for FailingBatchStorage {
+        fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+            self.0.get(key)
+        }
+
+        fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.put(key, value)
+        }
+
+        fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.delete(key)
+        }
+
+        fn scan_prefix(
+            &self,
+            prefix: &str,
+        ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+            self.0.scan_prefix(prefix)
+        }
+
+        fn write_batch(
+            &self,
+            _operations: &[BatchOperation],
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            Err(Box::from("batch failed"))
+        }
+    }
+
+    #[test]
+    fn keeps_previous_key_if_rotation_fails() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = EncryptedStorage::new(
+            Box::new(FailingBatchStorage(MemoryStorage::new())),
+            MasterKey::new("key1", [1; 32])?,
+            Vec::new(),
+        );
+        storage.put("test_1", "secret")?;
+
+        assert!(storage
+            .rotate_master_key(MasterKey::new("key2", [2; 32])?)
+            .is_err());
+        assert!(storage.storage.get("test_1")?.starts_with("enc:key1:"));
+        assert_eq!(storage.get("test_1")?, "secret");
+        storage.put("test_2", "secret")?;
+        assert!(storage.storage.get("test_2")?.starts_with("enc:key1:"));
+
+        Ok(())
+    }
+}
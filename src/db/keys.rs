@@ -0,0 +1,134 @@
+//! Parsing of the keys written by the protocol handlers.
+//!
+//! Storage backends can use this to map entries to structured tables while still accepting the
+//! plain key value layout.
+
+/// Protocols, that store their state with the default key layout.
+pub const PROTOCOLS: [&str; 7] = [
+    "coordinate_mediation",
+    "did_exchange",
+    "discover_features",
+    "issue_credential",
+    "pickup",
+    "present_proof",
+    "presentation_exchange",
+];
+
+/// Parsed representation of a storage key.
+#[derive(Debug, PartialEq)]
+pub enum StorageKey<'a> {
+    /// `message_{thid}_{id}`
+    Message { thid: &'a str, id: &'a str },
+    /// `comm_keypair_{from}_{to}`
+    CommKeyPair { from: &'a str, to: &'a str },
+    /// `key_agreement_key_{key_agreement_key}`
+    KeyAgreementKey { key_agreement_key: &'a str },
+    /// `{protocol}_state_{user_type}_{thid}`
+    ThreadState {
+        protocol: &'a str,
+        user_type: &'a str,
+        thid: &'a str,
+    },
+    /// `{protocol}_{from}_{to}_{state}_{thid}`
+    ProtocolData {
+        protocol: &'a str,
+        from: &'a str,
+        to: &'a str,
+        state: &'a str,
+        thid: &'a str,
+    },
+    /// any key not matching one of the layouts above
+    Other,
+}
+
+/// Parses a storage key into its components.
+///
+/// # Arguments
+/// * `key` - key to parse
+///
+/// # Returns
+/// * `StorageKey` - parsed key, `StorageKey::Other` if key does not match a known layout
+pub fn parse_storage_key(key: &str) -> StorageKey<'_> {
+    if let Some(rest) = key.strip_prefix("message_") {
+        // message ids are uuids without underscores, so thread ids may contain underscores
+        if let Some((thid, id)) = rest.rsplit_once('_') {
+            return StorageKey::Message { thid, id };
+        }
+    } else if let Some(rest) = key.strip_prefix("comm_keypair_") {
+        if let Some((from, to)) = rest.split_once('_') {
+            return StorageKey::CommKeyPair { from, to };
+        }
+    } else if let Some(key_agreement_key) = key.strip_prefix("key_agreement_key_") {
+        return StorageKey::KeyAgreementKey { key_agreement_key };
+    } else if let Some((protocol, rest)) = PROTOCOLS
+        .iter()
+        .find_map(|protocol| Some((*protocol, key.strip_prefix(protocol)?.strip_prefix('_')?)))
+    {
+        if let Some(rest) = rest.strip_prefix("state_") {
+            if let Some((user_type, thid)) = rest.split_once('_') {
+                return StorageKey::ThreadState {
+                    protocol,
+                    user_type,
+                    thid,
+                };
+            }
+        } else {
+            let mut parts = rest.splitn(4, '_');
+            if let (Some(from), Some(to), Some(state), Some(thid)) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            {
+                return StorageKey::ProtocolData {
+                    protocol,
+                    from,
+                    to,
+                    state,
+                    thid,
+                };
+            }
+        }
+    }
+
+    StorageKey::Other
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_storage_keys() {
+        assert_eq!(
+            parse_storage_key("message_thread_1_abc"),
+            StorageKey::Message {
+                thid: "thread_1",
+                id: "abc"
+            }
+        );
+        assert_eq!(
+            parse_storage_key("comm_keypair_did:key:z1_did:key:z2"),
+            StorageKey::CommKeyPair {
+                from: "did:key:z1",
+                to: "did:key:z2"
+            }
+        );
+        assert_eq!(
+            parse_storage_key("present_proof_state_Prover_abc"),
+            StorageKey::ThreadState {
+                protocol: "present_proof",
+                user_type: "Prover",
+                thid: "abc"
+            }
+        );
+        assert_eq!(
+            parse_storage_key("issue_credential_did:a_did:b_SendOfferCredential_abc"),
+            StorageKey::ProtocolData {
+                protocol: "issue_credential",
+                from: "did:a",
+                to: "did:b",
+                state: "SendOfferCredential",
+                thid: "abc"
+            }
+        );
+        assert_eq!(parse_storage_key("test1"), StorageKey::Other);
+    }
+}
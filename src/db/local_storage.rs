@@ -1,85 +1,143 @@
 use web_sys::Storage;
 
+use super::{DidCommStorage, StorageConfig};
+
 const LOCAL_STORAGE_PREFIX: &str = "equs-evan-didcomm-db";
 
-pub fn get_storage() -> Result<Storage, Box<dyn std::error::Error>> {
-    let window = web_sys::window().ok_or_else(|| "could not get window".to_string())?;
-    if let Ok(Some(local_storage)) = window.local_storage() {
-        Ok(local_storage)
-    } else {
-        Err(Box::from("could not get local storage"))
-    }
+pub struct LocalStorage {
+    prefix: String,
 }
 
-/// Write a value into local storage.
-///
-/// # Arguments
-/// * `key` - key to save the value for
-/// * `value` - string value to store
-pub fn write_db(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
-    get_storage()?
-        .set_item(&format!("{}:{}", LOCAL_STORAGE_PREFIX, key), value)
-        .map_err(|err| {
-            Box::from(
-                err.as_string()
-                    .unwrap_or_else(|| "could not write to local storage".to_string()),
-            )
-        })
+impl LocalStorage {
+    /// Creates a new local storage wrapper, using the default key prefix.
+    pub fn new() -> LocalStorage {
+        LocalStorage::from_config(&StorageConfig::default())
+    }
+
+    /// Creates a new local storage wrapper, using the configured key prefix and namespace.
+    ///
+    /// # Arguments
+    /// * `config` - `path` is used as key prefix, `namespace` is appended to it if set
+    pub fn from_config(config: &StorageConfig) -> LocalStorage {
+        let prefix = config.path.as_deref().unwrap_or(LOCAL_STORAGE_PREFIX);
+        LocalStorage {
+            prefix: match &config.namespace {
+                Some(namespace) => format!("{prefix}:{namespace}"),
+                None => prefix.to_string(),
+            },
+        }
+    }
+
+    fn get_storage(&self) -> Result<Storage, Box<dyn std::error::Error>> {
+        let window = web_sys::window().ok_or_else(|| "could not get window".to_string())?;
+        if let Ok(Some(local_storage)) = window.local_storage() {
+            Ok(local_storage)
+        } else {
+            Err(Box::from("could not get local storage"))
+        }
+    }
+
+    fn get_prefixed_key(&self, key: &str) -> String {
+        format!("{}:{}", self.prefix, key)
+    }
 }
 
-/// Gets a value from local storage.
-///
-/// # Arguments
-/// * `key` - key to load the value for
-///
-/// # Returns
-/// * `String` - stored value
-pub fn read_db(key: &str) -> Result<String, Box<dyn std::error::Error>> {
-    get_storage()?
-        .get_item(&format!("{}:{}", LOCAL_STORAGE_PREFIX, key))
-        .map_err(|err| {
-            err.as_string()
-                .unwrap_or_else(|| "could read from local storage".to_string())
-        })?
-        .ok_or_else(|| Box::from("".to_string()))
+impl Default for LocalStorage {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-/// Gets a list of values matching with key prefix from local storage.
-///
-/// # Arguments
-/// * `prefix` - key prefix to match values for
-///
-/// # Returns
-/// * `Vec<String>` - stored values
-pub fn search_db_keys(prefix: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let mut values: Vec<String> = Vec::new();
-    let local_storage = get_storage()?;
-    let length = local_storage.length().map_err(|err| {
-        err.as_string()
-            .unwrap_or_else(|| "couldn't read from local storage".to_string())
-    })?;
-
-    for index in 0..length {
-        let key = local_storage
-            .key(index)
+impl DidCommStorage for LocalStorage {
+    /// Gets a value from local storage.
+    ///
+    /// # Arguments
+    /// * `key` - key to load the value for
+    ///
+    /// # Returns
+    /// * `String` - stored value
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.get_storage()?
+            .get_item(&self.get_prefixed_key(key))
             .map_err(|err| {
                 err.as_string()
-                    .unwrap_or_else(|| "couldn't read from local storage".to_string())
+                    .unwrap_or_else(|| "could read from local storage".to_string())
             })?
-            .ok_or("Invalid index value.")?;
+            .ok_or_else(|| Box::from(format!("{key} not found")))
+    }
+
+    /// Write a value into local storage.
+    ///
+    /// # Arguments
+    /// * `key` - key to save the value for
+    /// * `value` - string value to store
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.get_storage()?
+            .set_item(&self.get_prefixed_key(key), value)
+            .map_err(|err| {
+                Box::from(
+                    err.as_string()
+                        .unwrap_or_else(|| "could not write to local storage".to_string()),
+                )
+            })
+    }
+
+    /// Deletes a value from local storage.
+    ///
+    /// # Arguments
+    /// * `key` - key to delete the value for
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.get_storage()?
+            .remove_item(&self.get_prefixed_key(key))
+            .map_err(|err| {
+                Box::from(
+                    err.as_string()
+                        .unwrap_or_else(|| "could not delete from local storage".to_string()),
+                )
+            })
+    }
+
+    /// Gets a list of entries matching with key prefix from local storage.
+    ///
+    /// # Arguments
+    /// * `prefix` - key prefix to match values for
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - stored key value pairs, keys are returned without storage prefix
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let mut entries: Vec<(String, String)> = Vec::new();
+        let local_storage = self.get_storage()?;
+        let storage_prefix = self.get_prefixed_key("");
+        let length = local_storage.length().map_err(|err| {
+            err.as_string()
+                .unwrap_or_else(|| "couldn't read from local storage".to_string())
+        })?;
 
-        if key.starts_with(&format!("{}:{}", LOCAL_STORAGE_PREFIX, prefix)) {
-            let value = local_storage
-                .get_item(&key)
+        for index in 0..length {
+            let key = local_storage
+                .key(index)
                 .map_err(|err| {
                     err.as_string()
                         .unwrap_or_else(|| "couldn't read from local storage".to_string())
                 })?
-                .ok_or(format!("Invalid value for key {}", key))?;
+                .ok_or("Invalid index value.")?;
 
-            values.push(value);
+            if key.starts_with(&self.get_prefixed_key(prefix)) {
+                let value = local_storage
+                    .get_item(&key)
+                    .map_err(|err| {
+                        err.as_string()
+                            .unwrap_or_else(|| "couldn't read from local storage".to_string())
+                    })?
+                    .ok_or(format!("Invalid value for key {}", key))?;
+
+                entries.push((key[storage_prefix.len()..].to_string(), value));
+            }
         }
-    }
 
-    return Ok(values);
+        Ok(entries)
+    }
 }
@@ -0,0 +1,98 @@
+//! Simple in memory storage.
+//!
+//! Used as fallback when no persistent storage backend is enabled. All data is lost when the
+//! storage instance is dropped. Each instance holds its own data, so a `StorageConfig` is not
+//! needed.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use super::{BatchOperation, DidCommStorage};
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: Mutex<BTreeMap<String, String>>,
+}
+
+impl MemoryStorage {
+    /// Creates a new, empty in memory storage.
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    fn lock(
+        &self,
+    ) -> Result<std::sync::MutexGuard<'_, BTreeMap<String, String>>, Box<dyn std::error::Error>>
+    {
+        self.entries
+            .lock()
+            .map_err(|e| Box::from(format!("could not lock memory storage: {e}")))
+    }
+}
+
+impl DidCommStorage for MemoryStorage {
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.lock()?
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Box::from(format!("{key} not found")))
+    }
+
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.lock()?.insert(key.to_string(), value.to_string());
+
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.lock()?.remove(key);
+
+        Ok(())
+    }
+
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        Ok(self
+            .lock()?
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect())
+    }
+
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut entries = self.lock()?;
+        for operation in operations.iter() {
+            match operation {
+                BatchOperation::Put { key, value } => {
+                    entries.insert(key.to_owned(), value.to_owned())
+                }
+                BatchOperation::Delete { key } => entries.remove(key),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_use_memory_storage() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = MemoryStorage::new();
+        storage.put("test_1", "helloooo")?;
+        storage.put("test_2", "world")?;
+        storage.put("other", "value")?;
+
+        assert_eq!(storage.get("test_1")?, "helloooo");
+        assert_eq!(storage.scan_prefix("test_")?.len(), 2);
+
+        storage.delete("test_1")?;
+        assert!(storage.get("test_1").is_err());
+
+        Ok(())
+    }
+}
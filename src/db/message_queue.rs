@@ -0,0 +1,150 @@
+//! Queues of messages, that a mediator holds for its recipients.
+//!
+//! Each queued message is stored with a `queued_message_{recipient_did}_{id}` entry, so the queue
+//! of a recipient can be read with a single prefix scan. The DID is hex encoded, so the prefix of
+//! one recipient can not match the queue of another recipient, whose DID starts with the same
+//! characters. Messages are returned in the order they have been queued in.
+
+use uuid::Uuid;
+
+use super::{BatchOperation, DidCommStorage};
+use crate::{datatypes::QueuedMessage, utils::get_now};
+
+const QUEUED_MESSAGE_PREFIX: &str = "queued_message_";
+
+/// Adds a message to the queue of a recipient.
+///
+/// # Arguments
+/// * `storage` - storage to save the message in
+/// * `recipient_did` - DID of the recipient, that picks up the message
+/// * `next` - DID or key id, that the message has been forwarded to
+/// * `message` - stringified encrypted message
+///
+/// # Returns
+/// * `QueuedMessage` - queued message with its id
+pub fn queue_message(
+    storage: &dyn DidCommStorage,
+    recipient_did: &str,
+    next: &str,
+    message: &str,
+) -> Result<QueuedMessage, Box<dyn std::error::Error>> {
+    let queued_message = QueuedMessage {
+        id: Uuid::new_v4().to_simple().to_string(),
+        next: next.to_string(),
+        message: message.to_string(),
+        received_time: get_now()?,
+    };
+    storage.put(
+        &get_queued_message_key(recipient_did, &queued_message.id),
+        &serde_json::to_string(&queued_message)?,
+    )?;
+
+    Ok(queued_message)
+}
+
+/// Gets the queued messages of a recipient, oldest first.
+///
+/// # Arguments
+/// * `storage` - storage to load the messages from
+/// * `recipient_did` - DID of the recipient
+/// * `next` - only return messages forwarded to this DID or key id, all messages if `None`
+///
+/// # Returns
+/// * `Vec<QueuedMessage>` - queued messages
+pub fn get_queued_messages(
+    storage: &dyn DidCommStorage,
+    recipient_did: &str,
+    next: Option<&str>,
+) -> Result<Vec<QueuedMessage>, Box<dyn std::error::Error>> {
+    let mut messages = Vec::new();
+    for (_, value) in storage.scan_prefix(&get_queued_message_key(recipient_did, ""))? {
+        let message: QueuedMessage = serde_json::from_str(&value)?;
+        if next.is_none() || next == Some(message.next.as_str()) {
+            messages.push(message);
+        }
+    }
+    messages.sort_by(|a, b| {
+        a.received_time
+            .cmp(&b.received_time)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    Ok(messages)
+}
+
+/// Deletes messages from the queue of a recipient, unknown ids are ignored.
+///
+/// # Arguments
+/// * `storage` - storage to delete the messages from
+/// * `recipient_did` - DID of the recipient
+/// * `ids` - ids of the messages to delete
+///
+/// # Returns
+/// * `usize` - number of deleted messages
+pub fn delete_queued_messages(
+    storage: &dyn DidCommStorage,
+    recipient_did: &str,
+    ids: &[String],
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let operations: Vec<BatchOperation> = storage
+        .scan_prefix(&get_queued_message_key(recipient_did, ""))?
+        .into_iter()
+        .filter(|(key, _)| {
+            ids.iter()
+                .any(|id| *key == get_queued_message_key(recipient_did, id))
+        })
+        .map(|(key, _)| BatchOperation::Delete { key })
+        .collect();
+    storage.write_batch(&operations)?;
+
+    Ok(operations.len())
+}
+
+fn get_queued_message_key(recipient_did: &str, id: &str) -> String {
+    format!(
+        "{QUEUED_MESSAGE_PREFIX}{}_{id}",
+        hex::encode(recipient_did.as_bytes())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryStorage;
+
+    #[test]
+    fn can_queue_messages_per_recipient() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = MemoryStorage::new();
+        let first = queue_message(&storage, "did:key:z1", "did:key:z1", "{}")?;
+        let second = queue_message(&storage, "did:key:z1", "did:key:z3#key-1", "{}")?;
+        queue_message(&storage, "did:key:z2", "did:key:z2", "{}")?;
+
+        let queued = get_queued_messages(&storage, "did:key:z1", None)?;
+        assert_eq!(queued.len(), 2);
+        assert!(queued.contains(&first) && queued.contains(&second));
+        assert_eq!(
+            get_queued_messages(&storage, "did:key:z1", Some("did:key:z3#key-1"))?,
+            vec![second.clone()],
+        );
+
+        assert_eq!(
+            delete_queued_messages(&storage, "did:key:z1", &[first.id, "unknown".to_string()])?,
+            1,
+        );
+        assert_eq!(
+            get_queued_messages(&storage, "did:key:z1", None)?,
+            vec![second]
+        );
+        assert_eq!(get_queued_messages(&storage, "did:key:z2", None)?.len(), 1);
+
+        // queues of DIDs, that start with the DID of another recipient, are kept apart
+        queue_message(&storage, "did:key:z1_a", "did:key:z1_a", "{}")?;
+        assert_eq!(get_queued_messages(&storage, "did:key:z1", None)?.len(), 1);
+        assert_eq!(
+            get_queued_messages(&storage, "did:key:z1_a", None)?.len(),
+            1
+        );
+
+        Ok(())
+    }
+}
@@ -1,14 +1,191 @@
+use std::rc::Rc;
+
+mod batch;
+mod encrypted;
+#[allow(dead_code)] // usage depends on enabled storage backends
+mod keys;
+mod memory;
+mod message_queue;
+mod retention;
+mod tenant;
+mod wallet;
+
+pub use batch::BatchStorage;
+pub use encrypted::{EncryptedStorage, MasterKey};
+pub use memory::MemoryStorage;
+pub use message_queue::{delete_queued_messages, get_queued_messages, queue_message};
+pub use retention::{apply_retention_policy, purge_thread, RetentionPolicy};
+pub use tenant::{create_tenant, delete_tenant, list_tenants, TenantStorage};
+pub use wallet::{export_wallet, import_wallet};
+
 cfg_if::cfg_if! {
     if #[cfg(target_arch = "wasm32")] {
         mod local_storage;
-        pub(crate) use local_storage::*;
+        pub use local_storage::LocalStorage;
     } else if #[cfg(feature = "debug_db")] {
         mod debug;
-        pub use debug::*;
+        pub use debug::DebugStorage;
     } else {
-        #[cfg(feature = "state_storage")]
-        mod rocks_db;
-        #[cfg(feature = "state_storage")]
-        pub(crate) use rocks_db::*;
+        #[cfg(feature = "redis_storage")]
+        mod redis_db;
+        #[cfg(feature = "redis_storage")]
+        pub use redis_db::RedisStorage;
+        #[cfg(feature = "sled_storage")]
+        mod sled_db;
+        #[cfg(feature = "sled_storage")]
+        pub use sled_db::SledStorage;
+        #[cfg(feature = "sqlite_storage")]
+        mod sqlite;
+        #[cfg(feature = "sqlite_storage")]
+        pub use sqlite::{SqliteStorage, ThreadState};
+    }
+}
+
+/// Location and key namespace of a storage backend.
+///
+/// The meaning of `path` depends on the backend: a directory for sled, a file for SQLite and the
+/// debug db, a connection url for redis and a key prefix for browser local storage. The
+/// `namespace` separates the data of multiple instances using the same location.
+#[derive(Clone, Debug, Default)]
+pub struct StorageConfig {
+    pub path: Option<String>,
+    pub namespace: Option<String>,
+}
+
+/// Write operation, that is applied as part of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchOperation {
+    Put { key: String, value: String },
+    Delete { key: String },
+}
+
+/// Key value store used to persist communication keypairs, protocol states and raw messages.
+///
+/// Implement this trait and pass it to `VadeDidComm::new` with `VadeDidCommConfig::storage` to
+/// use a custom storage, e.g. a database that is shared by multiple services. If no storage is
+/// passed, the backend selected by the enabled features will be used (see `get_default_storage`).
+pub trait DidCommStorage {
+    /// Gets a value from the storage.
+    ///
+    /// # Arguments
+    /// * `key` - key to load the value for
+    ///
+    /// # Returns
+    /// * `String` - stored value, returns an error if the key does not exist
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Writes a value into the storage, an existing value will be overwritten.
+    ///
+    /// # Arguments
+    /// * `key` - key to save the value for
+    /// * `value` - string value to store
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Deletes a value from the storage, deleting a missing key is not an error.
+    ///
+    /// # Arguments
+    /// * `key` - key to delete the value for
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Gets all entries with a key starting with the given prefix.
+    ///
+    /// # Arguments
+    /// * `prefix` - key prefix to match entries for
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - matching key value pairs
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>>;
+
+    /// Applies multiple writes at once. The provided backends apply all operations atomically
+    /// (except browser local storage), the default implementation applies them one by one.
+    ///
+    /// # Arguments
+    /// * `operations` - writes to apply
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        for operation in operations.iter() {
+            match operation {
+                BatchOperation::Put { key, value } => self.put(key, value)?,
+                BatchOperation::Delete { key } => self.delete(key)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Shares a storage, e.g. to keep access to an `EncryptedStorage` passed to a plugin instance.
+impl<T: DidCommStorage + ?Sized> DidCommStorage for Rc<T> {
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.as_ref().get(key)
+    }
+
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_ref().put(key, value)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_ref().delete(key)
+    }
+
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        self.as_ref().scan_prefix(prefix)
+    }
+
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_ref().write_batch(operations)
+    }
+}
+
+/// Gets a list of values matching with key prefix from a storage.
+///
+/// # Arguments
+/// * `storage` - storage to search in
+/// * `prefix` - key prefix to match values for
+///
+/// # Returns
+/// * `Vec<String>` - stored values
+pub fn search_db_keys(
+    storage: &dyn DidCommStorage,
+    prefix: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    Ok(storage
+        .scan_prefix(prefix)?
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect())
+}
+
+/// Creates the storage backend selected by the enabled features. Falls back to an in memory
+/// storage if no persistent backend is available.
+///
+/// # Arguments
+/// * `config` - location and namespace of the storage, backend defaults are used if not set
+///
+/// # Returns
+/// * `Box<dyn DidCommStorage>` - new storage instance
+pub fn get_default_storage(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    config: &StorageConfig,
+) -> Result<Box<dyn DidCommStorage>, Box<dyn std::error::Error>> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            Ok(Box::new(LocalStorage::from_config(config)))
+        } else if #[cfg(feature = "debug_db")] {
+            Ok(Box::new(DebugStorage::from_config(config)))
+        } else if #[cfg(feature = "redis_storage")] {
+            Ok(Box::new(RedisStorage::from_config(config)?))
+        } else if #[cfg(feature = "sqlite_storage")] {
+            Ok(Box::new(SqliteStorage::from_config(config)?))
+        } else if #[cfg(feature = "sled_storage")] {
+            Ok(Box::new(SledStorage::from_config(config)?))
+        } else {
+            Ok(Box::new(MemoryStorage::new()))
+        }
     }
 }
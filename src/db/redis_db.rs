@@ -0,0 +1,255 @@
+//! Redis storage.
+//!
+//! Allows multiple instances of a service to share the same state, e.g. to finish a DID exchange on
+//! another instance than the one it was started on. Keys of a namespace are stored with a
+//! `{namespace}:` prefix.
+
+use std::sync::{Mutex, MutexGuard};
+
+use redis::{Client, Connection};
+
+use super::{BatchOperation, DidCommStorage, StorageConfig};
+
+const REDIS_URL_ENV: &str = "VADE_DIDCOMM_REDIS_URL";
+const REDIS_DEFAULT_URL: &str = "redis://127.0.0.1:6379/";
+const SCAN_BATCH_SIZE: u64 = 1000;
+
+pub struct RedisStorage {
+    connection: Mutex<Connection>,
+    key_prefix: String,
+}
+
+impl RedisStorage {
+    /// Creates a new redis storage, connecting to the url from the `VADE_DIDCOMM_REDIS_URL`
+    /// environment variable or to a local redis server if it is not set.
+    pub fn new() -> Result<RedisStorage, Box<dyn std::error::Error>> {
+        RedisStorage::from_config(&StorageConfig::default())
+    }
+
+    /// Creates a new redis storage, connecting to the configured url. Falls back to the
+    /// `VADE_DIDCOMM_REDIS_URL` environment variable or to a local redis server if no url is set.
+    ///
+    /// # Arguments
+    /// * `config` - redis url and namespace
+    pub fn from_config(config: &StorageConfig) -> Result<RedisStorage, Box<dyn std::error::Error>> {
+        let url = match &config.path {
+            Some(url) => url.to_owned(),
+            None => std::env::var(REDIS_URL_ENV).unwrap_or_else(|_| REDIS_DEFAULT_URL.to_string()),
+        };
+        RedisStorage::open(&url, config.namespace.as_deref())
+    }
+
+    /// Creates a new redis storage, connecting to the given url.
+    ///
+    /// # Arguments
+    /// * `url` - redis connection url, e.g. `redis://127.0.0.1:6379/`
+    /// * `namespace` - namespace to prefix keys with, keys are not prefixed if `None`
+    pub fn open(
+        url: &str,
+        namespace: Option<&str>,
+    ) -> Result<RedisStorage, Box<dyn std::error::Error>> {
+        let connection = Client::open(url)?.get_connection()?;
+
+        Ok(RedisStorage {
+            connection: Mutex::new(connection),
+            key_prefix: namespace
+                .map(|namespace| format!("{namespace}:"))
+                .unwrap_or_default(),
+        })
+    }
+
+    fn prefixed(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, Box<dyn std::error::Error>> {
+        self.connection
+            .lock()
+            .map_err(|e| Box::from(format!("could not lock redis connection: {e}")))
+    }
+}
+
+/// Escapes glob special characters, so a key prefix can be used in a `MATCH` pattern.
+fn escape_pattern(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        if matches!(character, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+impl DidCommStorage for RedisStorage {
+    /// Gets a value from redis.
+    ///
+    /// # Arguments
+    /// * `key` - key to load the value for
+    ///
+    /// # Returns
+    /// * `String` - stored value
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let value: Option<String> = redis::cmd("GET")
+            .arg(self.prefixed(key))
+            .query(&mut *self.lock()?)?;
+
+        value.ok_or_else(|| Box::from(format!("{key} not found")))
+    }
+
+    /// Write a value into redis.
+    ///
+    /// # Arguments
+    /// * `key` - key to save the value for
+    /// * `value` - string value to store
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        redis::cmd("SET")
+            .arg(self.prefixed(key))
+            .arg(value)
+            .query::<()>(&mut *self.lock()?)?;
+
+        Ok(())
+    }
+
+    /// Deletes a value from redis.
+    ///
+    /// # Arguments
+    /// * `key` - key to delete the value for
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        redis::cmd("DEL")
+            .arg(self.prefixed(key))
+            .query::<()>(&mut *self.lock()?)?;
+
+        Ok(())
+    }
+
+    /// Gets a list of entries matching with key prefix from redis. Keys are matched on the server
+    /// with `SCAN`, values are fetched afterwards with `MGET`.
+    ///
+    /// # Arguments
+    /// * `prefix` - key prefix to match values for
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - stored key value pairs
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let mut connection = self.lock()?;
+        let pattern = format!("{}*", escape_pattern(&self.prefixed(prefix)));
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(SCAN_BATCH_SIZE)
+                .query(&mut *connection)?;
+            keys.extend(batch);
+            if next_cursor == 0 {
+                break;
+            }
+            cursor = next_cursor;
+        }
+        // SCAN may return a key multiple times
+        keys.sort();
+        keys.dedup();
+
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let values: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query(&mut *connection)?;
+
+        // entries deleted between SCAN and MGET are skipped
+        Ok(keys
+            .into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| {
+                Some((key.strip_prefix(&self.key_prefix)?.to_string(), value?))
+            })
+            .collect())
+    }
+
+    /// Applies multiple writes atomically to redis with `MULTI`/`EXEC`.
+    ///
+    /// # Arguments
+    /// * `operations` - writes to apply
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut pipeline = redis::pipe();
+        pipeline.atomic();
+        for operation in operations.iter() {
+            match operation {
+                BatchOperation::Put { key, value } => pipeline
+                    .cmd("SET")
+                    .arg(self.prefixed(key))
+                    .arg(value)
+                    .ignore(),
+                BatchOperation::Delete { key } => {
+                    pipeline.cmd("DEL").arg(self.prefixed(key)).ignore()
+                }
+            };
+        }
+        pipeline.query::<()>(&mut *self.lock()?)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate utilities;
+
+    use utilities::redis_stand_in::start_redis_stand_in;
+
+    use super::*;
+
+    #[test]
+    fn can_use_redis_db() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = RedisStorage::open(&start_redis_stand_in()?, None)?;
+        storage.put("test_1", "helloooo")?;
+        storage.put("test_2", "world")?;
+        storage.put("test*3", "escaped")?;
+        storage.put("other", "value")?;
+
+        assert_eq!(storage.get("test_1")?, "helloooo");
+        assert_eq!(storage.scan_prefix("test_")?.len(), 2);
+        assert_eq!(storage.scan_prefix("test*")?.len(), 1);
+
+        storage.delete("test_1")?;
+        assert!(storage.get("test_1").is_err());
+
+        storage.write_batch(&[
+            BatchOperation::Put {
+                key: "test_1".to_string(),
+                value: "batched".to_string(),
+            },
+            BatchOperation::Delete {
+                key: "test_2".to_string(),
+            },
+        ])?;
+        assert_eq!(storage.get("test_1")?, "batched");
+        assert!(storage.get("test_2").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_separate_redis_db_namespaces() -> Result<(), Box<dyn std::error::Error>> {
+        let url = start_redis_stand_in()?;
+        let storage_a = RedisStorage::open(&url, Some("namespace_a"))?;
+        let storage_b = RedisStorage::open(&url, Some("namespace_b"))?;
+        storage_a.put("test_1", "a")?;
+        storage_b.put("test_1", "b")?;
+
+        assert_eq!(storage_a.get("test_1")?, "a");
+        assert_eq!(storage_b.get("test_1")?, "b");
+        assert_eq!(
+            storage_a.scan_prefix("test_")?,
+            vec![("test_1".to_string(), "a".to_string())]
+        );
+
+        Ok(())
+    }
+}
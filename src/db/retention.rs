@@ -0,0 +1,216 @@
+//! Retention of finished protocol threads.
+//!
+//! A thread is finished, when all of its state records are in a final state of their protocol.
+//! The age of a thread is determined by the `created_time` of its latest stored message.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use super::{
+    keys::{parse_storage_key, StorageKey, PROTOCOLS},
+    BatchOperation,
+    DidCommStorage,
+};
+use crate::{datatypes::ExtendedMessage, utils::get_now};
+
+/// States, after which no further messages are exchanged in a thread.
+const FINAL_STATES: [(&str, &[&str]); 7] = [
+    (
+        "coordinate_mediation",
+        &["SendGrant", "ReceiveGrant", "SendDeny", "ReceiveDeny"],
+    ),
+    (
+        "did_exchange",
+        &[
+            "SendComplete",
+            "ReceiveComplete",
+            "SendProblemReport",
+            "ReceiveProblemReport",
+        ],
+    ),
+    ("discover_features", &["SendDisclose", "ReceiveDisclose"]),
+    ("issue_credential", &["Acknowledged", "ProblemReported"]),
+    (
+        "pickup",
+        &[
+            "SendStatus",
+            "ReceiveStatus",
+            "SendDelivery",
+            "ReceiveDelivery",
+        ],
+    ),
+    ("present_proof", &["Acknowledged", "ProblemReported"]),
+    (
+        "presentation_exchange",
+        &["SendPresentation", "ReceivePresentation"],
+    ),
+];
+
+/// Defines how long data of finished threads is kept.
+#[derive(Clone, Debug)]
+pub struct RetentionPolicy {
+    /// time to keep raw messages after the latest message of a finished thread
+    pub finished_thread_retention: Duration,
+    /// delete protocol states and data of expired threads as well, otherwise only raw messages
+    /// are deleted
+    pub purge_thread_state: bool,
+}
+
+/// Deletes all raw messages, protocol states and protocol data of a thread at once.
+///
+/// # Arguments
+/// * `storage` - storage to delete the thread from
+/// * `thid` - thread id
+///
+/// # Returns
+/// * `usize` - number of deleted entries
+pub fn purge_thread(
+    storage: &dyn DidCommStorage,
+    thid: &str,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let operations = get_delete_operations(get_thread_keys(storage, thid)?);
+    storage.write_batch(&operations)?;
+
+    Ok(operations.len())
+}
+
+/// Deletes the data of all finished threads, that have expired according to a retention policy.
+/// The data of all expired threads is deleted at once.
+///
+/// # Arguments
+/// * `storage` - storage to delete the threads from
+/// * `policy` - retention policy to apply
+///
+/// # Returns
+/// * `Vec<String>` - ids of threads, that data has been deleted for
+pub fn apply_retention_policy(
+    storage: &dyn DidCommStorage,
+    policy: &RetentionPolicy,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    // a thread may have a state record for each participant, e.g. in tests or for local agents
+    let mut threads: BTreeMap<String, bool> = BTreeMap::new();
+    for (protocol, final_states) in FINAL_STATES.iter() {
+        for (key, state) in storage.scan_prefix(&format!("{protocol}_state_"))? {
+            if let StorageKey::ThreadState { thid, .. } = parse_storage_key(&key) {
+                let finished = threads.entry(thid.to_string()).or_insert(true);
+                *finished &= final_states.contains(&state.as_str());
+            }
+        }
+    }
+
+    let now = get_now()?;
+    let mut purged = Vec::new();
+    let mut keys = Vec::new();
+    for (thid, finished) in threads.into_iter() {
+        if !finished {
+            continue;
+        }
+        let messages = get_message_keys(storage, &thid)?;
+        let last_activity = messages
+            .iter()
+            .filter_map(|(_, value)| {
+                serde_json::from_str::<ExtendedMessage>(value)
+                    .ok()?
+                    .created_time
+            })
+            .max();
+        let retention = policy.finished_thread_retention.as_secs();
+        if !matches!(last_activity, Some(time) if now.saturating_sub(time) >= retention) {
+            continue;
+        }
+
+        if policy.purge_thread_state {
+            keys.extend(get_thread_keys(storage, &thid)?);
+        } else {
+            keys.extend(messages.into_iter().map(|(key, _)| key));
+        }
+        purged.push(thid);
+    }
+    storage.write_batch(&get_delete_operations(keys))?;
+
+    Ok(purged)
+}
+
+/// Gets the keys of all raw messages, protocol states and protocol data of a thread.
+fn get_thread_keys(
+    storage: &dyn DidCommStorage,
+    thid: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut keys = get_message_keys(storage, thid)?
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect::<Vec<String>>();
+    for protocol in PROTOCOLS.iter() {
+        for (key, _) in storage.scan_prefix(&format!("{protocol}_"))? {
+            let belongs_to_thread = match parse_storage_key(&key) {
+                StorageKey::ThreadState { thid: key_thid, .. }
+                | StorageKey::ProtocolData { thid: key_thid, .. } => key_thid == thid,
+                _ => false,
+            };
+            if belongs_to_thread {
+                keys.push(key);
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+fn get_delete_operations(keys: Vec<String>) -> Vec<BatchOperation> {
+    keys.into_iter()
+        .map(|key| BatchOperation::Delete { key })
+        .collect()
+}
+
+/// Gets the raw messages of a thread. Keys are checked after scanning, as the prefix of a thread
+/// id also matches thread ids starting with it.
+fn get_message_keys(
+    storage: &dyn DidCommStorage,
+    thid: &str,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    Ok(storage
+        .scan_prefix(&format!("message_{thid}_"))?
+        .into_iter()
+        .filter(|(key, _)| match parse_storage_key(key) {
+            StorageKey::Message { thid: key_thid, .. } => key_thid == thid,
+            _ => false,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryStorage;
+
+    #[test]
+    fn can_purge_expired_threads() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = MemoryStorage::new();
+        let old_message = r#"{"type":"test","created_time":100}"#;
+        storage.put("message_thread1_msg1", old_message)?;
+        storage.put("message_thread1_a_msg1", old_message)?;
+        storage.put("issue_credential_state_Holder_thread1", "Acknowledged")?;
+        storage.put("issue_credential_state_Issuer_thread1", "Acknowledged")?;
+        storage.put(
+            "issue_credential_did:a_did:b_SendIssueCredential_thread1",
+            "{}",
+        )?;
+        storage.put(
+            "issue_credential_state_Holder_thread1_a",
+            "ReceiveOfferCredential",
+        )?;
+
+        let policy = RetentionPolicy {
+            finished_thread_retention: Duration::from_secs(60),
+            purge_thread_state: false,
+        };
+        assert_eq!(apply_retention_policy(&storage, &policy)?, vec!["thread1"]);
+        assert!(storage.get("message_thread1_msg1").is_err());
+        assert!(storage.get("message_thread1_a_msg1").is_ok());
+        assert!(storage.get("issue_credential_state_Holder_thread1").is_ok());
+
+        assert_eq!(purge_thread(&storage, "thread1")?, 3);
+        assert_eq!(storage.scan_prefix("issue_credential_")?.len(), 1);
+
+        Ok(())
+    }
+}
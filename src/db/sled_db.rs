@@ -0,0 +1,187 @@
+//! Embedded storage based on sled.
+//!
+//! sled locks its database directory, so a database can only be opened once per process. Opened
+//! databases are therefore cached and shared between all `SledStorage` instances using the same
+//! path. Namespaces are stored in separate trees of the database.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use super::{BatchOperation, DidCommStorage, StorageConfig};
+
+const SLED_DB_PATH: &str = "./.didcomm_sled_db";
+
+static OPEN_DBS: Mutex<BTreeMap<String, sled::Db>> = Mutex::new(BTreeMap::new());
+
+pub struct SledStorage {
+    tree: sled::Tree,
+}
+
+impl SledStorage {
+    /// Creates a new sled storage, located at the default path.
+    pub fn new() -> Result<SledStorage, Box<dyn std::error::Error>> {
+        SledStorage::open(SLED_DB_PATH, None)
+    }
+
+    /// Creates a new sled storage, located at the configured path.
+    ///
+    /// # Arguments
+    /// * `config` - database directory and namespace, defaults are used if not set
+    pub fn from_config(config: &StorageConfig) -> Result<SledStorage, Box<dyn std::error::Error>> {
+        SledStorage::open(
+            config.path.as_deref().unwrap_or(SLED_DB_PATH),
+            config.namespace.as_deref(),
+        )
+    }
+
+    /// Creates a new sled storage, located at the given path.
+    ///
+    /// # Arguments
+    /// * `path` - directory to store the database in
+    /// * `namespace` - name of the tree to store the data in, uses the default tree if `None`
+    pub fn open(
+        path: &str,
+        namespace: Option<&str>,
+    ) -> Result<SledStorage, Box<dyn std::error::Error>> {
+        let mut open_dbs = OPEN_DBS
+            .lock()
+            .map_err(|e| format!("could not lock sled db cache: {e}"))?;
+        let db = match open_dbs.get(path) {
+            Some(db) => db.clone(),
+            None => {
+                let db = sled::open(path)?;
+                open_dbs.insert(path.to_string(), db.clone());
+                db
+            }
+        };
+
+        let tree = match namespace {
+            Some(namespace) => db.open_tree(namespace)?,
+            None => (*db).clone(),
+        };
+
+        Ok(SledStorage { tree })
+    }
+}
+
+impl DidCommStorage for SledStorage {
+    /// Gets a value from the sled db.
+    ///
+    /// # Arguments
+    /// * `key` - key to load the value for
+    ///
+    /// # Returns
+    /// * `String` - stored value
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        match self.tree.get(key) {
+            Ok(Some(result)) => Ok(String::from_utf8(result.to_vec())?),
+            Ok(None) => Err(format!("{key} not found").into()),
+            Err(e) => Err(format!("Error while loading key: {key}, {e}").into()),
+        }
+    }
+
+    /// Write a value into the sled db.
+    ///
+    /// # Arguments
+    /// * `key` - key to save the value for
+    /// * `value` - string value to store
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.tree.insert(key, value.as_bytes())?;
+        self.tree.flush()?;
+
+        Ok(())
+    }
+
+    /// Deletes a value from the sled db.
+    ///
+    /// # Arguments
+    /// * `key` - key to delete the value for
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.tree.remove(key)?;
+        self.tree.flush()?;
+
+        Ok(())
+    }
+
+    /// Gets a list of entries matching with key prefix from the sled db.
+    ///
+    /// # Arguments
+    /// * `prefix` - key prefix to match values for
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - stored key value pairs
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let mut entries: Vec<(String, String)> = Vec::new();
+        for entry in self.tree.scan_prefix(prefix) {
+            let (key, value) = entry?;
+            entries.push((
+                String::from_utf8(key.to_vec())?,
+                String::from_utf8(value.to_vec())?,
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    /// Applies multiple writes atomically to the sled db.
+    ///
+    /// # Arguments
+    /// * `operations` - writes to apply
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut batch = sled::Batch::default();
+        for operation in operations.iter() {
+            match operation {
+                BatchOperation::Put { key, value } => {
+                    batch.insert(key.as_bytes(), value.as_bytes())
+                }
+                BatchOperation::Delete { key } => batch.remove(key.as_bytes()),
+            }
+        }
+        self.tree.apply_batch(batch)?;
+        self.tree.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_use_sled_db() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = SledStorage::new()?;
+        storage.put("test1", "helloooo")?;
+        let result = storage.get("test1")?;
+
+        assert_eq!(result, "helloooo");
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_separate_sled_db_namespaces() -> Result<(), Box<dyn std::error::Error>> {
+        let storage_a = SledStorage::open(SLED_DB_PATH, Some("namespace_a"))?;
+        let storage_b = SledStorage::open(SLED_DB_PATH, Some("namespace_b"))?;
+        storage_a.put("test3", "a")?;
+        storage_b.put("test3", "b")?;
+
+        assert_eq!(storage_a.get("test3")?, "a");
+        assert_eq!(storage_b.get("test3")?, "b");
+        assert_eq!(storage_a.scan_prefix("test3")?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_open_sled_db_multiple_times() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = SledStorage::new()?;
+        storage.put("test2", "shared")?;
+
+        assert_eq!(SledStorage::new()?.get("test2")?, "shared");
+
+        Ok(())
+    }
+}
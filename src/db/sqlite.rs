@@ -0,0 +1,788 @@
+//! SQLite storage.
+//!
+//! All entries are stored in a generic `entries` table, so the key value layout used by the
+//! protocol handlers keeps working. Additionally entries with a known key layout are mapped into
+//! indexed tables, that allow to query messages, threads, connections and protocol states by DID,
+//! protocol, state and time. Every row belongs to a namespace, so multiple storages can share one
+//! database file.
+//!
+//! Keys of tenant data are indexed without their `tenant:{tenant_id}:` prefix, the tenant id is
+//! stored in the `tenant` column of the indexed tables (empty for data without tenant).
+//!
+//! Indexing is unavailable if the storage is wrapped by an `EncryptedStorage`: encrypted values
+//! are only written to the `entries` table, so the indexed tables stay empty and the query
+//! functions do not return any values.
+
+use std::sync::{Mutex, MutexGuard};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{
+    encrypted::is_encrypted,
+    keys::{parse_storage_key, StorageKey},
+    tenant::split_tenant_key,
+    BatchOperation,
+    DidCommStorage,
+    StorageConfig,
+};
+use crate::{
+    datatypes::{CommKeyPair, ExtendedMessage},
+    utils::get_now,
+};
+
+const SQLITE_DB_PATH: &str = "./.didcomm_sqlite_db";
+
+const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS entries (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (namespace, key)
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        tenant TEXT NOT NULL,
+        thid TEXT NOT NULL,
+        id TEXT NOT NULL,
+        type TEXT,
+        from_did TEXT,
+        to_did TEXT,
+        created_time INTEGER,
+        PRIMARY KEY (namespace, key),
+        FOREIGN KEY (namespace, key) REFERENCES entries(namespace, key) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS messages_thid ON messages(namespace, tenant, thid);
+    CREATE INDEX IF NOT EXISTS messages_type ON messages(namespace, tenant, type);
+    CREATE INDEX IF NOT EXISTS messages_from_did ON messages(namespace, tenant, from_did);
+    CREATE INDEX IF NOT EXISTS messages_to_did ON messages(namespace, tenant, to_did);
+    CREATE INDEX IF NOT EXISTS messages_created_time ON messages(namespace, tenant, created_time);
+    CREATE TABLE IF NOT EXISTS connections (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        tenant TEXT NOT NULL,
+        from_did TEXT NOT NULL,
+        to_did TEXT NOT NULL,
+        key_agreement_key TEXT,
+        target_key_agreement_key TEXT,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (namespace, key),
+        FOREIGN KEY (namespace, key) REFERENCES entries(namespace, key) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS connections_from_did ON connections(namespace, tenant, from_did);
+    CREATE INDEX IF NOT EXISTS connections_to_did ON connections(namespace, tenant, to_did);
+    CREATE TABLE IF NOT EXISTS threads (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        tenant TEXT NOT NULL,
+        protocol TEXT NOT NULL,
+        user_type TEXT NOT NULL,
+        thid TEXT NOT NULL,
+        state TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (namespace, key),
+        FOREIGN KEY (namespace, key) REFERENCES entries(namespace, key) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS threads_protocol_state
+        ON threads(namespace, tenant, protocol, state);
+    CREATE INDEX IF NOT EXISTS threads_thid ON threads(namespace, tenant, thid);
+    CREATE INDEX IF NOT EXISTS threads_updated_at ON threads(namespace, tenant, updated_at);
+    CREATE TABLE IF NOT EXISTS protocol_states (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        tenant TEXT NOT NULL,
+        protocol TEXT NOT NULL,
+        from_did TEXT NOT NULL,
+        to_did TEXT NOT NULL,
+        state TEXT NOT NULL,
+        thid TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (namespace, key),
+        FOREIGN KEY (namespace, key) REFERENCES entries(namespace, key) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS protocol_states_protocol_state
+        ON protocol_states(namespace, tenant, protocol, state);
+    CREATE INDEX IF NOT EXISTS protocol_states_from_did
+        ON protocol_states(namespace, tenant, from_did);
+    CREATE INDEX IF NOT EXISTS protocol_states_to_did ON protocol_states(namespace, tenant, to_did);
+    CREATE INDEX IF NOT EXISTS protocol_states_thid ON protocol_states(namespace, tenant, thid);
+    CREATE INDEX IF NOT EXISTS protocol_states_updated_at
+        ON protocol_states(namespace, tenant, updated_at);
+"#;
+
+/// Current state of a protocol thread, as stored in the `threads` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadState {
+    pub protocol: String,
+    pub user_type: String,
+    pub thid: String,
+    pub state: String,
+    pub updated_at: u64,
+}
+
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+    namespace: String,
+}
+
+impl SqliteStorage {
+    /// Creates a new SQLite storage, located at the default path.
+    pub fn new() -> Result<SqliteStorage, Box<dyn std::error::Error>> {
+        SqliteStorage::open(SQLITE_DB_PATH, None)
+    }
+
+    /// Creates a new SQLite storage, located at the configured path.
+    ///
+    /// # Arguments
+    /// * `config` - database file and namespace, defaults are used if not set
+    pub fn from_config(
+        config: &StorageConfig,
+    ) -> Result<SqliteStorage, Box<dyn std::error::Error>> {
+        SqliteStorage::open(
+            config.path.as_deref().unwrap_or(SQLITE_DB_PATH),
+            config.namespace.as_deref(),
+        )
+    }
+
+    /// Creates a new SQLite storage, located at the given path.
+    ///
+    /// # Arguments
+    /// * `path` - file to store the database in
+    /// * `namespace` - namespace to store the data in, uses the default namespace if `None`
+    pub fn open(
+        path: &str,
+        namespace: Option<&str>,
+    ) -> Result<SqliteStorage, Box<dyn std::error::Error>> {
+        SqliteStorage::from_connection(Connection::open(path)?, namespace)
+    }
+
+    /// Creates a new SQLite storage, that only lives in memory.
+    pub fn open_in_memory() -> Result<SqliteStorage, Box<dyn std::error::Error>> {
+        SqliteStorage::from_connection(Connection::open_in_memory()?, None)
+    }
+
+    fn from_connection(
+        connection: Connection,
+        namespace: Option<&str>,
+    ) -> Result<SqliteStorage, Box<dyn std::error::Error>> {
+        connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(SqliteStorage {
+            connection: Mutex::new(connection),
+            namespace: namespace.unwrap_or_default().to_string(),
+        })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, Box<dyn std::error::Error>> {
+        self.connection
+            .lock()
+            .map_err(|e| Box::from(format!("could not lock sqlite connection: {e}")))
+    }
+
+    /// Gets all messages sent from or to a DID.
+    ///
+    /// # Arguments
+    /// * `did` - DID to search messages for
+    /// * `tenant_id` - tenant to search messages of, `None` for messages without tenant
+    ///
+    /// # Returns
+    /// * `Vec<String>` - stored raw messages, ordered by creation time
+    pub fn get_messages_by_did(
+        &self,
+        did: &str,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.query_values(
+            "SELECT e.value FROM messages m
+             JOIN entries e ON e.namespace = m.namespace AND e.key = m.key
+             WHERE m.namespace = ?1 AND m.tenant = ?2 AND (m.from_did = ?3 OR m.to_did = ?3)
+             ORDER BY m.created_time",
+            params![self.namespace, tenant_id.unwrap_or_default(), did],
+        )
+    }
+
+    /// Gets all messages of a thread.
+    ///
+    /// # Arguments
+    /// * `thid` - thread id to search messages for
+    /// * `tenant_id` - tenant to search messages of, `None` for messages without tenant
+    ///
+    /// # Returns
+    /// * `Vec<String>` - stored raw messages, ordered by creation time
+    pub fn get_messages_by_thid(
+        &self,
+        thid: &str,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.query_values(
+            "SELECT e.value FROM messages m
+             JOIN entries e ON e.namespace = m.namespace AND e.key = m.key
+             WHERE m.namespace = ?1 AND m.tenant = ?2 AND m.thid = ?3 ORDER BY m.created_time",
+            params![self.namespace, tenant_id.unwrap_or_default(), thid],
+        )
+    }
+
+    /// Gets all messages created within a time range.
+    ///
+    /// # Arguments
+    /// * `from` - start of the range in seconds since unix epoch (inclusive)
+    /// * `to` - end of the range in seconds since unix epoch (inclusive)
+    /// * `tenant_id` - tenant to search messages of, `None` for messages without tenant
+    ///
+    /// # Returns
+    /// * `Vec<String>` - stored raw messages, ordered by creation time
+    pub fn get_messages_by_time_range(
+        &self,
+        from: u64,
+        to: u64,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.query_values(
+            "SELECT e.value FROM messages m
+             JOIN entries e ON e.namespace = m.namespace AND e.key = m.key
+             WHERE m.namespace = ?1 AND m.tenant = ?2 AND m.created_time BETWEEN ?3 AND ?4
+             ORDER BY m.created_time",
+            params![
+                self.namespace,
+                tenant_id.unwrap_or_default(),
+                from as i64,
+                to as i64
+            ],
+        )
+    }
+
+    /// Gets all communication keypairs for connections from or to a DID.
+    ///
+    /// # Arguments
+    /// * `did` - DID to search connections for
+    /// * `tenant_id` - tenant to search connections of, `None` for connections without tenant
+    ///
+    /// # Returns
+    /// * `Vec<String>` - stored stringified `CommKeyPair`s
+    pub fn get_connections_by_did(
+        &self,
+        did: &str,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.query_values(
+            "SELECT e.value FROM connections c
+             JOIN entries e ON e.namespace = c.namespace AND e.key = c.key
+             WHERE c.namespace = ?1 AND c.tenant = ?2 AND (c.from_did = ?3 OR c.to_did = ?3)
+             ORDER BY c.updated_at",
+            params![self.namespace, tenant_id.unwrap_or_default(), did],
+        )
+    }
+
+    /// Gets the current states of all threads of a protocol, optionally filtered by state.
+    ///
+    /// # Arguments
+    /// * `protocol` - protocol name, e.g. `issue_credential`
+    /// * `state` - only return threads in this state
+    /// * `tenant_id` - tenant to search threads of, `None` for threads without tenant
+    ///
+    /// # Returns
+    /// * `Vec<ThreadState>` - thread states, ordered by last update
+    pub fn get_threads_by_protocol(
+        &self,
+        protocol: &str,
+        state: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<ThreadState>, Box<dyn std::error::Error>> {
+        self.query_threads(
+            "SELECT protocol, user_type, thid, state, updated_at FROM threads
+             WHERE namespace = ?1 AND tenant = ?2 AND protocol = ?3 AND (?4 IS NULL OR state = ?4)
+             ORDER BY updated_at",
+            params![
+                self.namespace,
+                tenant_id.unwrap_or_default(),
+                protocol,
+                state
+            ],
+        )
+    }
+
+    /// Gets the states of all threads updated within a time range.
+    ///
+    /// # Arguments
+    /// * `from` - start of the range in seconds since unix epoch (inclusive)
+    /// * `to` - end of the range in seconds since unix epoch (inclusive)
+    /// * `tenant_id` - tenant to search threads of, `None` for threads without tenant
+    ///
+    /// # Returns
+    /// * `Vec<ThreadState>` - thread states, ordered by last update
+    pub fn get_threads_by_time_range(
+        &self,
+        from: u64,
+        to: u64,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<ThreadState>, Box<dyn std::error::Error>> {
+        self.query_threads(
+            "SELECT protocol, user_type, thid, state, updated_at FROM threads
+             WHERE namespace = ?1 AND tenant = ?2 AND updated_at BETWEEN ?3 AND ?4
+             ORDER BY updated_at",
+            params![
+                self.namespace,
+                tenant_id.unwrap_or_default(),
+                from as i64,
+                to as i64
+            ],
+        )
+    }
+
+    /// Gets the data stored by a protocol for a state, e.g. all received credential offers.
+    ///
+    /// # Arguments
+    /// * `protocol` - protocol name, e.g. `issue_credential`
+    /// * `state` - state the data has been stored for
+    /// * `did` - only return data exchanged with this DID
+    /// * `tenant_id` - tenant to search data of, `None` for data without tenant
+    ///
+    /// # Returns
+    /// * `Vec<String>` - stored protocol data, ordered by last update
+    pub fn get_protocol_data_by_state(
+        &self,
+        protocol: &str,
+        state: &str,
+        did: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.query_values(
+            "SELECT e.value FROM protocol_states p
+             JOIN entries e ON e.namespace = p.namespace AND e.key = p.key
+             WHERE p.namespace = ?1 AND p.tenant = ?2 AND p.protocol = ?3 AND p.state = ?4
+             AND (?5 IS NULL OR p.from_did = ?5 OR p.to_did = ?5)
+             ORDER BY p.updated_at",
+            params![
+                self.namespace,
+                tenant_id.unwrap_or_default(),
+                protocol,
+                state,
+                did
+            ],
+        )
+    }
+
+    fn query_values(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let connection = self.lock()?;
+        let mut statement = connection.prepare(sql)?;
+        let values = statement
+            .query_map(params, |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(values)
+    }
+
+    fn query_threads(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<ThreadState>, Box<dyn std::error::Error>> {
+        let connection = self.lock()?;
+        let mut statement = connection.prepare(sql)?;
+        let threads = statement
+            .query_map(params, |row| {
+                Ok(ThreadState {
+                    protocol: row.get(0)?,
+                    user_type: row.get(1)?,
+                    thid: row.get(2)?,
+                    state: row.get(3)?,
+                    updated_at: row.get::<_, i64>(4)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<ThreadState>, _>>()?;
+
+        Ok(threads)
+    }
+}
+
+/// Writes an entry and its index entry.
+fn write_entry(
+    transaction: &rusqlite::Transaction,
+    namespace: &str,
+    key: &str,
+    value: &str,
+    now: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    transaction.execute(
+        "INSERT INTO entries (namespace, key, value, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(namespace, key)
+         DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![namespace, key, value, now],
+    )?;
+    write_index(transaction, namespace, key, value, now)
+}
+
+/// Writes the index entry for a key with a known layout. Keys of tenant data are parsed without
+/// their tenant prefix. Encrypted values can not be parsed, so their index entries are removed.
+fn write_index(
+    transaction: &rusqlite::Transaction,
+    namespace: &str,
+    key: &str,
+    value: &str,
+    now: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if is_encrypted(value) {
+        for table in ["messages", "connections", "threads", "protocol_states"] {
+            transaction.execute(
+                &format!("DELETE FROM {table} WHERE namespace = ?1 AND key = ?2"),
+                params![namespace, key],
+            )?;
+        }
+        return Ok(());
+    }
+
+    let (tenant_id, unprefixed_key) = split_tenant_key(key);
+    let tenant = tenant_id.unwrap_or_default();
+    match parse_storage_key(unprefixed_key) {
+        StorageKey::Message { thid, id } => {
+            let message: Option<ExtendedMessage> = serde_json::from_str(value).ok();
+            let message = message.as_ref();
+            transaction.execute(
+                "INSERT OR REPLACE INTO messages
+                 (namespace, key, tenant, thid, id, type, from_did, to_did, created_time)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    namespace,
+                    key,
+                    tenant,
+                    thid,
+                    id,
+                    message.map(|m| &m.r#type),
+                    message.and_then(|m| m.from.as_ref()),
+                    message.and_then(|m| m.to.as_ref()?.first()),
+                    message.and_then(|m| m.created_time).map(|t| t as i64),
+                ],
+            )?;
+        }
+        StorageKey::CommKeyPair { from, to } => {
+            let keypair: Option<CommKeyPair> = serde_json::from_str(value).ok();
+            let keypair = keypair.as_ref();
+            transaction.execute(
+                "INSERT OR REPLACE INTO connections
+                 (namespace, key, tenant, from_did, to_did, key_agreement_key,
+                 target_key_agreement_key, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    namespace,
+                    key,
+                    tenant,
+                    from,
+                    to,
+                    keypair.map(|k| &k.key_agreement_key),
+                    keypair.map(|k| &k.target_key_agreement_key),
+                    now,
+                ],
+            )?;
+        }
+        StorageKey::ThreadState {
+            protocol,
+            user_type,
+            thid,
+        } => {
+            transaction.execute(
+                "INSERT OR REPLACE INTO threads
+                 (namespace, key, tenant, protocol, user_type, thid, state, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![namespace, key, tenant, protocol, user_type, thid, value, now],
+            )?;
+        }
+        StorageKey::ProtocolData {
+            protocol,
+            from,
+            to,
+            state,
+            thid,
+        } => {
+            transaction.execute(
+                "INSERT OR REPLACE INTO protocol_states
+                 (namespace, key, tenant, protocol, from_did, to_did, state, thid, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![namespace, key, tenant, protocol, from, to, state, thid, now],
+            )?;
+        }
+        StorageKey::KeyAgreementKey { .. } | StorageKey::Other => {}
+    }
+
+    Ok(())
+}
+
+impl DidCommStorage for SqliteStorage {
+    /// Gets a value from the SQLite db.
+    ///
+    /// # Arguments
+    /// * `key` - key to load the value for
+    ///
+    /// # Returns
+    /// * `String` - stored value
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.lock()?
+            .query_row(
+                "SELECT value FROM entries WHERE namespace = ?1 AND key = ?2",
+                params![self.namespace, key],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| Box::from(format!("{key} not found")))
+    }
+
+    /// Write a value into the SQLite db and updates the index tables.
+    ///
+    /// # Arguments
+    /// * `key` - key to save the value for
+    /// * `value` - string value to store
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let now = get_now()? as i64;
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction()?;
+        write_entry(&transaction, &self.namespace, key, value, now)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Deletes a value from the SQLite db, index entries are removed as well.
+    ///
+    /// # Arguments
+    /// * `key` - key to delete the value for
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.lock()?.execute(
+            "DELETE FROM entries WHERE namespace = ?1 AND key = ?2",
+            params![self.namespace, key],
+        )?;
+
+        Ok(())
+    }
+
+    /// Gets a list of entries matching with key prefix from the SQLite db.
+    ///
+    /// # Arguments
+    /// * `prefix` - key prefix to match values for
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - stored key value pairs
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let connection = self.lock()?;
+        // compare by range instead of `LIKE`, so `_` and `%` in keys are not treated as wildcards
+        let mut statement = connection.prepare(
+            "SELECT key, value FROM entries
+             WHERE namespace = ?1 AND key >= ?2 AND substr(key, 1, ?3) = ?2
+             ORDER BY key",
+        )?;
+        let entries = statement
+            .query_map(
+                params![self.namespace, prefix, prefix.chars().count() as i64],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?
+            .collect::<Result<Vec<(String, String)>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Applies multiple writes in a single transaction to the SQLite db.
+    ///
+    /// # Arguments
+    /// * `operations` - writes to apply
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        let now = get_now()? as i64;
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction()?;
+        for operation in operations.iter() {
+            match operation {
+                BatchOperation::Put { key, value } => {
+                    write_entry(&transaction, &self.namespace, key, value, now)?
+                }
+                BatchOperation::Delete { key } => {
+                    transaction.execute(
+                        "DELETE FROM entries WHERE namespace = ?1 AND key = ?2",
+                        params![self.namespace, key],
+                    )?;
+                }
+            }
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{create_tenant, EncryptedStorage, MasterKey, TenantStorage};
+
+    #[test]
+    fn can_use_sqlite_db() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = SqliteStorage::open_in_memory()?;
+        storage.put("test1", "helloooo")?;
+        storage.put("test2", "world")?;
+        storage.put("other", "value")?;
+
+        assert_eq!(storage.get("test1")?, "helloooo");
+        assert_eq!(storage.scan_prefix("test")?.len(), 2);
+
+        storage.delete("test1")?;
+        assert!(storage.get("test1").is_err());
+
+        storage.write_batch(&[
+            BatchOperation::Put {
+                key: "test1".to_string(),
+                value: "batched".to_string(),
+            },
+            BatchOperation::Delete {
+                key: "test2".to_string(),
+            },
+        ])?;
+        assert_eq!(storage.get("test1")?, "batched");
+        assert!(storage.get("test2").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_query_indexed_tables() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = SqliteStorage::open_in_memory()?;
+        storage.put(
+            "message_thread1_msg1",
+            r#"{"type":"test","from":"did:a","to":["did:b"],"created_time":100}"#,
+        )?;
+        storage.put(
+            "message_thread2_msg2",
+            r#"{"type":"test","from":"did:b","to":["did:c"],"created_time":200}"#,
+        )?;
+        storage.put(
+            "issue_credential_state_Holder_thread1",
+            "ReceiveOfferCredential",
+        )?;
+        storage.put(
+            "issue_credential_did:a_did:b_ReceiveOfferCredential_thread1",
+            "{}",
+        )?;
+
+        assert_eq!(storage.get_messages_by_did("did:b", None)?.len(), 2);
+        assert_eq!(storage.get_messages_by_did("did:c", None)?.len(), 1);
+        assert_eq!(storage.get_messages_by_thid("thread1", None)?.len(), 1);
+        assert_eq!(storage.get_messages_by_time_range(150, 250, None)?.len(), 1);
+        assert_eq!(
+            storage.get_threads_by_protocol(
+                "issue_credential",
+                Some("ReceiveOfferCredential"),
+                None
+            )?[0]
+                .thid,
+            "thread1"
+        );
+        assert_eq!(
+            storage
+                .get_protocol_data_by_state(
+                    "issue_credential",
+                    "ReceiveOfferCredential",
+                    None,
+                    None
+                )?
+                .len(),
+            1
+        );
+
+        storage.delete("message_thread1_msg1")?;
+        assert_eq!(storage.get_messages_by_thid("thread1", None)?.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_index_tenant_and_encrypted_data() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join(format!("vade_didcomm_{}.db", uuid::Uuid::new_v4()));
+        let path = path.to_str().ok_or("invalid temp path")?;
+        let message = r#"{"type":"test","from":"did:a","to":["did:b"],"created_time":100}"#;
+        let storage = SqliteStorage::open(path, None)?;
+        create_tenant(&storage, "tenant1")?;
+        let tenant1 = TenantStorage::open(&storage, Some("tenant1"))?;
+        tenant1.put("message_thread1_msg1", message)?;
+        tenant1.put(
+            "issue_credential_state_Holder_thread1",
+            "ReceiveOfferCredential",
+        )?;
+
+        assert_eq!(
+            storage
+                .get_messages_by_thid("thread1", Some("tenant1"))?
+                .len(),
+            1
+        );
+        assert_eq!(
+            storage.get_messages_by_did("did:a", Some("tenant1"))?.len(),
+            1
+        );
+        assert_eq!(storage.get_messages_by_thid("thread1", None)?.len(), 0);
+        assert_eq!(
+            storage.get_threads_by_protocol("issue_credential", None, Some("tenant1"))?[0].state,
+            "ReceiveOfferCredential"
+        );
+
+        // encrypted values are not indexed, index entries of overwritten values are removed
+        let encrypted = EncryptedStorage::new(
+            Box::new(SqliteStorage::open(path, None)?),
+            MasterKey::new("key1", [1u8; 32])?,
+            Vec::new(),
+        );
+        create_tenant(&encrypted, "tenant2")?;
+        let tenant2 = TenantStorage::open(&encrypted, Some("tenant2"))?;
+        tenant2.put("message_thread2_msg2", message)?;
+        assert!(tenant2.get("message_thread2_msg2")?.contains("did:a"));
+        assert_eq!(
+            storage
+                .get_messages_by_thid("thread2", Some("tenant2"))?
+                .len(),
+            0
+        );
+
+        storage.put("tenant:tenant2:message_thread3_msg3", message)?;
+        assert_eq!(
+            storage
+                .get_messages_by_thid("thread3", Some("tenant2"))?
+                .len(),
+            1
+        );
+        tenant2.put("message_thread3_msg3", message)?;
+        assert_eq!(
+            storage
+                .get_messages_by_thid("thread3", Some("tenant2"))?
+                .len(),
+            0
+        );
+
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_separate_sqlite_db_namespaces() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join(format!("vade_didcomm_{}.db", uuid::Uuid::new_v4()));
+        let path = path.to_str().ok_or("invalid temp path")?;
+        let storage_a = SqliteStorage::open(path, Some("namespace_a"))?;
+        let storage_b = SqliteStorage::open(path, Some("namespace_b"))?;
+        storage_a.put("message_thread1_msg1", r#"{"type":"test","from":"did:a"}"#)?;
+        storage_b.put("message_thread1_msg1", r#"{"type":"test","from":"did:b"}"#)?;
+
+        assert!(storage_a.get("message_thread1_msg1")?.contains("did:a"));
+        assert!(storage_b.get("message_thread1_msg1")?.contains("did:b"));
+        assert_eq!(storage_a.get_messages_by_did("did:b", None)?.len(), 0);
+
+        storage_a.delete("message_thread1_msg1")?;
+        assert_eq!(storage_b.scan_prefix("message_")?.len(), 1);
+
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+}
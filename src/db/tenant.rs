@@ -0,0 +1,261 @@
+//! Tenant separation within a single storage.
+//!
+//! Tenants are registered with a `tenant_{tenant_id}` entry, their data is stored with a
+//! `tenant:{tenant_id}:` key prefix, so keys, threads and messages of one tenant can neither be
+//! read nor overwritten by another one.
+
+use super::{BatchOperation, DidCommStorage};
+use crate::{datatypes::Tenant, utils::get_now};
+
+const TENANT_REGISTRY_PREFIX: &str = "tenant_";
+const TENANT_KEY_PREFIX: &str = "tenant:";
+
+/// View on a storage, that only contains the data of a single tenant. Uses the storage as it is
+/// if no tenant is given, except for the data of tenants and the tenant registry, that can neither
+/// be scanned nor written without tenant.
+pub struct TenantStorage<'a> {
+    storage: &'a dyn DidCommStorage,
+    key_prefix: String,
+}
+
+impl<'a> TenantStorage<'a> {
+    /// Opens the data of a tenant, the tenant has to be created with `create_tenant` before.
+    ///
+    /// # Arguments
+    /// * `storage` - storage containing the data of all tenants
+    /// * `tenant_id` - tenant to open the data for, `None` for data without tenant
+    ///
+    /// # Returns
+    /// * `TenantStorage` - storage view of the tenant
+    pub fn open(
+        storage: &'a dyn DidCommStorage,
+        tenant_id: Option<&str>,
+    ) -> Result<TenantStorage<'a>, Box<dyn std::error::Error>> {
+        let key_prefix = match tenant_id {
+            Some(tenant_id) => {
+                storage
+                    .get(&get_registry_key(tenant_id))
+                    .map_err(|_| format!("tenant {tenant_id} does not exist"))?;
+                get_key_prefix(tenant_id)
+            }
+            None => String::new(),
+        };
+
+        Ok(TenantStorage {
+            storage,
+            key_prefix,
+        })
+    }
+
+    fn get_prefixed_key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
+
+    /// Checks if a key belongs to the data of a tenant or to the tenant registry, while the view
+    /// has been opened without tenant.
+    fn is_hidden_key(&self, key: &str) -> bool {
+        self.key_prefix.is_empty()
+            && (key.starts_with(TENANT_KEY_PREFIX) || key.starts_with(TENANT_REGISTRY_PREFIX))
+    }
+
+    fn ensure_writable_key(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.is_hidden_key(key) {
+            return Err(Box::from(format!(
+                "{key} belongs to the tenant data and can not be written without tenant"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl DidCommStorage for TenantStorage<'_> {
+    fn get(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.storage.get(&self.get_prefixed_key(key))
+    }
+
+    fn put(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable_key(key)?;
+        self.storage.put(&self.get_prefixed_key(key), value)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.storage.delete(&self.get_prefixed_key(key))
+    }
+
+    /// Gets a list of entries matching with key prefix from the tenants data.
+    ///
+    /// # Arguments
+    /// * `prefix` - key prefix to match values for
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - stored key value pairs, keys are returned without tenant prefix
+    fn scan_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        Ok(self
+            .storage
+            .scan_prefix(&self.get_prefixed_key(prefix))?
+            .into_iter()
+            .filter(|(key, _)| !self.is_hidden_key(key))
+            .map(|(key, value)| (key[self.key_prefix.len()..].to_string(), value))
+            .collect())
+    }
+
+    fn write_batch(&self, operations: &[BatchOperation]) -> Result<(), Box<dyn std::error::Error>> {
+        let operations = operations
+            .iter()
+            .map(|operation| match operation {
+                BatchOperation::Put { key, value } => {
+                    self.ensure_writable_key(key)?;
+                    Ok(BatchOperation::Put {
+                        key: self.get_prefixed_key(key),
+                        value: value.to_owned(),
+                    })
+                }
+                BatchOperation::Delete { key } => Ok(BatchOperation::Delete {
+                    key: self.get_prefixed_key(key),
+                }),
+            })
+            .collect::<Result<Vec<BatchOperation>, Box<dyn std::error::Error>>>()?;
+
+        self.storage.write_batch(&operations)
+    }
+}
+
+fn get_registry_key(tenant_id: &str) -> String {
+    format!("{TENANT_REGISTRY_PREFIX}{tenant_id}")
+}
+
+fn get_key_prefix(tenant_id: &str) -> String {
+    format!("{TENANT_KEY_PREFIX}{tenant_id}:")
+}
+
+/// Splits a key of the underlying storage into the tenant id and the key used by the tenant.
+///
+/// # Arguments
+/// * `key` - key, that may start with a tenant prefix
+///
+/// # Returns
+/// * `(Option<&str>, &str)` - tenant id, `None` for data without tenant, and key without prefix
+#[allow(dead_code)] // usage depends on enabled storage backends
+pub(super) fn split_tenant_key(key: &str) -> (Option<&str>, &str) {
+    key.strip_prefix(TENANT_KEY_PREFIX)
+        .and_then(|tenant_key| tenant_key.split_once(':'))
+        .map_or((None, key), |(tenant_id, key)| (Some(tenant_id), key))
+}
+
+/// Registers a new tenant.
+///
+/// # Arguments
+/// * `storage` - storage to register the tenant in
+/// * `tenant_id` - id of the new tenant, must not be empty or contain `:`
+///
+/// # Returns
+/// * `Tenant` - registered tenant
+pub fn create_tenant(
+    storage: &dyn DidCommStorage,
+    tenant_id: &str,
+) -> Result<Tenant, Box<dyn std::error::Error>> {
+    if tenant_id.is_empty() || tenant_id.contains(':') {
+        return Err(Box::from(format!(
+            "invalid tenant id '{tenant_id}', must not be empty or contain ':'"
+        )));
+    }
+    if storage.get(&get_registry_key(tenant_id)).is_ok() {
+        return Err(Box::from(format!("tenant {tenant_id} already exists")));
+    }
+
+    let tenant = Tenant {
+        tenant_id: tenant_id.to_string(),
+        created_at: get_now()?,
+    };
+    storage.put(
+        &get_registry_key(tenant_id),
+        &serde_json::to_string(&tenant)?,
+    )?;
+
+    Ok(tenant)
+}
+
+/// Gets all registered tenants.
+///
+/// # Arguments
+/// * `storage` - storage to search tenants in
+///
+/// # Returns
+/// * `Vec<Tenant>` - registered tenants
+pub fn list_tenants(
+    storage: &dyn DidCommStorage,
+) -> Result<Vec<Tenant>, Box<dyn std::error::Error>> {
+    storage
+        .scan_prefix(TENANT_REGISTRY_PREFIX)?
+        .into_iter()
+        .map(|(_, value)| Ok(serde_json::from_str(&value)?))
+        .collect()
+}
+
+/// Deletes a tenant and all of its data at once.
+///
+/// # Arguments
+/// * `storage` - storage to delete the tenant from
+/// * `tenant_id` - id of the tenant to delete
+///
+/// # Returns
+/// * `usize` - number of deleted entries, without the tenant registration itself
+pub fn delete_tenant(
+    storage: &dyn DidCommStorage,
+    tenant_id: &str,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let tenant_storage = TenantStorage::open(storage, Some(tenant_id))?;
+    let mut operations: Vec<BatchOperation> = tenant_storage
+        .scan_prefix("")?
+        .into_iter()
+        .map(|(key, _)| BatchOperation::Delete {
+            key: tenant_storage.get_prefixed_key(&key),
+        })
+        .collect();
+    let deleted = operations.len();
+    operations.push(BatchOperation::Delete {
+        key: get_registry_key(tenant_id),
+    });
+    storage.write_batch(&operations)?;
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryStorage;
+
+    #[test]
+    fn can_separate_tenants() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = MemoryStorage::new();
+        create_tenant(&storage, "tenant1")?;
+        create_tenant(&storage, "tenant2")?;
+        assert!(create_tenant(&storage, "tenant1").is_err());
+        assert!(TenantStorage::open(&storage, Some("tenant3")).is_err());
+
+        let tenant1 = TenantStorage::open(&storage, Some("tenant1"))?;
+        let tenant2 = TenantStorage::open(&storage, Some("tenant2"))?;
+        tenant1.put("message_1", "a")?;
+        tenant2.put("message_1", "b")?;
+
+        assert_eq!(tenant1.get("message_1")?, "a");
+        assert_eq!(
+            tenant2.scan_prefix("message_")?,
+            vec![("message_1".to_string(), "b".to_string())]
+        );
+        assert!(storage.get("message_1").is_err());
+        assert_eq!(list_tenants(&storage)?.len(), 2);
+
+        assert_eq!(delete_tenant(&storage, "tenant1")?, 1);
+        assert!(storage.get("tenant:tenant1:message_1").is_err());
+        assert_eq!(tenant2.get("message_1")?, "b");
+        assert_eq!(list_tenants(&storage)?[0].tenant_id, "tenant2");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! Export and import of all data of a storage.
+//!
+//! Archives contain all entries of a storage as JSON object, encrypted with XChaCha20Poly1305. The
+//! key is derived from a password with Argon2id, its parameters are stored in the archive, so they
+//! can be changed without breaking the import of older archives.
+
+use std::collections::BTreeMap;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    Key,
+    XChaCha20Poly1305,
+    XNonce,
+};
+use rand::RngCore;
+
+use super::{BatchOperation, DidCommStorage};
+use crate::datatypes::{WalletArchive, WalletArchiveKdf};
+
+const WALLET_ARCHIVE_VERSION: u32 = 1;
+const KDF_MEMORY_COST: u32 = 19456;
+const KDF_TIME_COST: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+
+/// Exports all entries of a storage into a password encrypted archive.
+///
+/// # Arguments
+/// * `storage` - storage to export
+/// * `password` - password to encrypt the archive with
+///
+/// # Returns
+/// * `WalletArchive` - encrypted archive
+pub fn export_wallet(
+    storage: &dyn DidCommStorage,
+    password: &str,
+) -> Result<WalletArchive, Box<dyn std::error::Error>> {
+    let entries: BTreeMap<String, String> = storage.scan_prefix("")?.into_iter().collect();
+
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let kdf = WalletArchiveKdf {
+        salt: hex::encode(salt),
+        memory_cost: KDF_MEMORY_COST,
+        time_cost: KDF_TIME_COST,
+        parallelism: KDF_PARALLELISM,
+    };
+
+    let ciphertext = XChaCha20Poly1305::new(&derive_key(password, &kdf)?)
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: serde_json::to_string(&entries)?.as_bytes(),
+                aad: &get_associated_data(WALLET_ARCHIVE_VERSION),
+            },
+        )
+        .map_err(|_| "could not encrypt wallet archive")?;
+
+    Ok(WalletArchive {
+        version: WALLET_ARCHIVE_VERSION,
+        kdf,
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+/// Imports all entries of an archive into a storage at once, existing entries with the same keys
+/// are overwritten.
+///
+/// # Arguments
+/// * `storage` - storage to import the entries into
+/// * `archive` - archive created with `export_wallet`
+/// * `password` - password the archive has been encrypted with
+///
+/// # Returns
+/// * `usize` - number of imported entries
+pub fn import_wallet(
+    storage: &dyn DidCommStorage,
+    archive: &WalletArchive,
+    password: &str,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    if archive.version != WALLET_ARCHIVE_VERSION {
+        return Err(Box::from(format!(
+            "unsupported wallet archive version {}",
+            archive.version
+        )));
+    }
+    let nonce = hex::decode(&archive.nonce)?;
+    if nonce.len() != NONCE_LENGTH {
+        return Err(Box::from("invalid nonce in wallet archive"));
+    }
+
+    let plaintext = XChaCha20Poly1305::new(&derive_key(password, &archive.kdf)?)
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &hex::decode(&archive.ciphertext)?,
+                aad: &get_associated_data(archive.version),
+            },
+        )
+        .map_err(|_| "could not decrypt wallet archive, password may be wrong")?;
+    let entries: BTreeMap<String, String> = serde_json::from_slice(&plaintext)?;
+
+    let operations: Vec<BatchOperation> = entries
+        .into_iter()
+        .map(|(key, value)| BatchOperation::Put { key, value })
+        .collect();
+    storage.write_batch(&operations)?;
+
+    Ok(operations.len())
+}
+
+fn derive_key(password: &str, kdf: &WalletArchiveKdf) -> Result<Key, Box<dyn std::error::Error>> {
+    let params = Params::new(kdf.memory_cost, kdf.time_cost, kdf.parallelism, Some(32))
+        .map_err(|e| format!("invalid wallet archive kdf parameters: {e}"))?;
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), &hex::decode(&kdf.salt)?, &mut key)
+        .map_err(|e| format!("could not derive wallet archive key: {e}"))?;
+
+    Ok(key)
+}
+
+fn get_associated_data(version: u32) -> Vec<u8> {
+    format!("vade-didcomm-wallet-v{version}").into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{create_tenant, MemoryStorage, TenantStorage};
+
+    #[test]
+    fn can_export_and_import_wallet() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = MemoryStorage::new();
+        storage.put("comm_keypair_did:a_did:b", "keypair")?;
+        storage.put("message_thread1_msg1", "message")?;
+
+        let archive = export_wallet(&storage, "secret")?;
+        assert!(!archive.ciphertext.contains(&hex::encode("keypair")));
+
+        let imported = MemoryStorage::new();
+        assert!(import_wallet(&imported, &archive, "wrong").is_err());
+        assert_eq!(import_wallet(&imported, &archive, "secret")?, 2);
+        assert_eq!(imported.get("comm_keypair_did:a_did:b")?, "keypair");
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_export_wallet_without_tenant_data() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = MemoryStorage::new();
+        create_tenant(&storage, "tenant1")?;
+        TenantStorage::open(&storage, Some("tenant1"))?.put("message_thread1_msg1", "tenant")?;
+        let default_storage = TenantStorage::open(&storage, None)?;
+        default_storage.put("message_thread2_msg2", "default")?;
+
+        let archive = export_wallet(&default_storage, "secret")?;
+        let imported = MemoryStorage::new();
+        assert_eq!(import_wallet(&imported, &archive, "secret")?, 1);
+        assert_eq!(imported.get("message_thread2_msg2")?, "default");
+
+        // tenant data can not be imported without tenant
+        let archive = export_wallet(&storage, "secret")?;
+        assert!(import_wallet(&default_storage, &archive, "secret").is_err());
+
+        Ok(())
+    }
+}
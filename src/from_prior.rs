@@ -0,0 +1,216 @@
+//! `from_prior` JWTs, that senders attach to messages to rotate their DID.
+//!
+//! The JWT is signed with an Ed25519 key of the prior DID (`iss`) and names the new DID (`sub`),
+//! that has to be the `from` of the message.
+
+use data_encoding::BASE64URL_NOPAD;
+use ed25519_dalek::{Signer, Verifier};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    datatypes::FromPriorOptions,
+    receive_policy::ReceivePolicy,
+    resolver::{get_did, resolve_did, DidResolver},
+};
+
+/// Signature algorithm of `from_prior` JWTs.
+const FROM_PRIOR_ALGORITHM: &str = "EdDSA";
+
+/// Claims of a `from_prior` JWT.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FromPriorClaims {
+    /// new DID of the sender
+    pub sub: String,
+    /// prior DID of the sender
+    pub iss: String,
+    /// time of the rotation in seconds since epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<u64>,
+}
+
+/// Header of a `from_prior` JWT.
+#[derive(Serialize, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+}
+
+/// Creates a `from_prior` JWT, that rotates the prior DID of the options to a new DID.
+///
+/// # Arguments
+/// * `options` - prior DID and its Ed25519 signing key
+/// * `did` - new DID of the sender
+/// * `now` - current time in seconds since epoch
+///
+/// # Returns
+/// * `String` - compact serialized JWT
+pub fn create_from_prior(
+    options: &FromPriorOptions,
+    did: &str,
+    now: u64,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let header = JwtHeader {
+        alg: FROM_PRIOR_ALGORITHM.to_string(),
+        kid: Some(
+            options
+                .prior_kid
+                .clone()
+                .unwrap_or_else(|| options.prior_did.clone()),
+        ),
+        typ: Some("JWT".to_string()),
+    };
+    let claims = FromPriorClaims {
+        sub: did.to_string(),
+        iss: options.prior_did.clone(),
+        iat: Some(now),
+    };
+    let signing_input = format!(
+        "{}.{}",
+        BASE64URL_NOPAD.encode(serde_json::to_string(&header)?.as_bytes()),
+        BASE64URL_NOPAD.encode(serde_json::to_string(&claims)?.as_bytes()),
+    );
+    let secret = ed25519_dalek::SecretKey::from_bytes(&options.prior_signing_secret)?;
+    let keypair = ed25519_dalek::Keypair {
+        public: ed25519_dalek::PublicKey::from(&secret),
+        secret,
+    };
+    let signature = keypair.sign(signing_input.as_bytes());
+
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        BASE64URL_NOPAD.encode(&signature.to_bytes()),
+    ))
+}
+
+/// Verifies a `from_prior` JWT of a received message. The JWT has to be signed with an Ed25519
+/// key of the prior DID and its new DID has to be the sender of the message.
+///
+/// # Arguments
+/// * `from_prior` - compact serialized JWT
+/// * `from` - sender of the message
+/// * `resolver` - resolver for prior DIDs, that can not be resolved locally
+/// * `policy` - receive policy of the plugin instance, used to check the `iat` claim
+/// * `now` - current time in seconds since epoch
+///
+/// # Returns
+/// * `FromPriorClaims` - verified claims
+pub async fn verify_from_prior(
+    from_prior: &str,
+    from: Option<&str>,
+    resolver: Option<&mut (dyn DidResolver + '_)>,
+    policy: &ReceivePolicy,
+    now: u64,
+) -> Result<FromPriorClaims, Box<dyn std::error::Error>> {
+    let (signing_input, encoded_signature) = from_prior
+        .rsplit_once('.')
+        .ok_or("invalid from_prior, expected a compact serialized JWT")?;
+    let (encoded_header, encoded_claims) = signing_input
+        .split_once('.')
+        .ok_or("invalid from_prior, expected a compact serialized JWT")?;
+    let header: JwtHeader =
+        serde_json::from_slice(&BASE64URL_NOPAD.decode(encoded_header.as_bytes())?)?;
+    let claims: FromPriorClaims =
+        serde_json::from_slice(&BASE64URL_NOPAD.decode(encoded_claims.as_bytes())?)?;
+
+    if header.alg != FROM_PRIOR_ALGORITHM {
+        return Err(Box::from(format!(
+            "unsupported from_prior algorithm '{}', only {} is supported",
+            header.alg, FROM_PRIOR_ALGORITHM,
+        )));
+    }
+    if from.map(get_did) != Some(claims.sub.as_str()) {
+        return Err(Box::from(format!(
+            "from_prior rotates to {}, but the message has been sent by {}",
+            claims.sub,
+            from.unwrap_or("an anonymous sender"),
+        )));
+    }
+    if claims.iss == claims.sub {
+        return Err(Box::from("from_prior must rotate to a new DID"));
+    }
+    let latest_iat = now.saturating_add(policy.max_clock_skew.as_secs());
+    if claims.iat.is_some_and(|iat| iat > latest_iat) {
+        return Err(Box::from(format!(
+            "from_prior has been issued after the allowed {}",
+            latest_iat
+        )));
+    }
+
+    let signature = ed25519_dalek::Signature::from_bytes(
+        &BASE64URL_NOPAD.decode(encoded_signature.as_bytes())?,
+    )?;
+    let is_valid = resolve_did(&claims.iss, resolver)
+        .await?
+        .verification_keys
+        .iter()
+        .filter_map(|key| ed25519_dalek::PublicKey::from_bytes(key).ok())
+        .any(|key| key.verify(signing_input.as_bytes(), &signature).is_ok());
+    if !is_valid {
+        return Err(Box::from(format!(
+            "from_prior is not signed with a key of the prior DID {}",
+            claims.iss
+        )));
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn can_create_and_verify_from_prior() -> Result<(), Box<dyn std::error::Error>> {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[3u8; 32])?;
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let prior_did = format!(
+            "did:key:z{}",
+            bs58::encode([&[0xed, 0x01][..], public.as_bytes()].concat()).into_string()
+        );
+        let options = FromPriorOptions {
+            prior_did: prior_did.clone(),
+            prior_kid: None,
+            prior_signing_secret: secret.to_bytes(),
+        };
+        let policy = ReceivePolicy::default();
+        let from_prior = create_from_prior(&options, "did:example:new", 1000)?;
+
+        let claims =
+            verify_from_prior(&from_prior, Some("did:example:new"), None, &policy, 1000).await?;
+        assert_eq!(claims.iss, prior_did);
+        assert_eq!(claims.sub, "did:example:new");
+
+        // the new DID has to be the sender and the JWT must not be issued in the future
+        assert!(
+            verify_from_prior(&from_prior, Some("did:example:other"), None, &policy, 1000)
+                .await
+                .is_err()
+        );
+        assert!(
+            verify_from_prior(&from_prior, Some("did:example:new"), None, &policy, 0)
+                .await
+                .is_err()
+        );
+
+        // JWTs signed with other keys are rejected
+        let forged = create_from_prior(
+            &FromPriorOptions {
+                prior_signing_secret: [4u8; 32],
+                ..options
+            },
+            "did:example:new",
+            1000,
+        )?;
+        assert!(
+            verify_from_prior(&forged, Some("did:example:new"), None, &policy, 1000)
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+}
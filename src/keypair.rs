@@ -1,22 +1,99 @@
-use crate::datatypes::CommKeyPair;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use rand_core::OsRng;
+use x25519_dalek::StaticSecret;
+
 #[cfg(feature = "state_storage")]
-use crate::{db::read_db, db::write_db};
+use crate::db::BatchOperation;
+use crate::{
+    datatypes::{CommKeyPair, KeyPair, KeyType},
+    db::DidCommStorage,
+};
+
+/// Creates a new random key pair of the given type.
+///
+/// # Arguments
+/// * `key_type` - type of the key pair to create
+///
+/// # Returns
+/// * `KeyPair` - new key pair
+pub fn create_key_pair(key_type: KeyType) -> Result<KeyPair, Box<dyn std::error::Error>> {
+    let secret: [u8; 32] = match key_type {
+        KeyType::X25519 => StaticSecret::new(OsRng).to_bytes(),
+        KeyType::Ed25519 => ed25519_dalek::SecretKey::generate(&mut OsRng).to_bytes(),
+        KeyType::Secp256k1 => k256::SecretKey::random(&mut rand::rngs::OsRng)
+            .to_be_bytes()
+            .into(),
+        KeyType::P256 => p256::SecretKey::random(&mut rand::rngs::OsRng)
+            .to_be_bytes()
+            .into(),
+    };
+
+    Ok(KeyPair {
+        key_type,
+        secret,
+        public: get_public_key(key_type, &secret)?,
+    })
+}
+
+/// Gets the public key for a secret key of the given type. Public keys of `secp256k1` and
+/// `P-256` keys are SEC1 compressed.
+///
+/// # Arguments
+/// * `key_type` - type of the secret key
+/// * `secret` - secret key
+///
+/// # Returns
+/// * `Vec<u8>` - public key
+pub fn get_public_key(
+    key_type: KeyType,
+    secret: &[u8; 32],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let public = match key_type {
+        KeyType::X25519 => x25519_dalek::PublicKey::from(&StaticSecret::from(*secret))
+            .to_bytes()
+            .to_vec(),
+        KeyType::Ed25519 => {
+            let secret_key = ed25519_dalek::SecretKey::from_bytes(secret)?;
+            ed25519_dalek::PublicKey::from(&secret_key)
+                .to_bytes()
+                .to_vec()
+        }
+        KeyType::Secp256k1 => k256::SecretKey::from_be_bytes(secret)
+            .map_err(|_| "invalid secp256k1 secret key")?
+            .public_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .to_vec(),
+        KeyType::P256 => p256::SecretKey::from_be_bytes(secret)
+            .map_err(|_| "invalid P-256 secret key")?
+            .public_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .to_vec(),
+    };
+
+    Ok(public)
+}
 
 /// Saves a communication keypair within db for two DIDs (from -> to). Entry key will be
 /// comm_keypair_{from}_{to}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the keypair in
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 /// * `pub_key` - pub key of the active did to communicate with the target did
 /// * `secret_key` - secret key of the active did to encrypt message for the target did
 /// * `target_pub_key` - pub key of the target did (optional nullable, default will be empty string)
 /// * `service_endpoint` - url, where the target did can be reached (optional nullable, default will be empty string)
+/// * `target_routing_keys` - keys of the mediators, that messages to the target did have to be forwarded by
 ///
 /// # Returns
 /// * `CommKeyPair` - new instance of the comm key pair
 #[allow(clippy::too_many_arguments)]
 pub fn save_com_keypair(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
     #[allow(unused_variables)] // may not be used, depending on feature setup
     from_did: &str,
     #[allow(unused_variables)] // may not be used, depending on feature setup
@@ -27,6 +104,7 @@ pub fn save_com_keypair(
     secret_key: &str,
     target_pub_key: Option<String>,
     service_endpoint: Option<String>,
+    target_routing_keys: Vec<String>,
 ) -> Result<CommKeyPair, Box<dyn std::error::Error>> {
     let comm_keypair = CommKeyPair {
         pub_key: String::from(pub_key),
@@ -35,19 +113,22 @@ pub fn save_com_keypair(
         target_key_agreement_key: String::from(target_key_agreement_key),
         target_pub_key: target_pub_key.unwrap_or_else(|| String::from("")),
         target_service_endpoint: service_endpoint.unwrap_or_else(|| String::from("")),
+        target_routing_keys,
     };
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            write_db(
-                &format!("comm_keypair_{}_{}", from_did, to_did),
-                &serde_json::to_string(&comm_keypair)?,
-            )?;
-
-            write_db(
-                &format!("key_agreement_key_{}", key_agreement_key),
-                &serde_json::to_string(&comm_keypair)?,
-            )?;
+            let value = serde_json::to_string(&comm_keypair)?;
+            storage.write_batch(&[
+                BatchOperation::Put {
+                    key: format!("comm_keypair_{}_{}", from_did, to_did),
+                    value: value.clone(),
+                },
+                BatchOperation::Put {
+                    key: format!("key_agreement_key_{}", key_agreement_key),
+                    value,
+                },
+            ])?;
         } else { }
     }
 
@@ -58,6 +139,7 @@ pub fn save_com_keypair(
 /// comm_keypair_{from}_{to}.
 ///
 /// # Arguments
+/// * `storage` - storage to load the keypair from
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 ///
@@ -65,16 +147,12 @@ pub fn save_com_keypair(
 /// * `CommKeyPair` - new instance of the comm key pair
 #[cfg(feature = "state_storage")]
 pub fn get_com_keypair(
+    storage: &dyn DidCommStorage,
     from_did: &str,
     to_did: &str,
 ) -> Result<CommKeyPair, Box<dyn std::error::Error>> {
-    let db_result = read_db(&format!("comm_keypair_{from_did}_{to_did}"))?;
-    log::debug!(
-        "receiving key: comm_keypair_{}_{} with data: {}",
-        from_did,
-        to_did,
-        db_result,
-    );
+    let db_result = storage.get(&format!("comm_keypair_{from_did}_{to_did}"))?;
+    log::debug!("receiving key: comm_keypair_{}_{}", from_did, to_did);
     let comm_keypair: CommKeyPair = serde_json::from_str(&db_result)?;
 
     Ok(comm_keypair)
@@ -84,6 +162,7 @@ pub fn get_com_keypair(
 /// comm_keypair_{from}_{to}.
 ///
 /// # Arguments
+/// * `storage` - storage to load the keypair from
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 ///
@@ -91,15 +170,116 @@ pub fn get_com_keypair(
 /// * `CommKeyPair` - new instance of the comm key pair
 #[cfg(feature = "state_storage")]
 pub fn get_key_agreement_key(
+    storage: &dyn DidCommStorage,
     key_agreement_key: &str,
 ) -> Result<CommKeyPair, Box<dyn std::error::Error>> {
-    let db_result = read_db(&format!("key_agreement_key_{key_agreement_key}"))?;
-    log::debug!(
-        "receving key: key_agreement_key_{} with data: {}",
-        key_agreement_key,
-        db_result,
-    );
+    let db_result = storage.get(&format!("key_agreement_key_{key_agreement_key}"))?;
+    log::debug!("receiving key: key_agreement_key_{}", key_agreement_key);
     let comm_keypair: CommKeyPair = serde_json::from_str(&db_result)?;
 
     Ok(comm_keypair)
 }
+
+/// Ensures, that a DID owns the key of the comm partner in a communication keypair. This is the
+/// case, if the DID is the key agreement DID of the comm partner or if the keypair stored for the
+/// DID uses the same public key.
+///
+/// # Arguments
+/// * `storage` - storage to load the keypairs from
+/// * `comm_keypair` - keypair, whose comm partner key should be owned by the DID
+/// * `receiver_dids` - DIDs of the receiver, that keypairs for the sender DID may be stored for
+/// * `sender_did` - DID, that claims to own the key
+#[cfg(feature = "state_storage")]
+pub fn ensure_key_owner(
+    storage: &dyn DidCommStorage,
+    comm_keypair: &CommKeyPair,
+    receiver_dids: &[String],
+    sender_did: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if comm_keypair.target_pub_key.is_empty() {
+        return Err(Box::from(format!(
+            "could not authenticate sender {}: no public key is known for the comm partner",
+            sender_did,
+        )));
+    }
+    let is_owner = sender_did == comm_keypair.target_key_agreement_key
+        || receiver_dids.iter().any(|receiver_did| {
+            get_com_keypair(storage, receiver_did, sender_did)
+                .map(|keypair| keypair.target_pub_key == comm_keypair.target_pub_key)
+                .unwrap_or(false)
+        });
+    if !is_owner {
+        return Err(Box::from(format!(
+            "could not authenticate sender {}: the message was encrypted with a key of another DID",
+            sender_did,
+        )));
+    }
+
+    Ok(())
+}
+
+/// Moves the communication keypairs of a comm partner from its prior DID to its new DID, after it
+/// rotated its DID with a `from_prior` JWT. `comm_keypair_{from}_{prior}` entries are stored as
+/// `comm_keypair_{from}_{did}`, the comm partner of these and of `key_agreement_key_*` entries
+/// with the prior DID as comm partner is updated to the new DID. All entries are written at once.
+///
+/// # Arguments
+/// * `storage` - storage with the keypairs
+/// * `prior_did` - prior DID of the comm partner
+/// * `did` - new DID of the comm partner
+/// * `target_pub_key` - hex encoded public key of the new DID, the prior key is kept if not set
+///
+/// # Returns
+/// * `usize` - number of updated entries
+#[cfg(feature = "state_storage")]
+pub fn rotate_comm_keypairs(
+    storage: &dyn DidCommStorage,
+    prior_did: &str,
+    did: &str,
+    target_pub_key: Option<&str>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let rotate = |mut comm_keypair: CommKeyPair| -> Result<String, Box<dyn std::error::Error>> {
+        comm_keypair.target_key_agreement_key = did.to_string();
+        if let Some(target_pub_key) = target_pub_key {
+            comm_keypair.target_pub_key = target_pub_key.to_string();
+        }
+        Ok(serde_json::to_string(&comm_keypair)?)
+    };
+
+    let mut operations = Vec::new();
+    // key agreement keys of the keypairs with the prior DID as comm partner
+    let mut key_agreement_keys = Vec::new();
+    let prior_suffix = format!("_{}", prior_did);
+    for (key, value) in storage.scan_prefix("comm_keypair_")? {
+        if let Some(from_did) = key
+            .strip_prefix("comm_keypair_")
+            .and_then(|dids| dids.strip_suffix(&prior_suffix))
+            .map(String::from)
+        {
+            let comm_keypair: CommKeyPair = serde_json::from_str(&value)?;
+            key_agreement_keys.push(comm_keypair.key_agreement_key.clone());
+            operations.push(BatchOperation::Delete { key });
+            operations.push(BatchOperation::Put {
+                key: format!("comm_keypair_{}_{}", from_did, did),
+                value: rotate(comm_keypair)?,
+            });
+        }
+    }
+    for (key, value) in storage.scan_prefix("key_agreement_key_")? {
+        let comm_keypair: CommKeyPair = serde_json::from_str(&value)?;
+        if comm_keypair.target_key_agreement_key == prior_did
+            || key_agreement_keys.contains(&comm_keypair.key_agreement_key)
+        {
+            operations.push(BatchOperation::Put {
+                key,
+                value: rotate(comm_keypair)?,
+            });
+        }
+    }
+    storage.write_batch(&operations)?;
+
+    Ok(operations
+        .iter()
+        .filter(|operation| matches!(operation, BatchOperation::Put { .. }))
+        .count())
+}
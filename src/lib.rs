@@ -4,20 +4,24 @@ extern crate didcomm_rs;
 extern crate ed25519_dalek;
 extern crate hex;
 extern crate log;
-extern crate redis;
 extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
 
+mod anoncrypt;
 pub mod datatypes;
-mod db;
+pub mod db;
+mod from_prior;
 mod keypair;
 mod message;
 mod protocol_handler;
 pub mod protocols;
+mod receive_policy;
+pub mod resolver;
 mod utils;
 mod vade_didcomm;
 
+pub use receive_policy::{ReceivePolicy, DEFAULT_MAX_CLOCK_SKEW};
 pub use utils::*;
 
 pub use crate::vade_didcomm::*;
@@ -1,9 +1,17 @@
+use data_encoding::BASE64URL_NOPAD;
 use didcomm_rs::{
     crypto::{CryptoAlgorithm, SignatureAlgorithm},
+    Jwe,
+    Jws,
     Message as DIDCommMessage,
 };
+use serde_json::Value;
 
-use crate::datatypes::ExtendedMessage;
+use crate::{
+    anoncrypt,
+    datatypes::{EncryptionAlgorithm, ExtendedMessage, KeyType, PackingMode},
+    keypair::get_public_key,
+};
 
 macro_rules! apply_optional {
     ($message:ident, $payload:ident, $payload_arg:ident) => {{
@@ -16,32 +24,105 @@ macro_rules! apply_optional {
     }};
 }
 
-/// Encrypt a stringified plain message, with a given encryption_key and a ed25519_dalek keypair using
-/// DIDComm rs. (checkout vade_didcomm.rs or tests/message.rs for example usage)
-/// Note: Ensure to always create new signing_key pairs to have altering results. Encryption key
-/// should be the shared_secret.
+/// Key pair to sign messages with, `Ed25519` keys sign with EdDSA, `secp256k1` keys with ES256K
+/// and `P-256` keys with ES256.
+pub struct SigningKeyPair {
+    pub key_type: KeyType,
+    pub secret: [u8; 32],
+    pub public: Vec<u8>,
+}
+impl SigningKeyPair {
+    /// Creates a signing key pair from a secret key.
+    ///
+    /// # Arguments
+    /// * `key_type` - type of the secret key, `X25519` keys can not be used for signing
+    /// * `secret` - secret key
+    ///
+    /// # Returns
+    /// * `SigningKeyPair` - key pair with the public key of the secret key
+    pub fn new(key_type: KeyType, secret: [u8; 32]) -> Result<Self, Box<dyn std::error::Error>> {
+        let key_pair = SigningKeyPair {
+            key_type,
+            secret,
+            public: get_public_key(key_type, &secret)?,
+        };
+        key_pair.get_signature_algorithm()?;
+
+        Ok(key_pair)
+    }
+
+    fn get_signature_algorithm(&self) -> Result<SignatureAlgorithm, Box<dyn std::error::Error>> {
+        match self.key_type {
+            KeyType::Ed25519 => Ok(SignatureAlgorithm::EdDsa),
+            KeyType::Secp256k1 => Ok(SignatureAlgorithm::Es256k),
+            KeyType::P256 => Ok(SignatureAlgorithm::Es256),
+            KeyType::X25519 => Err(Box::from("X25519 keys can not be used for signing")),
+        }
+    }
+
+    /// Gets the key in the format expected by the DIDComm rs signers, EdDSA requires the secret
+    /// followed by the public key.
+    fn get_signer_key(&self) -> Vec<u8> {
+        match self.key_type {
+            KeyType::Ed25519 => [&self.secret[..], &self.public].concat(),
+            _ => self.secret.to_vec(),
+        }
+    }
+}
+impl From<ed25519_dalek::Keypair> for SigningKeyPair {
+    fn from(keypair: ed25519_dalek::Keypair) -> Self {
+        SigningKeyPair {
+            key_type: KeyType::Ed25519,
+            secret: keypair.secret.to_bytes(),
+            public: keypair.public.to_bytes().to_vec(),
+        }
+    }
+}
+
+fn get_crypto_algorithm(encryption_algorithm: EncryptionAlgorithm) -> CryptoAlgorithm {
+    match encryption_algorithm {
+        EncryptionAlgorithm::Xc20p => CryptoAlgorithm::XC20P,
+        EncryptionAlgorithm::A256Gcm => CryptoAlgorithm::A256GCM,
+        EncryptionAlgorithm::A256CbcHs512 => CryptoAlgorithm::A256CBC,
+    }
+}
+
+/// Creates a DIDComm rs message for a stringified plain message and applies its headers.
 ///
 /// # Arguments
-/// * `message` - message string (should match message.rs/EncryptedMessage)
-/// * `encryption_secret` - encryption secret key from the sender
-/// * `encryption_target_public` - encryption public key from the receiver - if None it tries to resolve the did
-/// * `sign_keypair` - signing key_pair (ed25519_dalek keypair)
+/// * `message_string` - message string (should match datatypes.rs/ExtendedMessage)
+/// * `encryption_target_publics` - encryption public keys from the receivers
+/// * `encryption_algorithm` - content encryption algorithm
+/// * `include_from` - `false` to leave out the sender in the envelope
 ///
 /// # Returns
-/// * `String` - encrypted stringified message
-pub fn encrypt_message(
+/// * `DIDCommMessage` - message prepared for sealing
+fn prepare_message(
     message_string: &str,
-    encryption_secret: &[u8],
-    encryption_target_public: Option<Vec<u8>>,
-    sign_keypair: Option<ed25519_dalek::Keypair>,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let mut d_message = DIDCommMessage::new()
-        .body(message_string)?
-        .as_jwe(&CryptoAlgorithm::XC20P, encryption_target_public.clone());
+    encryption_target_publics: &[Option<Vec<u8>>],
+    encryption_algorithm: EncryptionAlgorithm,
+    include_from: bool,
+) -> Result<DIDCommMessage, Box<dyn std::error::Error>> {
+    let mut d_message = DIDCommMessage::new().body(message_string)?.as_jwe(
+        &get_crypto_algorithm(encryption_algorithm),
+        encryption_target_publics.first().cloned().flatten(),
+    );
     let message: ExtendedMessage = serde_json::from_str(message_string)?;
 
+    // each recipient entry of the message is created for a `to` DID
+    let recipient_count = message.to.as_ref().map_or(0, Vec::len);
+    if encryption_target_publics.len() > 1 && encryption_target_publics.len() != recipient_count {
+        return Err(Box::from(format!(
+            "got {} encryption public keys for {} recipients, expected one key per `to` DID",
+            encryption_target_publics.len(),
+            recipient_count,
+        )));
+    }
+
     // apply optional headers to known sections, use remaining as custom headers
-    apply_optional!(d_message, message, from);
+    if include_from {
+        apply_optional!(d_message, message, from);
+    }
 
     if let Some(values) = message.to {
         let to: Vec<&str> = values.iter().map(AsRef::as_ref).collect();
@@ -53,18 +134,50 @@ pub fn encrypt_message(
         d_message = d_message.add_header_field(key.to_owned(), val.to_string().to_owned());
     }
 
+    Ok(d_message)
+}
+
+/// Encrypt a stringified plain message, with a given encryption_key and a ed25519_dalek keypair using
+/// DIDComm rs. (checkout vade_didcomm.rs or tests/message.rs for example usage)
+/// Note: Ensure to always create new signing_key pairs to have altering results. Encryption key
+/// should be the shared_secret.
+///
+/// # Arguments
+/// * `message` - message string (should match message.rs/EncryptedMessage)
+/// * `encryption_secret` - encryption secret key from the sender
+/// * `encryption_target_publics` - encryption public keys from the receivers, one per `to` DID - keys
+///   have to be resolved beforehand, see `resolver::resolve_did`
+/// * `sign_keypair` - signing key pair
+/// * `encryption_algorithm` - content encryption algorithm
+///
+/// # Returns
+/// * `String` - encrypted stringified message
+pub fn encrypt_message(
+    message_string: &str,
+    encryption_secret: &[u8],
+    encryption_target_publics: Vec<Option<Vec<u8>>>,
+    sign_keypair: Option<SigningKeyPair>,
+    encryption_algorithm: EncryptionAlgorithm,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut d_message = prepare_message(
+        message_string,
+        &encryption_target_publics,
+        encryption_algorithm,
+        true,
+    )?;
+
     let encrypted;
     if let Some(sign_keypair) = sign_keypair {
         // ensure to set kid to pub key of temporary keypair for encryption / signing
-        d_message = d_message.kid(&hex::encode(sign_keypair.public.to_bytes()));
+        d_message = d_message.kid(&hex::encode(&sign_keypair.public));
 
         // sign and encrypt
         encrypted = d_message
             .seal_signed(
                 encryption_secret,
-                Some(vec![encryption_target_public]),
-                SignatureAlgorithm::EdDsa,
-                &sign_keypair.to_bytes(),
+                Some(encryption_target_publics),
+                sign_keypair.get_signature_algorithm()?,
+                &sign_keypair.get_signer_key(),
             )
             .map_err(|err| {
                 format!(
@@ -75,7 +188,7 @@ pub fn encrypt_message(
     } else {
         // no signing keys, so just encrypt
         encrypted = d_message
-            .seal(encryption_secret, Some(vec![encryption_target_public]))
+            .seal(encryption_secret, Some(encryption_target_publics))
             .map_err(|err| {
                 format!(
                     "could not run seal while encrypting message: {}",
@@ -87,13 +200,222 @@ pub fn encrypt_message(
     Ok(encrypted)
 }
 
+/// Encrypt a stringified plain message for receivers without revealing the sender (anoncrypt).
+/// The message is encrypted with `ECDH-ES+A256KW` key wrapping, the ephemeral key of the key
+/// agreement is added as `epk` to the protected header, so the receivers can decrypt the message
+/// without knowing the sender.
+///
+/// # Arguments
+/// * `message_string` - message string (should match datatypes.rs/ExtendedMessage)
+/// * `encryption_target_publics` - X25519 encryption public keys from the receivers, one per `to`
+///   DID
+/// * `encryption_algorithm` - content encryption algorithm
+///
+/// # Returns
+/// * `String` - encrypted stringified message
+pub fn encrypt_message_anonymously(
+    message_string: &str,
+    encryption_target_publics: Vec<Vec<u8>>,
+    encryption_algorithm: EncryptionAlgorithm,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let message: ExtendedMessage = serde_json::from_str(message_string)?;
+    // each recipient entry of the message is created for a `to` DID
+    let to = message.to.unwrap_or_default();
+    if encryption_target_publics.len() != to.len() {
+        return Err(Box::from(format!(
+            "got {} encryption public keys for {} recipients, expected one key per `to` DID",
+            encryption_target_publics.len(),
+            to.len(),
+        )));
+    }
+    let recipients: Vec<(String, Vec<u8>)> =
+        to.into_iter().zip(encryption_target_publics).collect();
+
+    anoncrypt::encrypt(message_string, &recipients, encryption_algorithm)
+        .map_err(|err| Box::from(format!("could not encrypt message anonymously: {err}")))
+}
+
+/// Decrypt a stringified message encrypted with `encrypt_message_anonymously`.
+///
+/// # Arguments
+/// * `message` - encrypted message string
+/// * `decryption_key` - X25519 decryption secret key from the receiver
+///
+/// # Returns
+/// * `String` - decrypted stringified message
+pub fn decrypt_message_anonymously(
+    message: &str,
+    decryption_key: &[u8; 32],
+) -> Result<String, Box<dyn std::error::Error>> {
+    anoncrypt::decrypt(message, decryption_key)
+        .map_err(|err| Box::from(format!("could not decrypt message: {err}")))
+}
+
+/// Detects the content encryption algorithm of an encrypted message from its `enc` header.
+/// Messages without `enc` header are treated as `XC20P` encrypted.
+///
+/// # Arguments
+/// * `message` - encrypted message string
+///
+/// # Returns
+/// * `EncryptionAlgorithm` - content encryption algorithm of the message
+pub fn get_encryption_algorithm(
+    message: &str,
+) -> Result<EncryptionAlgorithm, Box<dyn std::error::Error>> {
+    let jwe: Jwe = serde_json::from_str(message)?;
+    match jwe.protected.and_then(|header| header.enc) {
+        Some(enc) => EncryptionAlgorithm::from_enc_header(&enc)
+            .ok_or_else(|| Box::from(format!("unsupported content encryption algorithm '{enc}'"))),
+        None => Ok(EncryptionAlgorithm::Xc20p),
+    }
+}
+
+/// Detects how a received message has been packed.
+///
+/// # Arguments
+/// * `message` - received message string
+///
+/// # Returns
+/// * `PackingMode` - packing mode of the message
+pub fn get_packing_mode(message: &str) -> PackingMode {
+    match serde_json::from_str::<Jwe>(message) {
+        // anonymous senders wrap the content key with a key agreed with an ephemeral key only
+        Ok(jwe)
+            if jwe
+                .protected
+                .as_ref()
+                .and_then(|header| header.alg.as_deref())
+                .filter(|alg| alg.starts_with("ECDH-ES"))
+                .is_some() =>
+        {
+            PackingMode::Anoncrypt
+        }
+        // signed messages carry the public key of the signing key pair as kid, the kid is only
+        // claimed by the sender, so the signature has to be verified on receive
+        Ok(jwe)
+            if jwe
+                .protected
+                .as_ref()
+                .and_then(|header| header.kid.as_ref())
+                .is_some() =>
+        {
+            PackingMode::Signed
+        }
+        Ok(_) => PackingMode::Authcrypt,
+        Err(_) if serde_json::from_str::<Jws>(message).is_ok() => PackingMode::Jws,
+        Err(_) => PackingMode::Plaintext,
+    }
+}
+
+/// Gets the public key of the signer of a `signed` message from the `kid` of its protected header.
+/// The key is claimed by the sender and has to be checked against the key of its DID.
+///
+/// # Arguments
+/// * `message` - encrypted message string
+///
+/// # Returns
+/// * `Vec<u8>` - public key of the signer
+pub fn get_signed_message_kid(message: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let jwe: Jwe = serde_json::from_str(message)?;
+    let kid = jwe
+        .protected
+        .and_then(|header| header.kid)
+        .ok_or("signed message has no signer kid")?;
+
+    Ok(hex::decode(kid)?)
+}
+
+/// Sign a stringified plain message without encrypting it (JWS), so anyone with the public key
+/// of the signer can verify it. The public key of the signer is added as hex encoded `kid`.
+///
+/// # Arguments
+/// * `message_string` - message string (should match datatypes.rs/ExtendedMessage)
+/// * `sign_keypair` - signing key pair
+///
+/// # Returns
+/// * `String` - signed stringified message
+pub fn sign_message(
+    message_string: &str,
+    sign_keypair: SigningKeyPair,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let signature_algorithm = sign_keypair.get_signature_algorithm()?;
+    let signed = DIDCommMessage::new()
+        .body(message_string)?
+        .as_jws(&signature_algorithm)
+        .kid(&hex::encode(&sign_keypair.public))
+        .sign(signature_algorithm.signer(), &sign_keypair.get_signer_key())
+        .map_err(|err| {
+            format!(
+                "could not run sign while signing message: {}",
+                &err.to_string()
+            )
+        })?;
+
+    Ok(signed)
+}
+
+/// Gets the `kid` of the first signature of a signed message, either from its unprotected or from
+/// its protected header.
+fn get_signature_kid(jws: &Jws) -> Option<String> {
+    let signature = jws
+        .signatures
+        .as_ref()
+        .and_then(|signatures| signatures.first())
+        .or(jws.signature.as_ref())?;
+
+    signature
+        .header
+        .as_ref()
+        .and_then(|header| header.kid.clone())
+        .or_else(|| {
+            let protected = BASE64URL_NOPAD
+                .decode(signature.protected.as_ref()?.as_bytes())
+                .ok()?;
+            let protected: Value = serde_json::from_slice(&protected).ok()?;
+            protected["kid"].as_str().map(String::from)
+        })
+}
+
+/// Verify a stringified message signed with `sign_message`.
+///
+/// # Arguments
+/// * `message` - signed message string
+/// * `sign_public` - signing public key of the expected signer - if None the key in the `kid` of
+///   the message is used, which is only claimed by the sender and does not authenticate it
+///
+/// # Returns
+/// * `(String, String)` - verified stringified message and hex encoded public key of the signer
+pub fn verify_message(
+    message: &str,
+    sign_public: Option<&[u8]>,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let signer = match sign_public {
+        Some(sign_public) => sign_public.to_vec(),
+        None => {
+            let jws: Jws = serde_json::from_str(message)?;
+            hex::decode(get_signature_kid(&jws).ok_or("signed message has no signer kid")?)?
+        }
+    };
+
+    let verified = DIDCommMessage::verify(message.as_bytes(), &signer)
+        .map_err(|err| format!("could not verify signed message: {}", &err.to_string()))?;
+    let body = verified.get_body().map_err(|err| {
+        format!(
+            "could not get body from message while verifying message: {}",
+            &err.to_string()
+        )
+    })?;
+
+    Ok((body, hex::encode(signer)))
+}
+
 /// Decrypt a stringified encrypted message, with a given decryption_key and signing key using
 /// DIDComm rs. (checkout vade_didcomm.rs or tests/message.rs for example usage)
 ///
 /// # Arguments
 /// * `message` - message string (should match message.rs/EncryptedMessage)
 /// * `decryption_key` - decryption secret key from the receiver - if None it treats the message as "unencrypted"
-/// * `decryption_public` - decryption public key from the sender - if None it tries to resolve the did
+/// * `decryption_public` - decryption public key from the sender, resolved beforehand if unknown
 /// * `sign_public` - signing public key (usually delivered within the encrypted message kid field)
 ///
 /// # Returns
@@ -104,6 +426,8 @@ pub fn decrypt_message(
     decryption_public: Option<Vec<u8>>,
     sign_public: Option<&[u8]>,
 ) -> Result<String, Box<dyn std::error::Error>> {
+    // DIDComm rs picks the algorithm from the `enc` header, reject unsupported ones beforehand
+    get_encryption_algorithm(message)?;
     let received = DIDCommMessage::receive(message, decryption_key, decryption_public, sign_public)
         .map_err(|err| format!("could not decrypt message: {}", &err.to_string()))?;
 
@@ -122,11 +446,14 @@ mod tests {
     extern crate utilities;
 
     use didcomm_rs::Jwe;
+    use rand_core::OsRng;
     use serde::{Deserialize, Serialize};
+    use serde_json::json;
     use utilities::keypair::get_keypair_set;
+    use x25519_dalek::{PublicKey, StaticSecret};
 
     use super::*;
-    use crate::datatypes::MessageWithBody;
+    use crate::{datatypes::MessageWithBody, keypair::create_key_pair};
     #[derive(Debug, Serialize, Deserialize, Clone)]
     struct TestBody {
         test: bool,
@@ -149,8 +476,9 @@ mod tests {
         let encrypted = encrypt_message(
             &payload,
             &sign_keypair.user1_secret.to_bytes(),
-            Some(sign_keypair.user2_pub.to_bytes().to_vec()),
-            Some(sign_keypair.sign_keypair),
+            vec![Some(sign_keypair.user2_pub.to_bytes().to_vec())],
+            Some(sign_keypair.sign_keypair.into()),
+            EncryptionAlgorithm::Xc20p,
         )?;
         let _: Jwe = serde_json::from_str(&encrypted)?;
 
@@ -173,8 +501,9 @@ mod tests {
         let encrypted = encrypt_message(
             &payload,
             &sign_keypair.user1_secret.to_bytes(),
-            Some(sign_keypair.user2_pub.to_bytes().to_vec()),
-            Some(sign_keypair.sign_keypair),
+            vec![Some(sign_keypair.user2_pub.to_bytes().to_vec())],
+            Some(sign_keypair.sign_keypair.into()),
+            EncryptionAlgorithm::Xc20p,
         )?;
 
         let decrypted = decrypt_message(
@@ -190,4 +519,195 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn can_encrypt_message_for_multiple_recipients() -> Result<(), Box<dyn std::error::Error>> {
+        let sign_keypair = get_keypair_set();
+        let other_secret = StaticSecret::new(OsRng);
+        let payload = r#"{
+                "body": {"test": true},
+                "to": [
+                    "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG",
+                    "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp"
+                ],
+                "from": "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                "type": "test"
+            }"#
+        .to_string();
+        let encrypted = encrypt_message(
+            &payload,
+            &sign_keypair.user1_secret.to_bytes(),
+            vec![
+                Some(sign_keypair.user2_pub.to_bytes().to_vec()),
+                Some(PublicKey::from(&other_secret).to_bytes().to_vec()),
+            ],
+            None,
+            EncryptionAlgorithm::Xc20p,
+        )?;
+        let jwe: Jwe = serde_json::from_str(&encrypted)?;
+        assert_eq!(jwe.recipients.ok_or("recipients missing")?.len(), 2);
+
+        for recipient_secret in [
+            sign_keypair.user2_secret.to_bytes(),
+            other_secret.to_bytes(),
+        ] {
+            let decrypted = decrypt_message(
+                &encrypted,
+                Some(&recipient_secret),
+                Some(sign_keypair.user1_pub.to_bytes().to_vec()),
+                None,
+            )?;
+            let decryped_parsed: MessageWithBody<TestBody> = serde_json::from_str(&decrypted)?;
+            assert!(decryped_parsed.body.ok_or("body not available")?.test);
+        }
+
+        // keys have to match the recipients
+        assert!(encrypt_message(
+            &payload,
+            &sign_keypair.user1_secret.to_bytes(),
+            vec![Some(sign_keypair.user2_pub.to_bytes().to_vec()); 3],
+            None,
+            EncryptionAlgorithm::Xc20p,
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_sign_and_verify_message() -> Result<(), Box<dyn std::error::Error>> {
+        let sign_keypair = get_keypair_set();
+        let payload = r#"{
+                "body": {"test": true},
+                "to": [ "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG" ],
+                "from": "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                "type": "test"
+            }"#
+        .to_string();
+        let signer_public = sign_keypair.sign_keypair.public.to_bytes();
+        let signed = sign_message(&payload, sign_keypair.sign_keypair.into())?;
+        assert_eq!(get_packing_mode(&signed), PackingMode::Jws);
+
+        let (verified, signer) = verify_message(&signed, None)?;
+        let verified_parsed: MessageWithBody<TestBody> = serde_json::from_str(&verified)?;
+        assert!(verified_parsed.body.ok_or("body not available")?.test);
+        assert_eq!(signer, hex::encode(signer_public));
+
+        // messages of other signers must be rejected
+        assert!(
+            verify_message(&signed, Some(&sign_keypair.sign_keypair2.public.to_bytes())).is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_sign_and_verify_message_with_ecdsa_keys() -> Result<(), Box<dyn std::error::Error>> {
+        let payload = r#"{
+                "body": {"test": true},
+                "to": [ "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG" ],
+                "type": "test"
+            }"#;
+        for key_type in [KeyType::Secp256k1, KeyType::P256] {
+            let key_pair = create_key_pair(key_type)?;
+            assert_eq!(key_pair.public.len(), 33);
+            let signed = sign_message(payload, SigningKeyPair::new(key_type, key_pair.secret)?)?;
+
+            let (verified, signer) = verify_message(&signed, None)?;
+            let verified_parsed: MessageWithBody<TestBody> = serde_json::from_str(&verified)?;
+            assert!(verified_parsed.body.ok_or("body not available")?.test);
+            assert_eq!(signer, hex::encode(&key_pair.public));
+
+            let other_key_pair = create_key_pair(key_type)?;
+            assert!(verify_message(&signed, Some(&other_key_pair.public)).is_err());
+        }
+        assert!(
+            SigningKeyPair::new(KeyType::X25519, create_key_pair(KeyType::X25519)?.secret).is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_detect_encryption_algorithm() -> Result<(), Box<dyn std::error::Error>> {
+        let sign_keypair = get_keypair_set();
+        let payload = r#"{
+                "body": {"test": true},
+                "to": [ "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG" ],
+                "from": "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                "type": "test"
+            }"#
+        .to_string();
+
+        for encryption_algorithm in [
+            EncryptionAlgorithm::Xc20p,
+            EncryptionAlgorithm::A256Gcm,
+            EncryptionAlgorithm::A256CbcHs512,
+        ] {
+            let encrypted = encrypt_message(
+                &payload,
+                &sign_keypair.user1_secret.to_bytes(),
+                vec![Some(sign_keypair.user2_pub.to_bytes().to_vec())],
+                None,
+                encryption_algorithm,
+            )?;
+            assert_eq!(get_encryption_algorithm(&encrypted)?, encryption_algorithm);
+            decrypt_message(
+                &encrypted,
+                Some(&sign_keypair.user2_secret.to_bytes()),
+                Some(sign_keypair.user1_pub.to_bytes().to_vec()),
+                None,
+            )?;
+        }
+
+        let encrypted = encrypt_message(
+            &payload,
+            &sign_keypair.user1_secret.to_bytes(),
+            vec![Some(sign_keypair.user2_pub.to_bytes().to_vec())],
+            None,
+            EncryptionAlgorithm::Xc20p,
+        )?;
+        let mut jwe: Value = serde_json::from_str(&encrypted)?;
+        jwe["protected"]["enc"] = json!("A128CBC-HS256");
+        assert!(decrypt_message(
+            &serde_json::to_string(&jwe)?,
+            Some(&sign_keypair.user2_secret.to_bytes()),
+            Some(sign_keypair.user1_pub.to_bytes().to_vec()),
+            None,
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_encrypt_message_anonymously() -> Result<(), Box<dyn std::error::Error>> {
+        let sign_keypair = get_keypair_set();
+        let payload = r#"{
+                "body": {"test": true},
+                "to": [ "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG" ],
+                "from": "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                "type": "test"
+            }"#
+        .to_string();
+        let encrypted = encrypt_message_anonymously(
+            &payload,
+            vec![sign_keypair.user2_pub.to_bytes().to_vec()],
+            EncryptionAlgorithm::Xc20p,
+        )?;
+        let jwe: Jwe = serde_json::from_str(&encrypted)?;
+        let protected = jwe.protected.ok_or("protected header is missing")?;
+        assert!(protected.skid.is_none());
+        assert_eq!(protected.alg.as_deref(), Some("ECDH-ES+A256KW"));
+        assert_eq!(get_packing_mode(&encrypted), PackingMode::Anoncrypt);
+        let jwe: Value = serde_json::from_str(&encrypted)?;
+        assert_eq!(jwe["protected"]["epk"]["crv"], "X25519");
+
+        let decrypted =
+            decrypt_message_anonymously(&encrypted, &sign_keypair.user2_secret.to_bytes())?;
+        let decryped_parsed: MessageWithBody<TestBody> = serde_json::from_str(&decrypted)?;
+        assert!(decryped_parsed.body.ok_or("body not available")?.test);
+
+        Ok(())
+    }
 }
@@ -1,8 +1,13 @@
 use crate::{
     datatypes::{MessageDirection, MessageWithType, ProtocolHandleOutput},
+    db::{BatchStorage, DidCommStorage},
     protocols::{
+        coordinate_mediation::generate_coordinate_mediation_protocol,
         did_exchange::generate_did_exchange_protocol,
+        discover_features::generate_discover_features_protocol,
+        forward::generate_forward_protocol,
         issue_credential::generate_issue_credential_protocol,
+        message_pickup::generate_message_pickup_protocol,
         pingpong::generate_ping_pong_protocol,
         present_proof::generate_present_proof_protocol,
         presentation_exchange::generate_presentation_exchange_protocol,
@@ -16,15 +21,28 @@ impl ProtocolHandler {
     /// the message with step specific information or can store things like communication keys.
     ///
     /// # Arguments
+    /// * `storage` - storage to persist protocol states and keys in
     /// * `message` - message string (should match message.rs/ExtendedMessage)
+    /// * `sender_authenticated` - `true` if the message will be packed with `authcrypt` or `signed`
+    /// * `hidden_protocols` - ids of protocols, that are not disclosed to other agents
     ///
     /// # Returns
     /// * `ProtocolHandleOutput` - general information about the analyzed protocol step
     pub fn before_send(
+        storage: &dyn DidCommStorage,
         options: &str,
         message: &str,
+        sender_authenticated: bool,
+        hidden_protocols: &[String],
     ) -> Result<ProtocolHandleOutput, Box<dyn std::error::Error>> {
-        handle_protocol(options, message, MessageDirection::Send)
+        handle_protocol(
+            storage,
+            options,
+            message,
+            MessageDirection::Send,
+            sender_authenticated,
+            hidden_protocols,
+        )
     }
 
     /// Runs all protocol handlers for a message, to analyze it after receiving and decryption.
@@ -32,36 +50,85 @@ impl ProtocolHandler {
     /// like communication keys.
     ///
     /// # Arguments
+    /// * `storage` - storage to persist protocol states and keys in
     /// * `message` - message string (should match message.rs/ExtendedMessage))
+    /// * `sender_authenticated` - `true` if the sender owns the key of the `from` DID of the message
+    /// * `hidden_protocols` - ids of protocols, that are not disclosed to other agents
     ///
     /// # Returns
     /// * `ProtocolHandleOutput` - general information about the analyzed protocol step
     pub fn after_receive(
+        storage: &dyn DidCommStorage,
         options: &str,
         message: &str,
+        sender_authenticated: bool,
+        hidden_protocols: &[String],
     ) -> Result<ProtocolHandleOutput, Box<dyn std::error::Error>> {
-        handle_protocol(options, message, MessageDirection::Receive)
+        handle_protocol(
+            storage,
+            options,
+            message,
+            MessageDirection::Receive,
+            sender_authenticated,
+            hidden_protocols,
+        )
     }
 }
 
+/// Gets all registered protocols in the order, in which they are matched against message types.
+///
+/// # Returns
+/// * `Vec<Protocol>` - registered protocols
+pub(crate) fn get_protocols() -> Vec<Protocol> {
+    vec![
+        generate_did_exchange_protocol(),
+        generate_ping_pong_protocol(),
+        generate_present_proof_protocol(),
+        generate_issue_credential_protocol(),
+        generate_presentation_exchange_protocol(),
+        generate_forward_protocol(),
+        generate_coordinate_mediation_protocol(),
+        generate_message_pickup_protocol(),
+        generate_discover_features_protocol(),
+    ]
+}
+
+/// Sets the ids of the protocols, that are hidden from other agents, as `hiddenProtocols` in the
+/// options passed to the step handlers. Ids passed with the options of a call are replaced, so
+/// protocols are only hidden as configured for the plugin instance.
+fn set_hidden_protocols(
+    options: &str,
+    hidden_protocols: &[String],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut options: serde_json::Value = serde_json::from_str(options)?;
+    options
+        .as_object_mut()
+        .ok_or("options must be a json object")?
+        .insert(
+            "hiddenProtocols".to_string(),
+            serde_json::to_value(hidden_protocols)?,
+        );
+
+    Ok(serde_json::to_string(&options)?)
+}
+
 /// General protocol step handler for analyzing messages with a direction (incoming / outgoing).
 /// It analyse the message type and checks if a step with a specific direction is configured.
 /// When a step is found, the logic will be executed and no other handler will be searched.
+/// Steps, that require an authenticated sender, are rejected for unauthenticated messages.
 fn handle_protocol(
+    storage: &dyn DidCommStorage,
     options: &str,
     message: &str,
     direction: MessageDirection,
+    sender_authenticated: bool,
+    hidden_protocols: &[String],
 ) -> Result<ProtocolHandleOutput, Box<dyn std::error::Error>> {
+    let options = &set_hidden_protocols(options, hidden_protocols)?;
     let parsed_message: MessageWithType = serde_json::from_str(message)?;
     let m_type = parsed_message.r#type;
     // handle multiple protocols dynamically
-    let protocols: [&Protocol; 5] = [
-        &generate_did_exchange_protocol(),
-        &generate_ping_pong_protocol(),
-        &generate_present_proof_protocol(),
-        &generate_issue_credential_protocol(),
-        &generate_presentation_exchange_protocol(),
-    ];
+    let protocols = get_protocols();
     // protocol results
     let mut protocol_name: String = String::from("unknown");
     let mut step_name: String = String::from("unknown");
@@ -79,7 +146,18 @@ fn handle_protocol(
                 let protocol_type = format!("{}/{}", protocol_name, step.name);
                 // check for configured step names and directions
                 if step.direction == direction && m_type.contains(&protocol_type) {
-                    let step_outcome = (step.handler)(options, message)?;
+                    if step.authenticated && !sender_authenticated {
+                        return Err(Box::from(format!(
+                            "{} requires an authenticated sender, the message has to be packed \
+                             with 'authcrypt' or 'signed' by the owner of its `from` DID",
+                            protocol_type
+                        )));
+                    }
+                    // collect all writes of the step and commit them at once, so protocol state
+                    // and protocol data are either stored together or not at all
+                    let batch = BatchStorage::new(storage);
+                    let step_outcome = (step.handler)(&batch, options, message)?;
+                    batch.commit()?;
                     encrypt = step_outcome.encrypt;
                     metadata = step_outcome.metadata;
                     message_output = step_outcome.message;
@@ -0,0 +1,159 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+pub const COORDINATE_MEDIATION_PROTOCOL_URL: &str = "https://didcomm.org/coordinate-mediation/2.0";
+
+/// Body of `mediate-grant` messages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediateGrantData {
+    /// DID or key id, that the recipient has to use as routing key for its messages
+    pub routing_did: Option<String>,
+}
+
+/// Single change of the keylist of a recipient.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KeylistUpdate {
+    pub recipient_did: String,
+    pub action: KeylistUpdateAction,
+}
+
+/// Body of `keylist-update` messages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeylistUpdateData {
+    pub updates: Vec<KeylistUpdate>,
+}
+
+/// Result of a single change of the keylist of a recipient.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KeylistUpdated {
+    pub recipient_did: String,
+    pub action: KeylistUpdateAction,
+    pub result: KeylistUpdateResult,
+}
+
+/// Body of `keylist-update-response` messages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeylistUpdateResponseData {
+    pub updated: Vec<KeylistUpdated>,
+}
+
+/// Pagination of `keylist-query` messages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeylistQueryPaginate {
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Body of `keylist-query` messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct KeylistQueryData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paginate: Option<KeylistQueryPaginate>,
+}
+
+/// Entry of the keylist of a recipient.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KeylistKey {
+    pub recipient_did: String,
+}
+
+/// Pagination of `keylist` messages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeylistPagination {
+    pub count: usize,
+    pub offset: usize,
+    pub remaining: usize,
+}
+
+/// Body of `keylist` messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct KeylistData {
+    #[serde(default)]
+    pub keys: Vec<KeylistKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<KeylistPagination>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeylistUpdateAction {
+    Add,
+    Remove,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeylistUpdateResult {
+    ClientError,
+    ServerError,
+    NoChange,
+    Success,
+}
+
+/// Mediation between a recipient and its mediator, as stored by both of them.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Mediation {
+    pub mediator_did: String,
+    pub recipient_did: String,
+    pub state: State,
+    /// routing DID granted by the mediator
+    pub routing_did: Option<String>,
+    /// keys of the recipient, that the mediator confirmed to route messages for (recipient only)
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+/// Entry of the keylist of a mediator, that maps a key to the recipient it routes messages for.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MediationRoute {
+    pub mediator_did: String,
+    pub recipient_did: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum State {
+    SendRequest,
+    ReceiveRequest,
+    SendGrant,
+    ReceiveGrant,
+    SendDeny,
+    ReceiveDeny,
+    Unknown,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::str::FromStr for State {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SendRequest" => Ok(State::SendRequest),
+            "ReceiveRequest" => Ok(State::ReceiveRequest),
+            "SendGrant" => Ok(State::SendGrant),
+            "ReceiveGrant" => Ok(State::ReceiveGrant),
+            "SendDeny" => Ok(State::SendDeny),
+            "ReceiveDeny" => Ok(State::ReceiveDeny),
+            _ => Ok(State::Unknown),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum UserType {
+    Recipient,
+    Mediator,
+}
+
+impl fmt::Display for UserType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{
+    db::DidCommStorage,
+    protocols::{
+        coordinate_mediation::datatypes::{Mediation, MediationRoute, State, UserType},
+        thread::ThreadState,
+    },
+};
+
+impl ThreadState for State {
+    const PROTOCOL: &'static str = "coordinate_mediation";
+}
+
+/// Saves a mediation between a recipient and its mediator. Entry key will be
+/// mediation_{user_type}_{mediator_did}_{recipient_did}.
+///
+/// # Arguments
+/// * `storage` - storage to save the data in
+/// * `mediation` - mediation to save
+/// * `user_type` - role of the own DID in the mediation
+pub fn save_mediation(
+    storage: &dyn DidCommStorage,
+    mediation: &Mediation,
+    user_type: &UserType,
+) -> Result<(), Box<dyn std::error::Error>> {
+    storage.put(
+        &format!(
+            "mediation_{}_{}_{}",
+            user_type, mediation.mediator_did, mediation.recipient_did
+        ),
+        &serde_json::to_string(mediation)?,
+    )?;
+
+    Ok(())
+}
+
+/// Loads the mediation between a recipient and its mediator. Entry key will be
+/// mediation_{user_type}_{mediator_did}_{recipient_did}.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `mediator_did` - DID of the mediator
+/// * `recipient_did` - DID of the recipient
+/// * `user_type` - role of the own DID in the mediation
+///
+/// # Returns
+/// * `Mediation` - stored mediation, returns an error if no mediation has been requested
+pub fn get_mediation(
+    storage: &dyn DidCommStorage,
+    mediator_did: &str,
+    recipient_did: &str,
+    user_type: &UserType,
+) -> Result<Mediation, Box<dyn std::error::Error>> {
+    let mediation = storage
+        .get(&format!(
+            "mediation_{}_{}_{}",
+            user_type, mediator_did, recipient_did
+        ))
+        .map_err(|_| {
+            format!(
+                "no mediation between mediator {} and recipient {}",
+                mediator_did, recipient_did
+            )
+        })?;
+
+    Ok(serde_json::from_str(&mediation)?)
+}
+
+/// Loads the mediation between a recipient and its mediator and ensures, that it has been granted.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `mediator_did` - DID of the mediator
+/// * `recipient_did` - DID of the recipient
+/// * `user_type` - role of the own DID in the mediation
+///
+/// # Returns
+/// * `Mediation` - granted mediation
+pub fn get_granted_mediation(
+    storage: &dyn DidCommStorage,
+    mediator_did: &str,
+    recipient_did: &str,
+    user_type: &UserType,
+) -> Result<Mediation, Box<dyn std::error::Error>> {
+    let mediation = get_mediation(storage, mediator_did, recipient_did, user_type)?;
+    let granted = match user_type {
+        UserType::Recipient => mediation.state == State::ReceiveGrant,
+        UserType::Mediator => mediation.state == State::SendGrant,
+    };
+    if !granted {
+        return Err(Box::from(format!(
+            "mediation between mediator {} and recipient {} has not been granted",
+            mediator_did, recipient_did
+        )));
+    }
+
+    Ok(mediation)
+}
+
+/// Saves a key of the keylist of a mediator. Entry key will be mediation_route_{key}.
+///
+/// # Arguments
+/// * `storage` - storage to save the data in
+/// * `key` - DID or key id, that messages are routed to the recipient for
+/// * `route` - mediator and recipient of the key
+pub fn save_mediation_route(
+    storage: &dyn DidCommStorage,
+    key: &str,
+    route: &MediationRoute,
+) -> Result<(), Box<dyn std::error::Error>> {
+    storage.put(
+        &format!("mediation_route_{}", key),
+        &serde_json::to_string(route)?,
+    )?;
+
+    Ok(())
+}
+
+/// Deletes a key from the keylist of a mediator. Entry key will be mediation_route_{key}.
+///
+/// # Arguments
+/// * `storage` - storage to delete the data from
+/// * `key` - DID or key id, that messages are routed to the recipient for
+pub fn delete_mediation_route(
+    storage: &dyn DidCommStorage,
+    key: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    storage.delete(&format!("mediation_route_{}", key))
+}
+
+/// Loads the recipient, that a mediator routes messages for a key to. Entry key will be
+/// mediation_route_{key}.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `key` - DID or key id, e.g. the `next` of a forward message
+///
+/// # Returns
+/// * `Option<MediationRoute>` - mediator and recipient of the key, `None` if it is not routed
+pub fn get_mediation_route(
+    storage: &dyn DidCommStorage,
+    key: &str,
+) -> Result<Option<MediationRoute>, Box<dyn std::error::Error>> {
+    match storage.get(&format!("mediation_route_{}", key)) {
+        Ok(route) => Ok(Some(serde_json::from_str(&route)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Gets all keys, that a mediator routes messages to a recipient for.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `mediator_did` - DID of the mediator
+/// * `recipient_did` - DID of the recipient
+///
+/// # Returns
+/// * `Vec<String>` - keys of the recipient, sorted
+pub fn get_mediation_keys(
+    storage: &dyn DidCommStorage,
+    mediator_did: &str,
+    recipient_did: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut keys = Vec::new();
+    for (key, value) in storage.scan_prefix("mediation_route_")? {
+        let route: MediationRoute = serde_json::from_str(&value)?;
+        if route.mediator_did == mediator_did && route.recipient_did == recipient_did {
+            keys.push(
+                key.strip_prefix("mediation_route_")
+                    .unwrap_or(&key)
+                    .to_string(),
+            );
+        }
+    }
+    keys.sort();
+
+    Ok(keys)
+}
@@ -0,0 +1,303 @@
+use serde_json::Value;
+
+#[cfg(feature = "state_storage")]
+use crate::{
+    datatypes::HasFromAndTo,
+    protocols::{
+        coordinate_mediation::{
+            datatypes::{
+                KeylistKey,
+                KeylistPagination,
+                KeylistUpdateAction,
+                KeylistUpdateResult,
+                KeylistUpdated,
+                Mediation,
+                MediationRoute,
+                State,
+                UserType,
+            },
+            mediation::{
+                delete_mediation_route,
+                get_granted_mediation,
+                get_mediation,
+                get_mediation_keys,
+                get_mediation_route,
+                save_mediation,
+                save_mediation_route,
+            },
+        },
+        thread::{get_thid, transition_state},
+    },
+    utils::add_to_metadata,
+};
+use crate::{
+    datatypes::MessageWithBody,
+    db::DidCommStorage,
+    protocols::{
+        coordinate_mediation::datatypes::{
+            KeylistData,
+            KeylistQueryData,
+            KeylistUpdateData,
+            KeylistUpdateResponseData,
+            MediateGrantData,
+        },
+        protocol::{generate_step_output, StepResult},
+    },
+};
+
+/// Protocol handler for direction: `receive`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/mediate-request`
+pub fn receive_mediate_request(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let request_message: MessageWithBody<Value> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = request_message.get_from_to()?;
+            let thid = get_thid(&request_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Mediator,
+                &[State::Unknown],
+                &State::ReceiveRequest,
+            )?;
+            save_mediation(
+                storage,
+                &Mediation {
+                    mediator_did: from_to.to,
+                    recipient_did: from_to.from,
+                    state: State::ReceiveRequest,
+                    routing_did: None,
+                    keys: Vec::new(),
+                },
+                &UserType::Mediator,
+            )?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `send`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/mediate-grant`
+pub fn send_mediate_grant(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let grant_message: MessageWithBody<MediateGrantData> = serde_json::from_str(message)?;
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    let routing_did = grant_message
+        .body
+        .as_ref()
+        .and_then(|body| body.routing_did.to_owned())
+        .ok_or("routing_did is required for mediate-grant messages")?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = grant_message.get_from_to()?;
+            let thid = get_thid(&grant_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Mediator,
+                &[State::ReceiveRequest],
+                &State::SendGrant,
+            )?;
+            let mut mediation =
+                get_mediation(storage, &from_to.from, &from_to.to, &UserType::Mediator)?;
+            mediation.state = State::SendGrant;
+            mediation.routing_did = Some(routing_did);
+            save_mediation(storage, &mediation, &UserType::Mediator)?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `send`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/mediate-deny`
+pub fn send_mediate_deny(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let deny_message: MessageWithBody<Value> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = deny_message.get_from_to()?;
+            let thid = get_thid(&deny_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Mediator,
+                &[State::ReceiveRequest],
+                &State::SendDeny,
+            )?;
+            let mut mediation =
+                get_mediation(storage, &from_to.from, &from_to.to, &UserType::Mediator)?;
+            mediation.state = State::SendDeny;
+            save_mediation(storage, &mediation, &UserType::Mediator)?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/keylist-update`
+/// Applies the updates to the keylist of the recipient and returns their results as `updated`
+/// metadata, that can be used as body of the `keylist-update-response` message.
+pub fn receive_keylist_update(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let update_message: MessageWithBody<KeylistUpdateData> = serde_json::from_str(message)?;
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut metadata = "{}".to_string();
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = update_message.get_from_to()?;
+            get_granted_mediation(storage, &from_to.to, &from_to.from, &UserType::Mediator)?;
+            let updates = update_message
+                .body
+                .ok_or("missing keylist updates in body")?
+                .updates;
+            let mut updated = Vec::new();
+            for update in updates.into_iter() {
+                let route = get_mediation_route(storage, &update.recipient_did)?;
+                let is_own_route = route.as_ref().map(|route| {
+                    route.mediator_did == from_to.to && route.recipient_did == from_to.from
+                });
+                let result = match (&update.action, is_own_route) {
+                    (_, Some(false)) => KeylistUpdateResult::ClientError,
+                    (KeylistUpdateAction::Add, Some(true)) => KeylistUpdateResult::NoChange,
+                    (KeylistUpdateAction::Add, None) => {
+                        save_mediation_route(
+                            storage,
+                            &update.recipient_did,
+                            &MediationRoute {
+                                mediator_did: from_to.to.to_owned(),
+                                recipient_did: from_to.from.to_owned(),
+                            },
+                        )?;
+                        KeylistUpdateResult::Success
+                    }
+                    (KeylistUpdateAction::Remove, Some(true)) => {
+                        delete_mediation_route(storage, &update.recipient_did)?;
+                        KeylistUpdateResult::Success
+                    }
+                    (KeylistUpdateAction::Remove, None) => KeylistUpdateResult::NoChange,
+                };
+                updated.push(KeylistUpdated {
+                    recipient_did: update.recipient_did,
+                    action: update.action,
+                    result,
+                });
+            }
+
+            metadata = add_to_metadata(&metadata, "updated", &serde_json::to_string(&updated)?)?;
+        } else { }
+    }
+
+    generate_step_output(message, &metadata)
+}
+
+/// Protocol handler for direction: `send`, type:
+/// `COORDINATE_MEDIATION_PROTOCOL_URL/keylist-update-response`
+pub fn send_keylist_update_response(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let response_message: MessageWithBody<KeylistUpdateResponseData> =
+        serde_json::from_str(message)?;
+    response_message
+        .body
+        .as_ref()
+        .ok_or("missing keylist update results in body")?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = response_message.get_from_to()?;
+            get_granted_mediation(storage, &from_to.from, &from_to.to, &UserType::Mediator)?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/keylist-query`
+pub fn receive_keylist_query(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let query_message: MessageWithBody<KeylistQueryData> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = query_message.get_from_to()?;
+            get_granted_mediation(storage, &from_to.to, &from_to.from, &UserType::Mediator)?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `send`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/keylist`
+/// Fills the keys of the message with the stored keylist of the recipient. A given `pagination`
+/// selects the page of the keylist by its `offset` and `count`.
+pub fn send_keylist(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut keylist_message: MessageWithBody<KeylistData> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = keylist_message.get_from_to()?;
+            get_granted_mediation(storage, &from_to.from, &from_to.to, &UserType::Mediator)?;
+            let keys = get_mediation_keys(storage, &from_to.from, &from_to.to)?;
+            let mut keylist = keylist_message.body.take().unwrap_or_default();
+            let (offset, count) = match keylist.pagination.as_ref() {
+                Some(pagination) => (pagination.offset.min(keys.len()), pagination.count),
+                None => (0, keys.len()),
+            };
+            keylist.keys = keys
+                .iter()
+                .skip(offset)
+                .take(count)
+                .map(|key| KeylistKey {
+                    recipient_did: key.to_owned(),
+                })
+                .collect();
+            if keylist.pagination.is_some() {
+                keylist.pagination = Some(KeylistPagination {
+                    count: keylist.keys.len(),
+                    offset,
+                    remaining: keys.len() - offset - keylist.keys.len(),
+                });
+            }
+            keylist_message.body = Some(keylist);
+        } else { }
+    }
+
+    generate_step_output(&serde_json::to_string(&keylist_message)?, "{}")
+}
@@ -0,0 +1,65 @@
+pub mod datatypes;
+#[cfg(feature = "state_storage")]
+pub(crate) mod mediation;
+mod mediator;
+mod recipient;
+
+use crate::protocols::{
+    coordinate_mediation::{
+        datatypes::COORDINATE_MEDIATION_PROTOCOL_URL,
+        mediator::{
+            receive_keylist_query,
+            receive_keylist_update,
+            receive_mediate_request,
+            send_keylist,
+            send_keylist_update_response,
+            send_mediate_deny,
+            send_mediate_grant,
+        },
+        recipient::{
+            receive_keylist,
+            receive_keylist_update_response,
+            receive_mediate_deny,
+            receive_mediate_grant,
+            send_keylist_query,
+            send_keylist_update,
+            send_mediate_request,
+        },
+    },
+    protocol::{generate_authenticated_receive_step, generate_authenticated_send_step, Protocol},
+};
+
+/// Creates a new coordinate_mediation protocol and maps the specific step handler functions.
+/// Steps are matched by their type prefix, so `keylist-update-response` has to be checked before
+/// `keylist-update` and `keylist` has to be checked last.
+///
+/// # Returns
+/// * `Protocol` - the new Coordinate Mediation protocol handler
+pub(crate) fn generate_coordinate_mediation_protocol() -> Protocol {
+    Protocol {
+        name: String::from(COORDINATE_MEDIATION_PROTOCOL_URL),
+        roles: vec![String::from("mediator"), String::from("recipient")],
+        steps: vec![
+            generate_authenticated_send_step("mediate-request", send_mediate_request),
+            generate_authenticated_receive_step("mediate-request", receive_mediate_request),
+            generate_authenticated_send_step("mediate-grant", send_mediate_grant),
+            generate_authenticated_receive_step("mediate-grant", receive_mediate_grant),
+            generate_authenticated_send_step("mediate-deny", send_mediate_deny),
+            generate_authenticated_receive_step("mediate-deny", receive_mediate_deny),
+            generate_authenticated_send_step(
+                "keylist-update-response",
+                send_keylist_update_response,
+            ),
+            generate_authenticated_receive_step(
+                "keylist-update-response",
+                receive_keylist_update_response,
+            ),
+            generate_authenticated_send_step("keylist-update", send_keylist_update),
+            generate_authenticated_receive_step("keylist-update", receive_keylist_update),
+            generate_authenticated_send_step("keylist-query", send_keylist_query),
+            generate_authenticated_receive_step("keylist-query", receive_keylist_query),
+            generate_authenticated_send_step("keylist", send_keylist),
+            generate_authenticated_receive_step("keylist", receive_keylist),
+        ],
+    }
+}
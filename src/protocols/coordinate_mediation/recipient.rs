@@ -0,0 +1,270 @@
+use serde_json::Value;
+
+#[cfg(feature = "state_storage")]
+use crate::{
+    datatypes::HasFromAndTo,
+    protocols::{
+        coordinate_mediation::{
+            datatypes::{KeylistUpdateAction, KeylistUpdateResult, Mediation, State, UserType},
+            mediation::{get_granted_mediation, get_mediation, save_mediation},
+        },
+        thread::{get_thid, transition_state},
+    },
+    utils::add_to_metadata,
+};
+use crate::{
+    datatypes::MessageWithBody,
+    db::DidCommStorage,
+    protocols::{
+        coordinate_mediation::datatypes::{
+            KeylistData,
+            KeylistQueryData,
+            KeylistUpdateData,
+            KeylistUpdateResponseData,
+            MediateGrantData,
+        },
+        protocol::{generate_step_output, StepResult},
+    },
+};
+
+/// Protocol handler for direction: `send`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/mediate-request`
+pub fn send_mediate_request(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let request_message: MessageWithBody<Value> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = request_message.get_from_to()?;
+            let thid = get_thid(&request_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Recipient,
+                &[State::Unknown],
+                &State::SendRequest,
+            )?;
+            // keep the keys of a previous mediation, they are still routed by the mediator
+            let keys = get_mediation(storage, &from_to.to, &from_to.from, &UserType::Recipient)
+                .map(|mediation| mediation.keys)
+                .unwrap_or_default();
+            save_mediation(
+                storage,
+                &Mediation {
+                    mediator_did: from_to.to,
+                    recipient_did: from_to.from,
+                    state: State::SendRequest,
+                    routing_did: None,
+                    keys,
+                },
+                &UserType::Recipient,
+            )?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/mediate-grant`
+pub fn receive_mediate_grant(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let grant_message: MessageWithBody<MediateGrantData> = serde_json::from_str(message)?;
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut metadata = "{}".to_string();
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = grant_message.get_from_to()?;
+            let thid = get_thid(&grant_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Recipient,
+                &[State::SendRequest],
+                &State::ReceiveGrant,
+            )?;
+            let mut mediation =
+                get_mediation(storage, &from_to.from, &from_to.to, &UserType::Recipient)?;
+            mediation.state = State::ReceiveGrant;
+            mediation.routing_did = grant_message.body.and_then(|body| body.routing_did);
+            save_mediation(storage, &mediation, &UserType::Recipient)?;
+
+            if let Some(routing_did) = mediation.routing_did.as_ref() {
+                metadata = add_to_metadata(&metadata, "routingDid", routing_did)?;
+            }
+        } else { }
+    }
+
+    generate_step_output(message, &metadata)
+}
+
+/// Protocol handler for direction: `receive`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/mediate-deny`
+pub fn receive_mediate_deny(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let deny_message: MessageWithBody<Value> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = deny_message.get_from_to()?;
+            let thid = get_thid(&deny_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Recipient,
+                &[State::SendRequest],
+                &State::ReceiveDeny,
+            )?;
+            let mut mediation =
+                get_mediation(storage, &from_to.from, &from_to.to, &UserType::Recipient)?;
+            mediation.state = State::ReceiveDeny;
+            mediation.routing_did = None;
+            save_mediation(storage, &mediation, &UserType::Recipient)?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `send`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/keylist-update`
+pub fn send_keylist_update(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let update_message: MessageWithBody<KeylistUpdateData> = serde_json::from_str(message)?;
+    let updates = update_message
+        .body
+        .as_ref()
+        .ok_or("missing keylist updates in body")?;
+    if updates.updates.is_empty() {
+        return Err(Box::from("keylist-update requires at least one update"));
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = update_message.get_from_to()?;
+            get_granted_mediation(storage, &from_to.to, &from_to.from, &UserType::Recipient)?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type:
+/// `COORDINATE_MEDIATION_PROTOCOL_URL/keylist-update-response`
+/// Stores the keys, that the mediator confirmed to route messages for.
+pub fn receive_keylist_update_response(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let response_message: MessageWithBody<KeylistUpdateResponseData> =
+        serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = response_message.get_from_to()?;
+            let mut mediation = get_granted_mediation(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &UserType::Recipient,
+            )?;
+            let updated = response_message
+                .body
+                .ok_or("missing keylist update results in body")?
+                .updated;
+            for update in updated.iter().filter(|update| {
+                matches!(
+                    update.result,
+                    KeylistUpdateResult::Success | KeylistUpdateResult::NoChange
+                )
+            }) {
+                mediation.keys.retain(|key| key != &update.recipient_did);
+                if update.action == KeylistUpdateAction::Add {
+                    mediation.keys.push(update.recipient_did.to_owned());
+                }
+            }
+            save_mediation(storage, &mediation, &UserType::Recipient)?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `send`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/keylist-query`
+pub fn send_keylist_query(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let query_message: MessageWithBody<KeylistQueryData> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = query_message.get_from_to()?;
+            get_granted_mediation(storage, &from_to.to, &from_to.from, &UserType::Recipient)?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `COORDINATE_MEDIATION_PROTOCOL_URL/keylist`
+/// Replaces the stored keys with the keys of a complete keylist.
+pub fn receive_keylist(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let keylist_message: MessageWithBody<KeylistData> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = keylist_message.get_from_to()?;
+            let mut mediation = get_granted_mediation(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &UserType::Recipient,
+            )?;
+            let keylist = keylist_message.body.unwrap_or_default();
+            // only a keylist without further pages replaces the stored keys
+            let is_complete = match keylist.pagination.as_ref() {
+                Some(pagination) => pagination.offset == 0 && pagination.remaining == 0,
+                None => true,
+            };
+            if is_complete {
+                mediation.keys = keylist
+                    .keys
+                    .into_iter()
+                    .map(|key| key.recipient_did)
+                    .collect();
+                save_mediation(storage, &mediation, &UserType::Recipient)?;
+            }
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
@@ -5,6 +5,7 @@ use crate::protocols::{
 };
 use crate::{
     datatypes::ExtendedMessage,
+    db::DidCommStorage,
     protocols::{
         did_exchange::DID_EXCHANGE_PROTOCOL_URL,
         protocol::{generate_step_output, StepResult},
@@ -14,18 +15,24 @@ use crate::{
 /// protocol handler for direction: `send`, type: `DID_EXCHANGE_PROTOCOL_URL/complete`
 /// just ensures to set the correct message type, before the message will be sent (first time for
 /// DID exchange, that a encrypted message will be sent)
-pub fn send_complete(_options: &str, message: &str) -> StepResult {
+pub fn send_complete(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let mut parsed_message: ExtendedMessage = serde_json::from_str(message)?;
     parsed_message.r#type = format!("{DID_EXCHANGE_PROTOCOL_URL}/complete");
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
             let thid = parsed_message.thid.as_ref().ok_or("Thread id can't be empty")?;
-            let current_state: State = get_current_state(&thid, &UserType::Inviter)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, thid, &UserType::Inviter)?.parse()?;
 
             match current_state {
                 State::ReceiveResponse => {
-                    save_state(&thid, &State::SendComplete, &UserType::Inviter)?
+                    save_state(storage, thid, &State::SendComplete, &UserType::Inviter)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -42,7 +49,12 @@ pub fn send_complete(_options: &str, message: &str) -> StepResult {
 }
 
 /// protocol handler for direction: `receive`, type: `DID_EXCHANGE_PROTOCOL_URL/complete`
-pub fn receive_complete(_options: &str, message: &str) -> StepResult {
+pub fn receive_complete(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
 
@@ -50,11 +62,13 @@ pub fn receive_complete(_options: &str, message: &str) -> StepResult {
         if #[cfg(feature = "state_storage")] {
 
             let thid = parsed_message.thid.as_ref().ok_or("Thread id can't be empty")?;
-            let current_state: State = get_current_state(&thid, &UserType::Invitee)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, thid, &UserType::Invitee)?.parse()?;
 
             match current_state {
                 State::SendResponse => save_state(
-                    &thid,
+                    storage,
+                    thid,
                     &State::ReceiveComplete,
                     &UserType::Invitee,
                 )?,
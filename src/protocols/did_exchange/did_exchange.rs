@@ -1,5 +1,5 @@
 use crate::{
-    db::{read_db, write_db},
+    db::DidCommStorage,
     protocols::did_exchange::datatypes::{State, UserType},
 };
 
@@ -7,19 +7,21 @@ use crate::{
 /// did_exchange_{from}_{to}_{state}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the data in
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 /// * `thid` - thread id
 /// * `exchange_data` - did exchange data
 /// * `state` - State
 pub fn save_didexchange(
+    storage: &dyn DidCommStorage,
     from_did: &str,
     to_did: &str,
     thid: &str,
     exchange_data: &str,
     state: &State,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    write_db(
+    storage.put(
         &format!("did_exchange_{}_{}_{}_{}", from_did, to_did, state, thid),
         exchange_data,
     )?;
@@ -31,15 +33,17 @@ pub fn save_didexchange(
 /// did_exchange_state_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the data in
 /// * `state` - State
 /// * `thid` - thread id
 /// * `user_type` - UserType
 pub fn save_state(
+    storage: &dyn DidCommStorage,
     thid: &str,
     state: &State,
     user_type: &UserType,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    write_db(
+    storage.put(
         &format!("did_exchange_state_{}_{}", user_type, thid),
         &state.to_string(),
     )?;
@@ -51,16 +55,18 @@ pub fn save_state(
 /// did_exchange_state_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to load the data from
 /// * `thid` - thread id
 /// * `user_type` - UserType
 ///
 /// # Returns
 /// * `state` - State stored in db.
 pub fn get_current_state(
+    storage: &dyn DidCommStorage,
     thid: &str,
     user_type: &UserType,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let result = read_db(&format!("did_exchange_state_{}_{}", user_type, thid));
+    let result = storage.get(&format!("did_exchange_state_{}_{}", user_type, thid));
     let state = match result {
         Ok(value) => value,
         Err(_) => "Unknown".to_string(),
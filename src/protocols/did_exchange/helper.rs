@@ -37,6 +37,10 @@ pub struct DidExchangeOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "hex_option")]
     pub did_exchange_my_secret: Option<[u8; 32]>,
+    /// keys of the mediators, that the comm partner has to forward messages to us by
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_routing_keys: Option<Vec<String>>,
     #[serde(flatten)]
     pub didcomm_options: DidCommOptions,
 }
@@ -60,6 +64,7 @@ pub struct DidExchangeBaseMessage {
 /// * `from_did` - DID to build the DID document for
 /// * `public_key_encoded` - communication pub key for the DID exchange that will be sent to the target
 /// * `service_endpoint` - url where the user can be reached
+/// * `routing_keys` - keys of the mediators, that messages to the user have to be forwarded by
 ///
 /// # Returns
 /// * `CommunicationDidDocument` - constructed DIDComm object, ready to be sent
@@ -67,6 +72,7 @@ pub fn get_communication_did_doc(
     from_did: &str,
     public_key_encoded: &str,
     service_endpoint: &str,
+    routing_keys: &[String],
 ) -> CommunicationDidDocument {
     let key_id = format!("{from_did}#key-1");
     let pub_key_vec = vec![DidCommPubKey {
@@ -81,6 +87,7 @@ pub fn get_communication_did_doc(
         priority: 0,
         service_endpoint: service_endpoint.to_string(),
         recipient_keys: [public_key_encoded.to_string()].to_vec(),
+        routing_keys: routing_keys.to_vec(),
     }];
 
     CommunicationDidDocument {
@@ -99,17 +106,19 @@ pub fn get_communication_did_doc(
 /// * `from_did` - DID that sends the message
 /// * `to_did` - DID that receives the message
 /// * `from_service_endpoint` - url where the user can be reached
+/// * `from_routing_keys` - keys of the mediators, that messages to the user have to be forwarded by
 /// * `encoded_keypair` - communication keypair (only pubkey will be used)
 ///
 /// # Returns
 /// * `MessageWithBody<CommunicationDidDocument>` - constructed DIDComm object, ready to be sent
-#[allow(clippy::type_complexity)]
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn get_did_exchange_message(
     step_type: DidExchangeType,
     from_did: &str,
     key_agreement_did: &str,
     to_did: &str,
     from_service_endpoint: &str,
+    from_routing_keys: &[String],
     pub_key: &str,
     message: &DidExchangeBaseMessage,
 ) -> Result<
@@ -121,7 +130,12 @@ pub fn get_did_exchange_message(
 > {
     let message = message.clone();
     // convert this to doc attach with base 64 use data_encoding::BASE64;
-    let did_document = get_communication_did_doc(key_agreement_did, pub_key, from_service_endpoint);
+    let did_document = get_communication_did_doc(
+        key_agreement_did,
+        pub_key,
+        from_service_endpoint,
+        from_routing_keys,
+    );
     let base64_encoded_did_document =
         BASE64.encode(serde_json::to_string(&did_document)?.as_bytes());
     let fallback_id = Uuid::new_v4().to_simple().to_string();
@@ -201,6 +215,7 @@ pub fn get_exchange_info_from_message(
         ));
     }
     let service_endpoint = &did_document.service[0].service_endpoint;
+    let routing_keys = did_document.service[0].routing_keys.clone();
 
     Ok(ExchangeInfo {
         from: from_did,
@@ -208,6 +223,7 @@ pub fn get_exchange_info_from_message(
         did_id: did_document.id,
         pub_key_hex,
         service_endpoint: String::from(service_endpoint),
+        routing_keys,
     })
 }
 
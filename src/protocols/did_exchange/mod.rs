@@ -25,6 +25,7 @@ use crate::protocols::{
 pub(crate) fn generate_did_exchange_protocol() -> Protocol {
     Protocol {
         name: String::from(DID_EXCHANGE_PROTOCOL_URL),
+        roles: vec![String::from("requester"), String::from("responder")],
         steps: vec![
             generate_send_step("request", send_request),
             generate_receive_step("request", receive_request),
@@ -5,6 +5,7 @@ use crate::protocols::did_exchange::{
 };
 use crate::{
     datatypes::MessageWithBody,
+    db::DidCommStorage,
     protocols::{
         did_exchange::datatypes::ProblemReportData,
         protocol::{generate_step_output, StepResult},
@@ -12,7 +13,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `DID_EXCHANGE_PROTOCOL_URL/problem-report`
-pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
+pub fn send_problem_report(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let problem_report_message: MessageWithBody<ProblemReportData> = serde_json::from_str(message)?;
 
     cfg_if::cfg_if! {
@@ -21,16 +27,17 @@ pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
                 .body
                 .as_ref()
                 .ok_or("missing problem report data in body")?;
-            let thid = &problem_report_message
+            let thid = problem_report_message
                 .thid
                 .as_ref()
                 .ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(&thid, &problem_report_data.user_type)?
-                .parse()?;
+            let current_state: State =
+                get_current_state(storage, thid, &problem_report_data.user_type)?.parse()?;
 
             match current_state {
                 State::Unknown | State::ReceiveRequest | State::ReceiveResponse => save_state(
+                    storage,
                     thid,
                     &State::SendProblemReport,
                     &problem_report_data.user_type,
@@ -50,7 +57,12 @@ pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `DID_EXCHANGE_PROTOCOL_URL/problem-report`
-pub fn receive_problem_report(_options: &str, message: &str) -> StepResult {
+pub fn receive_problem_report(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let problem_report_message: MessageWithBody<ProblemReportData> = serde_json::from_str(message)?;
 
@@ -75,11 +87,12 @@ pub fn receive_problem_report(_options: &str, message: &str) -> StepResult {
                     )))
                 }
             };
-            let current_state: State = get_current_state(&thid, &current_user_type)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &current_user_type)?.parse()?;
 
             match current_state {
                 State::Unknown | State::SendRequest | State::SendResponse => {
-                    save_state(&thid, &State::ReceiveProblemReport, &current_user_type)?
+                    save_state(storage, &thid, &State::ReceiveProblemReport, &current_user_type)?
                 }
                 _ => {
                     return Err(Box::from(format!(
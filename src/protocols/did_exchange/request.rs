@@ -9,12 +9,16 @@ use super::helper::{
     DidExchangeType,
 };
 #[cfg(feature = "state_storage")]
-use crate::protocols::did_exchange::{
-    datatypes::{State, UserType},
-    did_exchange::{save_didexchange, save_state},
+use crate::protocols::{
+    did_exchange::{
+        datatypes::{State, UserType},
+        did_exchange::{save_didexchange, save_state},
+    },
+    out_of_band::{datatypes::UserType as InvitationUserType, invitation::use_invitation},
 };
 use crate::{
     datatypes::{Base64Container, BaseMessage, DidDocumentBodyAttachment, MessageWithBody},
+    db::DidCommStorage,
     get_from_to_from_message,
     keypair::save_com_keypair,
     protocols::{
@@ -29,7 +33,8 @@ use crate::{
 /// to decrypt the message)
 /// Creates and stores a new communication keypair, that will be used for further communication with
 /// the target DID.
-pub fn send_request(options: &str, message: &str) -> StepResult {
+/// If `pthid` references a received out-of-band invitation, the thread is linked to it.
+pub fn send_request(storage: &dyn DidCommStorage, options: &str, message: &str) -> StepResult {
     let parsed_message: DidExchangeBaseMessage = serde_json::from_str(message)?;
     let options: DidExchangeOptions = serde_json::from_str(options)?;
     let exchange_info = get_from_to_from_message(&parsed_message.base_message)?;
@@ -49,6 +54,7 @@ pub fn send_request(options: &str, message: &str) -> StepResult {
         .map(|v| v.to_owned())
         .unwrap_or_else(|| format!("did:key:z{}", bs58::encode(data).into_string()));
     let encoded_keypair = save_com_keypair(
+        storage,
         &exchange_info.from,
         &exchange_info.to,
         &key_did,
@@ -57,6 +63,7 @@ pub fn send_request(options: &str, message: &str) -> StepResult {
         &hex::encode(secret_key.to_bytes()),
         None,
         None,
+        Vec::new(),
     )?;
     let metadata = serde_json::to_string(&encoded_keypair)?;
     let pub_key_bytes = hex::decode(encoded_keypair.pub_key)?;
@@ -67,6 +74,7 @@ pub fn send_request(options: &str, message: &str) -> StepResult {
         &key_did,
         &exchange_info.to,
         &options.service_endpoint.unwrap_or_default(),
+        &options.service_routing_keys.unwrap_or_default(),
         pub_key_base58_string,
         &parsed_message,
     )?;
@@ -75,6 +83,7 @@ pub fn send_request(options: &str, message: &str) -> StepResult {
     // store keys for documents DID as well, so we can use both DIDs in the future
     if exchange_info.from != did_document.id {
         save_com_keypair(
+            storage,
             &exchange_info.from,
             &exchange_info.to,
             &key_did,
@@ -83,16 +92,27 @@ pub fn send_request(options: &str, message: &str) -> StepResult {
             &hex::encode(secret_key.to_bytes()),
             None,
             None,
+            Vec::new(),
         )?;
     }
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
             let thid = parsed_message.thid.ok_or("Thread id can't be empty")?;
-
-            save_state(&thid, &State::SendRequest, &UserType::Inviter)?;
+            if let Some(pthid) = parsed_message.pthid {
+                use_invitation(
+                    storage,
+                    &pthid,
+                    &InvitationUserType::Invitee,
+                    &exchange_info.to,
+                    &thid,
+                )?;
+            }
+
+            save_state(storage, &thid, &State::SendRequest, &UserType::Inviter)?;
 
             save_didexchange(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -109,11 +129,15 @@ pub fn send_request(options: &str, message: &str) -> StepResult {
 /// protocol handler for direction: `receive`, type: `DID_EXCHANGE_PROTOCOL_URL/request`
 /// Receives the partners DID and communication pub key and generates new communication keypairs,
 /// stores it within the db.
-pub fn receive_request(options: &str, message: &str) -> StepResult {
+/// If `pthid` references an out-of-band invitation created by us, the thread is linked to it.
+/// Requests for single-use invitations, that have already been used, are rejected.
+pub fn receive_request(storage: &dyn DidCommStorage, options: &str, message: &str) -> StepResult {
     let parsed_message: MessageWithBody<DidDocumentBodyAttachment<Base64Container>> =
         serde_json::from_str(message)?;
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let thid = parsed_message.thid.ok_or("Thread id can't be empty")?;
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    let pthid = parsed_message.pthid;
     let did_document = get_did_document_from_body(message)?;
     let parsed_message: BaseMessage = serde_json::from_str(message)?;
     let exchange_info = get_exchange_info_from_message(&parsed_message, did_document)?;
@@ -129,6 +153,7 @@ pub fn receive_request(options: &str, message: &str) -> StepResult {
     let data = [codec, pub_key.as_bytes()].concat();
     let key_did = format!("did:key:z{}", bs58::encode(data).into_string());
     let encoded_keypair = save_com_keypair(
+        storage,
         &exchange_info.to,
         &exchange_info.from,
         &key_did,
@@ -137,11 +162,13 @@ pub fn receive_request(options: &str, message: &str) -> StepResult {
         &hex::encode(secret_key.to_bytes()),
         Some(exchange_info.clone().pub_key_hex),
         Some(exchange_info.clone().service_endpoint),
+        exchange_info.routing_keys.clone(),
     )?;
     // in case we received a DID document from a known DID and we might be using this documents
     // DID for communication in future, store key for documents DID as well
     if exchange_info.from != exchange_info.did_id {
         save_com_keypair(
+            storage,
             &exchange_info.to,
             &exchange_info.did_id,
             &key_did,
@@ -150,15 +177,27 @@ pub fn receive_request(options: &str, message: &str) -> StepResult {
             &hex::encode(secret_key.to_bytes()),
             Some(exchange_info.pub_key_hex),
             Some(exchange_info.service_endpoint),
+            exchange_info.routing_keys,
         )?;
     }
     let metadata = serde_json::to_string(&encoded_keypair)?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            save_state(&thid, &State::ReceiveRequest, &UserType::Invitee)?;
+            if let Some(pthid) = pthid {
+                use_invitation(
+                    storage,
+                    &pthid,
+                    &InvitationUserType::Inviter,
+                    &exchange_info.to,
+                    &thid,
+                )?;
+            }
+
+            save_state(storage, &thid, &State::ReceiveRequest, &UserType::Invitee)?;
 
             save_didexchange(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
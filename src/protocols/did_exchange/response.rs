@@ -12,6 +12,7 @@ use super::helper::{
 #[cfg(not(feature = "state_storage"))]
 use crate::datatypes::CommKeyPair;
 use crate::{
+    db::DidCommStorage,
     get_from_to_from_message,
     protocols::protocol::{generate_step_output, StepResult},
 };
@@ -29,15 +30,20 @@ use crate::{
 /// that should be sent. Message will be sent NOT encrypted. (the other party does not have the
 /// comm pub key to decrypt the message)
 /// Constructs a message including the communication pub key, that was generated during receive_request.
-pub fn send_response(options: &str, message: &str) -> StepResult {
+pub fn send_response(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: DidExchangeBaseMessage = serde_json::from_str(message)?;
     let options: DidExchangeOptions = serde_json::from_str(options)?;
     let exchange_info = get_from_to_from_message(&parsed_message.base_message)?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let encoded_keypair = get_com_keypair(&exchange_info.from, &exchange_info.to)?;
-            pub_key_bytes = hex::decode(encoded_keypair.pub_key)?;
+            let encoded_keypair = get_com_keypair(storage, &exchange_info.from, &exchange_info.to)?;
+            let pub_key_bytes = hex::decode(encoded_keypair.pub_key)?;
         } else {
             let secret_key = options
                 .did_exchange_my_secret
@@ -60,6 +66,7 @@ pub fn send_response(options: &str, message: &str) -> StepResult {
         &key_agreement_key,
         &exchange_info.to,
         &options.service_endpoint.unwrap_or_default(),
+        &options.service_routing_keys.unwrap_or_default(),
         pub_key_base58_string,
         &parsed_message,
     )?;
@@ -68,10 +75,11 @@ pub fn send_response(options: &str, message: &str) -> StepResult {
         if #[cfg(feature = "state_storage")] {
             let thid = parsed_message.thid.ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Invitee)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Invitee)?.parse()?;
             match current_state {
                 State::ReceiveRequest => {
-                    save_state(&thid, &State::SendResponse, &UserType::Invitee)?
+                    save_state(storage, &thid, &State::SendResponse, &UserType::Invitee)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -83,6 +91,7 @@ pub fn send_response(options: &str, message: &str) -> StepResult {
             };
 
             save_didexchange(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -99,6 +108,8 @@ pub fn send_response(options: &str, message: &str) -> StepResult {
 /// Receives the partners pub key and updates the existing communication key pair for this DID in
 /// the db.
 pub fn receive_response(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
     #[allow(unused_variables)] // may not be used, depending on feature setup
     options: &str,
     message: &str,
@@ -109,9 +120,10 @@ pub fn receive_response(
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let encoded_keypair = get_key_agreement_key(&exchange_info.to)?;
+            let encoded_keypair = get_key_agreement_key(storage, &exchange_info.to)?;
 
             let enhanced_encoded_keypair = save_com_keypair(
+                storage,
                 &exchange_info.to,
                 &exchange_info.from,
                 &exchange_info.to,
@@ -120,11 +132,13 @@ pub fn receive_response(
                 &encoded_keypair.secret_key,
                 Some(exchange_info.pub_key_hex.to_owned()),
                 Some(exchange_info.service_endpoint.to_owned()),
+                exchange_info.routing_keys.to_owned(),
             )?;
             // in case we received a DID document from a known DID and we might be using this documents
             // DID for communication in future, store key for documents DID as well
             if exchange_info.from != exchange_info.did_id {
                 save_com_keypair(
+                    storage,
                     &exchange_info.to,
                     &exchange_info.from,
                     &exchange_info.to,
@@ -133,9 +147,10 @@ pub fn receive_response(
                     &encoded_keypair.secret_key,
                     Some(exchange_info.pub_key_hex),
                     Some(exchange_info.service_endpoint),
+                    exchange_info.routing_keys,
                 )?;
             }
-            comm_key_pair = &enhanced_encoded_keypair;
+            let comm_key_pair = &enhanced_encoded_keypair;
         } else {
             let options: DidExchangeOptions = serde_json::from_str(options)?;
             let secret_key = options
@@ -150,6 +165,7 @@ pub fn receive_response(
                 target_key_agreement_key: exchange_info.did_id,
                 target_pub_key: exchange_info.pub_key_hex,
                 target_service_endpoint: exchange_info.service_endpoint,
+                target_routing_keys: exchange_info.routing_keys,
             };
         }
     }
@@ -160,10 +176,11 @@ pub fn receive_response(
         if #[cfg(feature = "state_storage")] {
             let thid = parsed_message.thid.ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Inviter)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Inviter)?.parse()?;
             match current_state {
                 State::SendRequest => {
-                    save_state(&thid, &State::ReceiveResponse, &UserType::Inviter)?
+                    save_state(storage, &thid, &State::ReceiveResponse, &UserType::Inviter)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -175,6 +192,7 @@ pub fn receive_response(
             };
 
             save_didexchange(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+pub const DISCOVER_FEATURES_PROTOCOL_URL: &str = "https://didcomm.org/discover-features/2.0";
+
+/// Feature type of protocols, queries for other feature types are not answered.
+pub const FEATURE_TYPE_PROTOCOL: &str = "protocol";
+
+/// Single query of a `queries` message.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Query {
+    #[serde(rename = "feature-type")]
+    pub feature_type: String,
+    /// feature id, may end with `*` to match all ids starting with the given prefix
+    pub r#match: String,
+}
+
+/// Body of `queries` messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct QueriesData {
+    pub queries: Vec<Query>,
+}
+
+/// Single disclosure of a `disclose` message.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Disclosure {
+    #[serde(rename = "feature-type")]
+    pub feature_type: String,
+    pub id: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    /// names of the message types of a protocol
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<String>,
+}
+
+/// Body of `disclose` messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DiscloseData {
+    /// filled in by the responder, when sending the message without disclosures
+    #[serde(default)]
+    pub disclosures: Vec<Disclosure>,
+}
+
+/// Options of the responder, when receiving `queries` and sending `disclose` messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DiscoverFeaturesOptions {
+    /// ids of protocols, that are never disclosed, ids may end with `*` like query matches, set
+    /// from `VadeDidCommConfig.hidden_protocols` by the protocol handler
+    #[serde(default)]
+    pub hidden_protocols: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum State {
+    SendQueries,
+    ReceiveQueries,
+    SendDisclose,
+    ReceiveDisclose,
+    Unknown,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::str::FromStr for State {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SendQueries" => Ok(State::SendQueries),
+            "ReceiveQueries" => Ok(State::ReceiveQueries),
+            "SendDisclose" => Ok(State::SendDisclose),
+            "ReceiveDisclose" => Ok(State::ReceiveDisclose),
+            _ => Ok(State::Unknown),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum UserType {
+    Requester,
+    Responder,
+}
+
+impl fmt::Display for UserType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
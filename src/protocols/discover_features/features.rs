@@ -0,0 +1,66 @@
+use crate::{
+    db::DidCommStorage,
+    protocols::{discover_features::datatypes::State, thread::ThreadState},
+};
+
+impl ThreadState for State {
+    const PROTOCOL: &'static str = "discover_features";
+}
+
+/// Saves the body of received `queries` for two DIDs (from -> to). Entry key will be
+/// discover_features_{from}_{to}_ReceiveQueries_{thid}.
+///
+/// # Arguments
+/// * `storage` - storage to save the data in
+/// * `from_did` - from DID
+/// * `to_did` - to DID
+/// * `thid` - thread id
+/// * `queries` - stringified queries body
+pub fn save_queries(
+    storage: &dyn DidCommStorage,
+    from_did: &str,
+    to_did: &str,
+    thid: &str,
+    queries: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    storage.put(
+        &format!(
+            "discover_features_{}_{}_{}_{}",
+            from_did,
+            to_did,
+            State::ReceiveQueries,
+            thid
+        ),
+        queries,
+    )?;
+
+    Ok(())
+}
+
+/// Loads the body of received `queries` for two DIDs (from -> to). Entry key will be
+/// discover_features_{from}_{to}_ReceiveQueries_{thid}.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `from_did` - from DID
+/// * `to_did` - to DID
+/// * `thid` - thread id
+///
+/// # Returns
+/// * `String` - stringified queries body
+pub fn get_queries(
+    storage: &dyn DidCommStorage,
+    from_did: &str,
+    to_did: &str,
+    thid: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    storage
+        .get(&format!(
+            "discover_features_{}_{}_{}_{}",
+            from_did,
+            to_did,
+            State::ReceiveQueries,
+            thid
+        ))
+        .map_err(|_| Box::from(format!("no queries found for thread {}", thid)))
+}
@@ -0,0 +1,31 @@
+pub mod datatypes;
+#[cfg(feature = "state_storage")]
+mod features;
+mod requester;
+mod responder;
+
+use crate::protocols::{
+    discover_features::{
+        datatypes::DISCOVER_FEATURES_PROTOCOL_URL,
+        requester::{receive_disclose, send_queries},
+        responder::{receive_queries, send_disclose},
+    },
+    protocol::{generate_receive_step, generate_send_step, Protocol},
+};
+
+/// Creates a new discover_features protocol and maps the specific step handler functions.
+///
+/// # Returns
+/// * `Protocol` - the new Discover Features protocol handler
+pub(crate) fn generate_discover_features_protocol() -> Protocol {
+    Protocol {
+        name: String::from(DISCOVER_FEATURES_PROTOCOL_URL),
+        roles: vec![String::from("requester"), String::from("responder")],
+        steps: vec![
+            generate_send_step("queries", send_queries),
+            generate_receive_step("queries", receive_queries),
+            generate_send_step("disclose", send_disclose),
+            generate_receive_step("disclose", receive_disclose),
+        ],
+    }
+}
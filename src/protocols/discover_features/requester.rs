@@ -0,0 +1,76 @@
+#[cfg(feature = "state_storage")]
+use crate::protocols::{
+    discover_features::datatypes::{State, UserType},
+    thread::{get_thid, transition_state},
+};
+use crate::{
+    datatypes::MessageWithBody,
+    db::DidCommStorage,
+    protocols::{
+        discover_features::datatypes::{DiscloseData, QueriesData},
+        protocol::{generate_step_output, StepResult},
+    },
+    utils::add_to_metadata,
+};
+
+/// Protocol handler for direction: `send`, type: `DISCOVER_FEATURES_PROTOCOL_URL/queries`
+pub fn send_queries(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let queries_message: MessageWithBody<QueriesData> = serde_json::from_str(message)?;
+    let queries = queries_message
+        .body
+        .as_ref()
+        .ok_or("missing queries in body")?;
+    if queries.queries.is_empty() {
+        return Err(Box::from("queries requires at least one query"));
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            transition_state(
+                storage,
+                &get_thid(&queries_message)?,
+                &UserType::Requester,
+                &[State::Unknown],
+                &State::SendQueries,
+            )?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `DISCOVER_FEATURES_PROTOCOL_URL/disclose`
+/// Returns the disclosures as stringified json array in the `disclosures` metadata.
+pub fn receive_disclose(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let disclose_message: MessageWithBody<DiscloseData> = serde_json::from_str(message)?;
+    let disclosures = disclose_message
+        .body
+        .as_ref()
+        .map(|body| body.disclosures.to_owned())
+        .unwrap_or_default();
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            transition_state(
+                storage,
+                &get_thid(&disclose_message)?,
+                &UserType::Requester,
+                &[State::Unknown, State::SendQueries],
+                &State::ReceiveDisclose,
+            )?;
+        } else { }
+    }
+
+    let metadata = add_to_metadata("{}", "disclosures", &serde_json::to_string(&disclosures)?)?;
+    generate_step_output(message, &metadata)
+}
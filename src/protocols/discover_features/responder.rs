@@ -0,0 +1,163 @@
+#[cfg(feature = "state_storage")]
+use crate::{
+    datatypes::HasFromAndTo,
+    protocols::{
+        discover_features::{
+            datatypes::{State, UserType},
+            features::{get_queries, save_queries},
+        },
+        thread::{get_thid, transition_state},
+    },
+};
+use crate::{
+    datatypes::MessageWithBody,
+    db::DidCommStorage,
+    protocol_handler::get_protocols,
+    protocols::{
+        discover_features::datatypes::{
+            DiscloseData,
+            Disclosure,
+            DiscoverFeaturesOptions,
+            QueriesData,
+            Query,
+            FEATURE_TYPE_PROTOCOL,
+        },
+        protocol::{generate_step_output, StepResult},
+    },
+    utils::add_to_metadata,
+};
+
+/// Checks if a feature id matches a query match or a hidden protocol id, that may end with `*`.
+fn matches_id(pattern: &str, id: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => id.starts_with(prefix),
+        None => pattern == id,
+    }
+}
+
+/// Answers queries with the registered protocols, that match at least one query and are not
+/// hidden.
+///
+/// # Arguments
+/// * `queries` - queries to answer
+/// * `hidden_protocols` - ids of protocols, that must not be disclosed
+///
+/// # Returns
+/// * `Vec<Disclosure>` - disclosures of the matching protocols with their roles and steps
+fn get_disclosures(queries: &[Query], hidden_protocols: &[String]) -> Vec<Disclosure> {
+    get_protocols()
+        .iter()
+        .filter(|protocol| {
+            queries.iter().any(|query| {
+                query.feature_type == FEATURE_TYPE_PROTOCOL
+                    && matches_id(&query.r#match, &protocol.name)
+            }) && !hidden_protocols
+                .iter()
+                .any(|hidden| matches_id(hidden, &protocol.name))
+        })
+        .map(|protocol| {
+            let mut steps: Vec<String> = Vec::new();
+            for step in protocol.steps.iter() {
+                if !steps.contains(&step.name) {
+                    steps.push(step.name.to_owned());
+                }
+            }
+            Disclosure {
+                feature_type: FEATURE_TYPE_PROTOCOL.to_string(),
+                id: protocol.name.to_owned(),
+                roles: protocol.roles.to_owned(),
+                steps,
+            }
+        })
+        .collect()
+}
+
+/// Protocol handler for direction: `receive`, type: `DISCOVER_FEATURES_PROTOCOL_URL/queries`
+/// Returns the disclosures, that answer the queries, as stringified json array in the
+/// `disclosures` metadata.
+pub fn receive_queries(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    options: &str,
+    message: &str,
+) -> StepResult {
+    let queries_message: MessageWithBody<QueriesData> = serde_json::from_str(message)?;
+    let options: DiscoverFeaturesOptions = serde_json::from_str(options)?;
+    let queries = queries_message
+        .body
+        .as_ref()
+        .ok_or("missing queries in body")?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = queries_message.get_from_to()?;
+            let thid = get_thid(&queries_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Responder,
+                &[State::Unknown],
+                &State::ReceiveQueries,
+            )?;
+            save_queries(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &thid,
+                &serde_json::to_string(queries)?,
+            )?;
+        } else { }
+    }
+
+    let disclosures = get_disclosures(&queries.queries, &options.hidden_protocols);
+    let metadata = add_to_metadata("{}", "disclosures", &serde_json::to_string(&disclosures)?)?;
+    generate_step_output(message, &metadata)
+}
+
+/// Protocol handler for direction: `send`, type: `DISCOVER_FEATURES_PROTOCOL_URL/disclose`
+/// Fills the body with the answers to the queries of the thread, if no disclosures have been
+/// passed. All protocols, that are not hidden, are disclosed, if no queries have been received.
+pub fn send_disclose(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    options: &str,
+    message: &str,
+) -> StepResult {
+    let mut disclose_message: MessageWithBody<DiscloseData> = serde_json::from_str(message)?;
+    let options: DiscoverFeaturesOptions = serde_json::from_str(options)?;
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut queries = vec![Query {
+        feature_type: FEATURE_TYPE_PROTOCOL.to_string(),
+        r#match: "*".to_string(),
+    }];
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = disclose_message.get_from_to()?;
+            let thid = get_thid(&disclose_message)?;
+            let previous_state = transition_state(
+                storage,
+                &thid,
+                &UserType::Responder,
+                &[State::Unknown, State::ReceiveQueries],
+                &State::SendDisclose,
+            )?;
+            if previous_state == State::ReceiveQueries {
+                let received = get_queries(storage, &from_to.to, &from_to.from, &thid)?;
+                queries = serde_json::from_str::<QueriesData>(&received)?.queries;
+            }
+        } else { }
+    }
+
+    let has_disclosures = match &disclose_message.body {
+        Some(body) => !body.disclosures.is_empty(),
+        None => false,
+    };
+    if !has_disclosures {
+        disclose_message.body = Some(DiscloseData {
+            disclosures: get_disclosures(&queries, &options.hidden_protocols),
+        });
+    }
+
+    generate_step_output(&serde_json::to_string(&disclose_message)?, "{}")
+}
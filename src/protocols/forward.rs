@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use data_encoding::BASE64URL_NOPAD;
+use didcomm_rs::{Attachment, AttachmentData};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::protocol::{generate_receive_step, generate_step_output, Protocol, StepResult};
+use crate::{
+    datatypes::{EncryptionAlgorithm, MessageWithBody},
+    db::DidCommStorage,
+    fill_message_id_and_timestamps,
+    message::encrypt_message_anonymously,
+    resolver::get_did,
+};
+#[cfg(feature = "state_storage")]
+use crate::{db::queue_message, protocols::coordinate_mediation::mediation::get_mediation_route};
+
+pub const FORWARD_PROTOCOL_URL: &str = "https://didcomm.org/routing/2.0";
+
+/// Media type of encrypted messages attached to forward and delivery messages.
+const FORWARDED_MESSAGE_MEDIA_TYPE: &str = "application/didcomm-encrypted+json";
+
+/// Body of forward messages.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ForwardBody {
+    /// DID or key id of the receiver of the attached message
+    pub next: String,
+}
+
+/// Metadata of received forward messages.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardMetadata {
+    /// DID or key id of the receiver, the attached message has to be forwarded to
+    pub next: String,
+    /// stringified encrypted message, that has to be forwarded
+    pub forwarded_message: String,
+    /// recipient, that the message has been queued for, if `next` is in the keylist of a
+    /// mediation granted by the receiver
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queued_for: Option<String>,
+}
+
+/// Creates a new forward protocol and maps the specific step handler functions.
+///
+/// # Returns
+/// * `Protocol` - the new forward protocol handler
+pub fn generate_forward_protocol() -> Protocol {
+    Protocol {
+        name: String::from(FORWARD_PROTOCOL_URL),
+        roles: vec![
+            String::from("sender"),
+            String::from("mediator"),
+            String::from("recipient"),
+        ],
+        steps: vec![generate_receive_step("forward", receive_forward)],
+    }
+}
+
+/// Wraps an encrypted message in a forward message for a mediator and encrypts it anonymously
+/// with the key of the mediator.
+///
+/// # Arguments
+/// * `message` - encrypted message string, that the mediator has to forward
+/// * `next` - DID or key id of the receiver of `message`
+/// * `routing_key` - key id of the mediator
+/// * `routing_public_key` - public key of `routing_key`
+/// * `encryption_algorithm` - content encryption algorithm
+///
+/// # Returns
+/// * `String` - encrypted forward message
+pub fn wrap_in_forward_message(
+    message: &str,
+    next: &str,
+    routing_key: &str,
+    routing_public_key: Vec<u8>,
+    encryption_algorithm: EncryptionAlgorithm,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let forward_message = MessageWithBody {
+        body: Some(ForwardBody {
+            next: next.to_string(),
+        }),
+        created_time: None,
+        expires_time: None,
+        from: None,
+        id: None,
+        pthid: None,
+        r#type: format!("{}/forward", FORWARD_PROTOCOL_URL),
+        thid: None,
+        to: Some(vec![get_did(routing_key).to_string()]),
+        other: HashMap::new(),
+        attachments: vec![get_message_attachment(None, message)],
+    };
+    let forward_message =
+        fill_message_id_and_timestamps(&serde_json::to_string(&forward_message)?)?;
+
+    encrypt_message_anonymously(
+        &forward_message,
+        vec![routing_public_key],
+        encryption_algorithm,
+    )
+}
+
+/// Creates an attachment containing an encrypted message.
+///
+/// # Arguments
+/// * `id` - attachment id
+/// * `message` - stringified encrypted message
+///
+/// # Returns
+/// * `Attachment` - attachment with the base64url encoded message
+pub(crate) fn get_message_attachment(id: Option<String>, message: &str) -> Attachment {
+    Attachment {
+        id,
+        description: None,
+        filename: None,
+        media_type: Some(FORWARDED_MESSAGE_MEDIA_TYPE.to_string()),
+        format: None,
+        lastmod_time: None,
+        byte_count: None,
+        data: AttachmentData {
+            jws: None,
+            hash: None,
+            links: Vec::new(),
+            base64: Some(BASE64URL_NOPAD.encode(message.as_bytes())),
+            json: None,
+        },
+    }
+}
+
+/// Gets the message attached with `get_message_attachment`, attachments with json data are
+/// accepted as well.
+///
+/// # Arguments
+/// * `attachment` - attachment containing a message
+///
+/// # Returns
+/// * `String` - stringified message
+pub(crate) fn get_attached_message(
+    attachment: &Attachment,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let message: Value = match (&attachment.data.base64, &attachment.data.json) {
+        (Some(base64), _) => serde_json::from_slice(
+            &BASE64URL_NOPAD.decode(base64.trim_end_matches('=').as_bytes())?,
+        )?,
+        (None, Some(json)) => serde_json::from_str(json)?,
+        (None, None) => return Err(Box::from("attachment has no message data")),
+    };
+
+    Ok(serde_json::to_string(&message)?)
+}
+
+/// Protocol handler for direction: `receive`, type: `routing/2.0/forward`
+/// Unwraps the attached message and returns it with its receiver as metadata, so it can be
+/// forwarded to `next`. Messages for keys of recipients, that mediation has been granted to, are
+/// added to the message queue of the recipient, so they can be picked up later on.
+pub fn receive_forward(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let parsed_message: MessageWithBody<ForwardBody> = serde_json::from_str(message)?;
+    let next = parsed_message
+        .body
+        .ok_or("body is a required field for forward messages")?
+        .next;
+    let attachment = match parsed_message.attachments.as_slice() {
+        [attachment] => attachment,
+        _ => {
+            return Err(Box::from(
+                "forward messages must have exactly one attachment with the forwarded message",
+            ))
+        }
+    };
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut metadata = ForwardMetadata {
+        next,
+        forwarded_message: get_attached_message(attachment)?,
+        queued_for: None,
+    };
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let route = match get_mediation_route(storage, &metadata.next)? {
+                Some(route) => Some(route),
+                None => get_mediation_route(storage, get_did(&metadata.next))?,
+            };
+            if let Some(route) = route {
+                queue_message(
+                    storage,
+                    &route.recipient_did,
+                    &metadata.next,
+                    &metadata.forwarded_message,
+                )?;
+                metadata.queued_for = Some(route.recipient_did);
+            }
+        } else { }
+    }
+
+    generate_step_output(message, &serde_json::to_string(&metadata)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use x25519_dalek::{PublicKey, StaticSecret};
+
+    use super::*;
+    use crate::{db::MemoryStorage, message::decrypt_message_anonymously};
+
+    #[test]
+    fn can_wrap_and_unwrap_forward_messages() -> Result<(), Box<dyn std::error::Error>> {
+        let mediator_secret = StaticSecret::from([5u8; 32]);
+        let mediator_public = PublicKey::from(&mediator_secret);
+        let inner_message =
+            r#"{"protected":"e30","recipients":[],"ciphertext":"","iv":"","tag":""}"#;
+
+        let wrapped = wrap_in_forward_message(
+            inner_message,
+            "did:example:receiver",
+            "did:example:mediator#key-1",
+            mediator_public.as_bytes().to_vec(),
+            EncryptionAlgorithm::Xc20p,
+        )?;
+        let forward_message = decrypt_message_anonymously(&wrapped, &mediator_secret.to_bytes())?;
+        let parsed: MessageWithBody<ForwardBody> = serde_json::from_str(&forward_message)?;
+        assert_eq!(parsed.to, Some(vec!["did:example:mediator".to_string()]));
+
+        let output = receive_forward(&MemoryStorage::new(), "{}", &forward_message)?;
+        let metadata: ForwardMetadata = serde_json::from_str(&output.metadata)?;
+        assert_eq!(metadata.next, "did:example:receiver");
+        assert_eq!(
+            serde_json::from_str::<Value>(&metadata.forwarded_message)?,
+            serde_json::from_str::<Value>(inner_message)?,
+        );
+
+        Ok(())
+    }
+}
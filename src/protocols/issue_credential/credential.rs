@@ -1,7 +1,6 @@
 #[cfg(feature = "state_storage")]
 use crate::{
-    db::read_db,
-    db::write_db,
+    db::DidCommStorage,
     protocols::issue_credential::datatypes::UserType,
     protocols::issue_credential::datatypes::{CredentialData, State},
 };
@@ -10,6 +9,7 @@ use crate::{
 /// issue_credential_{from}_{to}_{state}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the data in
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 /// * `thid` - thread id
@@ -17,13 +17,14 @@ use crate::{
 /// * `state` - State
 #[cfg(feature = "state_storage")]
 pub fn save_credential(
+    storage: &dyn DidCommStorage,
     from_did: &str,
     to_did: &str,
     thid: &str,
     credential: &str,
     state: &State,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    write_db(
+    storage.put(
         &format!(
             "issue_credential_{}_{}_{}_{}",
             from_did, to_did, state, thid
@@ -38,6 +39,7 @@ pub fn save_credential(
 /// issue_credential_{from}_{to}_{state}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to load the data from
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 /// * `thid` - thread id
@@ -48,12 +50,13 @@ pub fn save_credential(
 #[allow(dead_code)]
 #[cfg(feature = "state_storage")]
 pub fn get_credential(
+    storage: &dyn DidCommStorage,
     from_did: &str,
     to_did: &str,
     thid: &str,
     state: &State,
 ) -> Result<CredentialData, Box<dyn std::error::Error>> {
-    let credential = read_db(&format!(
+    let credential = storage.get(&format!(
         "issue_credential_{from_did}_{to_did}_{state}_{thid}"
     ))?;
     let credential_data: CredentialData = serde_json::from_str(&credential)?;
@@ -64,16 +67,18 @@ pub fn get_credential(
 /// issue_credential_state_{user_type}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the data in
 /// * `state` - State
 /// * `thid` - thread id
 /// * `user_type` - UserType
 #[cfg(feature = "state_storage")]
 pub fn save_state(
+    storage: &dyn DidCommStorage,
     thid: &str,
     state: &State,
     user_type: &UserType,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    write_db(
+    storage.put(
         &format!("issue_credential_state_{}_{}", user_type, thid),
         &state.to_string(),
     )?;
@@ -85,6 +90,7 @@ pub fn save_state(
 /// issue_credential_state_{user_type}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to load the data from
 /// * `thid` - thread id
 /// * `user_type` - UserType
 ///
@@ -92,10 +98,11 @@ pub fn save_state(
 /// * `state` - State stored in db.
 #[cfg(feature = "state_storage")]
 pub fn get_current_state(
+    storage: &dyn DidCommStorage,
     thid: &str,
     user_type: &UserType,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let result = read_db(&format!("issue_credential_state_{}_{}", user_type, thid));
+    let result = storage.get(&format!("issue_credential_state_{}_{}", user_type, thid));
     let state = match result {
         Ok(value) => value,
         Err(_) => "Unknown".to_string(),
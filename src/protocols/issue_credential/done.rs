@@ -3,13 +3,21 @@ use crate::protocols::issue_credential::{
     credential::{get_current_state, save_state},
     datatypes::{State, UserType},
 };
-use crate::protocols::{
-    issue_credential::datatypes::Ack,
-    protocol::{generate_step_output, StepResult},
+use crate::{
+    db::DidCommStorage,
+    protocols::{
+        issue_credential::datatypes::Ack,
+        protocol::{generate_step_output, StepResult},
+    },
 };
 
 /// Protocol handler for direction: `send`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/ack`
-pub fn send_credential_ack(_options: &str, message: &str) -> StepResult {
+pub fn send_credential_ack(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: Ack = serde_json::from_str(message)?;
 
     cfg_if::cfg_if! {
@@ -20,13 +28,14 @@ pub fn send_credential_ack(_options: &str, message: &str) -> StepResult {
                 .ok_or("Thread id can't be empty")?;
 
             let current_state: State = get_current_state(
+                storage,
                 thid,
                 &parsed_message.body.user_type,
             )?.parse()?;
 
             match current_state {
                 State::ReceiveIssueCredential => {
-                    save_state(thid, &State::Acknowledged, &parsed_message.body.user_type)?
+                    save_state(storage, thid, &State::Acknowledged, &parsed_message.body.user_type)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -43,7 +52,12 @@ pub fn send_credential_ack(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/ack`
-pub fn receive_credential_ack(_options: &str, message: &str) -> StepResult {
+pub fn receive_credential_ack(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: Ack = serde_json::from_str(message)?;
 
@@ -58,11 +72,17 @@ pub fn receive_credential_ack(_options: &str, message: &str) -> StepResult {
                 }
                 let current_user_type = UserType::Issuer;
 
-                let current_state: State = get_current_state(&thid, &current_user_type)?.parse()?;
+                let current_state: State =
+                    get_current_state(storage, &thid, &current_user_type)?.parse()?;
 
                 match current_state {
                     State::SendIssueCredential => {
-                        save_state(&thid, &State::Acknowledged, &parsed_message.body.user_type)?
+                        save_state(
+                            storage,
+                            &thid,
+                            &State::Acknowledged,
+                            &parsed_message.body.user_type,
+                        )?
                     }
                     _ => {
                         return Err(Box::from(format!(
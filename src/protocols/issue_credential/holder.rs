@@ -10,6 +10,7 @@ use crate::protocols::issue_credential::{
 };
 use crate::{
     datatypes::{BaseMessage, ExtendedMessage, MessageWithBody},
+    db::DidCommStorage,
     get_from_to_from_message,
     protocols::{
         issue_credential::datatypes::CredentialData,
@@ -18,7 +19,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/propose_credential`
-pub fn send_propose_credential(_options: &str, message: &str) -> StepResult {
+pub fn send_propose_credential(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
     let base_message: BaseMessage = BaseMessage {
         body: HashMap::new(),
@@ -36,11 +42,12 @@ pub fn send_propose_credential(_options: &str, message: &str) -> StepResult {
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let current_state: State = get_current_state(&thid, &UserType::Holder)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Holder)?.parse()?;
 
             match current_state {
                 State::ReceiveOfferCredential | State::Unknown => {
-                    save_state(&thid, &State::SendProposeCredential, &UserType::Holder)?
+                    save_state(storage, &thid, &State::SendProposeCredential, &UserType::Holder)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -57,13 +64,14 @@ pub fn send_propose_credential(_options: &str, message: &str) -> StepResult {
         IssueCredentialType::ProposeCredential,
         &exchange_info.from,
         &exchange_info.to,
-        credential_data,
+        credential_data.clone(),
         &thid,
     )?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
             save_credential(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -77,7 +85,12 @@ pub fn send_propose_credential(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/offer_credential`
-pub fn receive_offer_credential(_options: &str, message: &str) -> StepResult {
+pub fn receive_offer_credential(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: MessageWithBody<CredentialData> = serde_json::from_str(message)?;
 
@@ -106,11 +119,12 @@ pub fn receive_offer_credential(_options: &str, message: &str) -> StepResult {
                 .credential_data
                 .ok_or("Credential data not provided.")?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Holder)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Holder)?.parse()?;
 
             match current_state {
                 State::SendProposeCredential => {
-                    save_state(&thid, &State::ReceiveOfferCredential, &UserType::Holder)?
+                    save_state(storage, &thid, &State::ReceiveOfferCredential, &UserType::Holder)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -122,6 +136,7 @@ pub fn receive_offer_credential(_options: &str, message: &str) -> StepResult {
             };
 
             save_credential(
+                storage,
                 &base_info.to,
                 &base_info.from,
                 &thid,
@@ -135,7 +150,12 @@ pub fn receive_offer_credential(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `send`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/request_credential`
-pub fn send_request_credential(_options: &str, message: &str) -> StepResult {
+pub fn send_request_credential(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
     let base_message: BaseMessage = BaseMessage {
         body: HashMap::new(),
@@ -152,11 +172,12 @@ pub fn send_request_credential(_options: &str, message: &str) -> StepResult {
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let current_state: State = get_current_state(&thid, &UserType::Holder)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Holder)?.parse()?;
 
             match current_state {
                 State::ReceiveOfferCredential | State::Unknown => {
-                    save_state(&thid, &State::SendRequestCredential, &UserType::Holder)?
+                    save_state(storage, &thid, &State::SendRequestCredential, &UserType::Holder)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -173,13 +194,14 @@ pub fn send_request_credential(_options: &str, message: &str) -> StepResult {
         IssueCredentialType::RequestCredential,
         &exchange_info.from,
         &exchange_info.to,
-        credential_data,
+        credential_data.clone(),
         &thid,
     )?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
             save_credential(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -193,7 +215,12 @@ pub fn send_request_credential(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/issue_credential`
-pub fn receive_issue_credential(_options: &str, message: &str) -> StepResult {
+pub fn receive_issue_credential(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: MessageWithBody<CredentialData> = serde_json::from_str(message)?;
 
@@ -202,7 +229,13 @@ pub fn receive_issue_credential(_options: &str, message: &str) -> StepResult {
         body: HashMap::new(),
         from: parsed_message.from.clone(),
         r#type: parsed_message.r#type.clone(),
-        to: Some(parsed_message.to.ok_or("To DID not provided")?.to_vec()),
+        to: Some(
+            parsed_message
+                .to
+                .as_ref()
+                .ok_or("To DID not provided")?
+                .to_vec(),
+        ),
     };
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
@@ -217,11 +250,12 @@ pub fn receive_issue_credential(_options: &str, message: &str) -> StepResult {
                 .credential_data
                 .ok_or("Credential data not provided.")?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Holder)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Holder)?.parse()?;
 
             match current_state {
                 State::SendRequestCredential => {
-                    save_state(&thid, &State::ReceiveIssueCredential, &UserType::Holder)?
+                    save_state(storage, &thid, &State::ReceiveIssueCredential, &UserType::Holder)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -233,6 +267,7 @@ pub fn receive_issue_credential(_options: &str, message: &str) -> StepResult {
             };
 
             save_credential(
+                storage,
                 &base_info.to,
                 &base_info.from,
                 &thid,
@@ -10,6 +10,7 @@ use crate::protocols::issue_credential::{
 };
 use crate::{
     datatypes::{BaseMessage, ExtendedMessage, MessageWithBody},
+    db::DidCommStorage,
     get_from_to_from_message,
     protocols::{
         issue_credential::datatypes::CredentialData,
@@ -18,7 +19,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/offer_credential`
-pub fn send_offer_credential(_options: &str, message: &str) -> StepResult {
+pub fn send_offer_credential(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
     let base_message: BaseMessage = BaseMessage {
         body: HashMap::new(),
@@ -37,16 +43,17 @@ pub fn send_offer_credential(_options: &str, message: &str) -> StepResult {
         IssueCredentialType::OfferCredential,
         &exchange_info.from,
         &exchange_info.to,
-        credential_data,
+        credential_data.clone(),
         &thid,
     )?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let current_state: State = get_current_state(&thid, &UserType::Issuer)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Issuer)?.parse()?;
             match current_state {
                 State::ReceiveProposeCredential | State::Unknown => {
-                    save_state(&thid, &State::SendOfferCredential, &UserType::Issuer)?
+                    save_state(storage, &thid, &State::SendOfferCredential, &UserType::Issuer)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -58,6 +65,7 @@ pub fn send_offer_credential(_options: &str, message: &str) -> StepResult {
             };
 
             save_credential(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -71,7 +79,12 @@ pub fn send_offer_credential(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/request_credential`
-pub fn receive_request_credential(_options: &str, message: &str) -> StepResult {
+pub fn receive_request_credential(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: MessageWithBody<CredentialData> = serde_json::from_str(message)?;
 
@@ -95,11 +108,12 @@ pub fn receive_request_credential(_options: &str, message: &str) -> StepResult {
                 .ok_or("Thread id can't be empty")?;
             let base_info = get_from_to_from_message(&base_message)?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Issuer)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Issuer)?.parse()?;
 
             match current_state {
                 State::SendOfferCredential | State::Unknown => {
-                    save_state(&thid, &State::ReceiveRequestCredential, &UserType::Issuer)?
+                    save_state(storage, &thid, &State::ReceiveRequestCredential, &UserType::Issuer)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -117,6 +131,7 @@ pub fn receive_request_credential(_options: &str, message: &str) -> StepResult {
                 .ok_or("Credential data not provided.")?;
 
             save_credential(
+                storage,
                 &base_info.from,
                 &base_info.to,
                 &thid,
@@ -130,7 +145,12 @@ pub fn receive_request_credential(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/propose-credential`
-pub fn receive_propose_credential(_options: &str, message: &str) -> StepResult {
+pub fn receive_propose_credential(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: MessageWithBody<CredentialData> = serde_json::from_str(message)?;
 
@@ -161,10 +181,11 @@ pub fn receive_propose_credential(_options: &str, message: &str) -> StepResult {
                 .credential_data
                 .ok_or("Credential data not provided.")?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Issuer)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Issuer)?.parse()?;
             match current_state {
                 State::SendOfferCredential | State::Unknown => {
-                    save_state(&thid, &State::ReceiveProposeCredential, &UserType::Issuer)?
+                    save_state(storage, &thid, &State::ReceiveProposeCredential, &UserType::Issuer)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -176,6 +197,7 @@ pub fn receive_propose_credential(_options: &str, message: &str) -> StepResult {
             };
 
             save_credential(
+                storage,
                 &base_info.from,
                 &base_info.to,
                 &thid,
@@ -189,7 +211,12 @@ pub fn receive_propose_credential(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `send`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/issue_credential`
-pub fn send_issue_credential(_options: &str, message: &str) -> StepResult {
+pub fn send_issue_credential(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
     let base_message: BaseMessage = BaseMessage {
         body: HashMap::new(),
@@ -208,16 +235,17 @@ pub fn send_issue_credential(_options: &str, message: &str) -> StepResult {
         IssueCredentialType::IssueCredential,
         &exchange_info.from,
         &exchange_info.to,
-        credential_data,
+        credential_data.clone(),
         &thid,
     )?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let current_state: State = get_current_state(&thid, &UserType::Issuer)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Issuer)?.parse()?;
             match current_state {
                 State::ReceiveRequestCredential => {
-                    save_state(&thid, &State::SendIssueCredential, &UserType::Issuer)?
+                    save_state(storage, &thid, &State::SendIssueCredential, &UserType::Issuer)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -229,6 +257,7 @@ pub fn send_issue_credential(_options: &str, message: &str) -> StepResult {
             };
 
             save_credential(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
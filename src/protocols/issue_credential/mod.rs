@@ -34,6 +34,7 @@ use crate::protocols::{
 pub fn generate_issue_credential_protocol() -> Protocol {
     Protocol {
         name: String::from(ISSUE_CREDENTIAL_PROTOCOL_URL),
+        roles: vec![String::from("issuer"), String::from("holder")],
         steps: vec![
             generate_send_step("propose-credential", send_propose_credential),
             generate_receive_step("propose-credential", receive_propose_credential),
@@ -3,13 +3,21 @@ use crate::protocols::issue_credential::{
     credential::{get_current_state, save_state},
     datatypes::{State, UserType},
 };
-use crate::protocols::{
-    issue_credential::datatypes::ProblemReport,
-    protocol::{generate_step_output, StepResult},
+use crate::{
+    db::DidCommStorage,
+    protocols::{
+        issue_credential::datatypes::ProblemReport,
+        protocol::{generate_step_output, StepResult},
+    },
 };
 
 /// Protocol handler for direction: `send`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/problem-report`
-pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
+pub fn send_problem_report(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let problem_report: ProblemReport = serde_json::from_str(message)?;
 
     cfg_if::cfg_if! {
@@ -20,12 +28,14 @@ pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
                 .as_ref()
                 .ok_or("Thread id can't be empty")?;
             let current_state: State = get_current_state(
+                storage,
                 thid,
                 &problem_report_data.user_type,
             )?.parse()?;
 
             match current_state {
                 State::ReceiveProposeCredential | State::ReceiveOfferCredential => save_state(
+                    storage,
                     thid,
                     &State::ProblemReported,
                     &problem_report_data.user_type,
@@ -45,7 +55,12 @@ pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `ISSUE_CREDENTIAL_PROTOCOL_URL/problem-report`
-pub fn receive_problem_report(_options: &str, message: &str) -> StepResult {
+pub fn receive_problem_report(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let problem_report: ProblemReport = serde_json::from_str(message)?;
 
@@ -64,11 +79,12 @@ pub fn receive_problem_report(_options: &str, message: &str) -> StepResult {
                     )))
                 }
             };
-            let current_state: State = get_current_state(&thid, &current_user_type)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &current_user_type)?.parse()?;
 
             match current_state {
                 State::SendProposeCredential | State::SendOfferCredential => {
-                    save_state(&thid, &State::ProblemReported, &current_user_type)?
+                    save_state(storage, &thid, &State::ProblemReported, &current_user_type)?
                 }
 
                 _ => {
@@ -0,0 +1,121 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+pub const MESSAGE_PICKUP_PROTOCOL_URL: &str = "https://didcomm.org/messagepickup/3.0";
+
+/// Body of `status-request` messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StatusRequestData {
+    /// only count messages forwarded to this DID or key id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient_did: Option<String>,
+}
+
+/// Body of `status` messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct StatusData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient_did: Option<String>,
+    /// filled in by the mediator, when sending the message
+    #[serde(default)]
+    pub message_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longest_waited_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newest_received_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest_received_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub live_delivery: Option<bool>,
+}
+
+/// Body of `delivery-request` messages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeliveryRequestData {
+    /// maximum number of messages to deliver
+    pub limit: usize,
+    /// only deliver messages forwarded to this DID or key id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient_did: Option<String>,
+}
+
+/// Body of `delivery` messages, the messages are sent as attachments.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DeliveryData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient_did: Option<String>,
+}
+
+/// Body of `messages-received` messages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessagesReceivedData {
+    pub message_id_list: Vec<String>,
+}
+
+/// Body of `live-delivery-change` messages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LiveDeliveryChangeData {
+    pub live_delivery: bool,
+}
+
+/// Message of a received `delivery`, as returned in the `messages` metadata.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveredMessage {
+    /// id to acknowledge the message with in `messages-received`
+    pub id: String,
+    /// stringified encrypted message
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum State {
+    SendStatusRequest,
+    ReceiveStatusRequest,
+    SendStatus,
+    ReceiveStatus,
+    SendDeliveryRequest,
+    ReceiveDeliveryRequest,
+    SendDelivery,
+    ReceiveDelivery,
+    Unknown,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::str::FromStr for State {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SendStatusRequest" => Ok(State::SendStatusRequest),
+            "ReceiveStatusRequest" => Ok(State::ReceiveStatusRequest),
+            "SendStatus" => Ok(State::SendStatus),
+            "ReceiveStatus" => Ok(State::ReceiveStatus),
+            "SendDeliveryRequest" => Ok(State::SendDeliveryRequest),
+            "ReceiveDeliveryRequest" => Ok(State::ReceiveDeliveryRequest),
+            "SendDelivery" => Ok(State::SendDelivery),
+            "ReceiveDelivery" => Ok(State::ReceiveDelivery),
+            _ => Ok(State::Unknown),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum UserType {
+    Recipient,
+    Mediator,
+}
+
+impl fmt::Display for UserType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
@@ -0,0 +1,317 @@
+#[cfg(feature = "state_storage")]
+use crate::{
+    datatypes::HasFromAndTo,
+    db::{delete_queued_messages, get_queued_messages},
+    protocols::{
+        coordinate_mediation::{
+            datatypes::UserType as MediationUserType,
+            mediation::get_granted_mediation,
+        },
+        forward::get_message_attachment,
+        message_pickup::{
+            datatypes::{State, UserType},
+            pickup::{get_pickup_request, get_status, save_live_delivery, save_pickup_request},
+        },
+        thread::{get_thid, transition_state},
+    },
+    utils::add_to_metadata,
+};
+use crate::{
+    datatypes::MessageWithBody,
+    db::DidCommStorage,
+    protocols::{
+        message_pickup::datatypes::{
+            DeliveryData,
+            DeliveryRequestData,
+            LiveDeliveryChangeData,
+            MessagesReceivedData,
+            StatusData,
+            StatusRequestData,
+        },
+        protocol::{generate_step_output, StepResult},
+    },
+};
+
+/// Protocol handler for direction: `receive`, type: `MESSAGE_PICKUP_PROTOCOL_URL/status-request`
+pub fn receive_status_request(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let request_message: MessageWithBody<StatusRequestData> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = request_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.to,
+                &from_to.from,
+                &MediationUserType::Mediator,
+            )?;
+            let thid = get_thid(&request_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Mediator,
+                &[State::Unknown],
+                &State::ReceiveStatusRequest,
+            )?;
+            save_pickup_request(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &thid,
+                &serde_json::to_string(&request_message.body.unwrap_or_default())?,
+                &State::ReceiveStatusRequest,
+            )?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `send`, type: `MESSAGE_PICKUP_PROTOCOL_URL/status`
+/// Fills the body of the message with the status of the message queue of the recipient. Messages
+/// are filtered by the `recipient_did` of the body or of the request answered with this message.
+pub fn send_status(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut status_message: MessageWithBody<StatusData> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = status_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &MediationUserType::Mediator,
+            )?;
+            let thid = get_thid(&status_message)?;
+            let previous_state = transition_state(
+                storage,
+                &thid,
+                &UserType::Mediator,
+                &[
+                    State::Unknown,
+                    State::ReceiveStatusRequest,
+                    State::ReceiveDeliveryRequest,
+                ],
+                &State::SendStatus,
+            )?;
+            let requested_recipient_did = match previous_state {
+                State::ReceiveStatusRequest | State::ReceiveDeliveryRequest => {
+                    let request = get_pickup_request(
+                        storage,
+                        &from_to.to,
+                        &from_to.from,
+                        &thid,
+                        &previous_state,
+                    )?;
+                    serde_json::from_str::<StatusRequestData>(&request)?.recipient_did
+                }
+                _ => None,
+            };
+            let recipient_did = status_message
+                .body
+                .and_then(|body| body.recipient_did)
+                .or(requested_recipient_did);
+            let status = get_status(storage, &from_to.to, recipient_did.as_deref())?;
+            status_message.body = Some(status);
+        } else { }
+    }
+
+    generate_step_output(&serde_json::to_string(&status_message)?, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `MESSAGE_PICKUP_PROTOCOL_URL/delivery-request`
+/// Returns the number of messages, that can be delivered, as `messageCount` metadata. A `status`
+/// message has to be sent instead of a `delivery`, if there are no messages.
+pub fn receive_delivery_request(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let request_message: MessageWithBody<DeliveryRequestData> = serde_json::from_str(message)?;
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut metadata = "{}".to_string();
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = request_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.to,
+                &from_to.from,
+                &MediationUserType::Mediator,
+            )?;
+            let request = request_message
+                .body
+                .as_ref()
+                .ok_or("missing delivery request in body")?;
+            let thid = get_thid(&request_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Mediator,
+                &[State::Unknown],
+                &State::ReceiveDeliveryRequest,
+            )?;
+            save_pickup_request(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &thid,
+                &serde_json::to_string(request)?,
+                &State::ReceiveDeliveryRequest,
+            )?;
+
+            let messages =
+                get_queued_messages(storage, &from_to.from, request.recipient_did.as_deref())?;
+            let message_count = messages.len().min(request.limit);
+            metadata = add_to_metadata(&metadata, "messageCount", &message_count.to_string())?;
+        } else { }
+    }
+
+    generate_step_output(message, &metadata)
+}
+
+/// Protocol handler for direction: `send`, type: `MESSAGE_PICKUP_PROTOCOL_URL/delivery`
+/// Attaches the oldest queued messages of the recipient up to the limit of the delivery request.
+pub fn send_delivery(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut delivery_message: MessageWithBody<DeliveryData> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = delivery_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &MediationUserType::Mediator,
+            )?;
+            let thid = get_thid(&delivery_message)?;
+            transition_state(
+                storage,
+                &thid,
+                &UserType::Mediator,
+                &[State::ReceiveDeliveryRequest],
+                &State::SendDelivery,
+            )?;
+            let request: DeliveryRequestData = serde_json::from_str(&get_pickup_request(
+                storage,
+                &from_to.to,
+                &from_to.from,
+                &thid,
+                &State::ReceiveDeliveryRequest,
+            )?)?;
+            let messages =
+                get_queued_messages(storage, &from_to.to, request.recipient_did.as_deref())?;
+            if messages.is_empty() {
+                return Err(Box::from(format!(
+                    "no messages queued for {}, send a status message instead",
+                    from_to.to
+                )));
+            }
+            delivery_message.attachments = messages
+                .iter()
+                .take(request.limit)
+                .map(|queued| {
+                    get_message_attachment(Some(queued.id.to_owned()), &queued.message)
+                })
+                .collect();
+            delivery_message.body = Some(DeliveryData {
+                recipient_did: request.recipient_did,
+            });
+        } else { }
+    }
+
+    generate_step_output(&serde_json::to_string(&delivery_message)?, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `MESSAGE_PICKUP_PROTOCOL_URL/messages-received`
+/// Deletes the received messages from the queue of the recipient and returns the number of
+/// remaining messages as `messageCount` metadata.
+pub fn receive_messages_received(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let received_message: MessageWithBody<MessagesReceivedData> = serde_json::from_str(message)?;
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut metadata = "{}".to_string();
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = received_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.to,
+                &from_to.from,
+                &MediationUserType::Mediator,
+            )?;
+            let message_ids = received_message
+                .body
+                .ok_or("missing message ids in body")?
+                .message_id_list;
+            delete_queued_messages(storage, &from_to.from, &message_ids)?;
+            let message_count = get_queued_messages(storage, &from_to.from, None)?.len();
+            metadata = add_to_metadata(&metadata, "messageCount", &message_count.to_string())?;
+        } else { }
+    }
+
+    generate_step_output(message, &metadata)
+}
+
+/// Protocol handler for direction: `receive`, type:
+/// `MESSAGE_PICKUP_PROTOCOL_URL/live-delivery-change`
+/// Stores the live delivery mode of the recipient and returns it as `liveDelivery` metadata.
+pub fn receive_live_delivery_change(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let change_message: MessageWithBody<LiveDeliveryChangeData> = serde_json::from_str(message)?;
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    let live_delivery = change_message
+        .body
+        .as_ref()
+        .ok_or("missing live_delivery in body")?
+        .live_delivery;
+    #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+    let mut metadata = "{}".to_string();
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = change_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.to,
+                &from_to.from,
+                &MediationUserType::Mediator,
+            )?;
+            save_live_delivery(storage, &from_to.from, live_delivery)?;
+            metadata = add_to_metadata(&metadata, "liveDelivery", &live_delivery.to_string())?;
+        } else { }
+    }
+
+    generate_step_output(message, &metadata)
+}
@@ -0,0 +1,58 @@
+pub mod datatypes;
+mod mediator;
+#[cfg(feature = "state_storage")]
+mod pickup;
+mod recipient;
+
+use crate::protocols::{
+    message_pickup::{
+        datatypes::MESSAGE_PICKUP_PROTOCOL_URL,
+        mediator::{
+            receive_delivery_request,
+            receive_live_delivery_change,
+            receive_messages_received,
+            receive_status_request,
+            send_delivery,
+            send_status,
+        },
+        recipient::{
+            receive_delivery,
+            receive_status,
+            send_delivery_request,
+            send_live_delivery_change,
+            send_messages_received,
+            send_status_request,
+        },
+    },
+    protocol::{generate_authenticated_receive_step, generate_authenticated_send_step, Protocol},
+};
+
+/// Creates a new message_pickup protocol and maps the specific step handler functions.
+/// Steps are matched by their type prefix, so requests have to be checked before the steps
+/// answering them.
+///
+/// # Returns
+/// * `Protocol` - the new Message Pickup protocol handler
+pub(crate) fn generate_message_pickup_protocol() -> Protocol {
+    Protocol {
+        name: String::from(MESSAGE_PICKUP_PROTOCOL_URL),
+        roles: vec![String::from("mediator"), String::from("recipient")],
+        steps: vec![
+            generate_authenticated_send_step("status-request", send_status_request),
+            generate_authenticated_receive_step("status-request", receive_status_request),
+            generate_authenticated_send_step("status", send_status),
+            generate_authenticated_receive_step("status", receive_status),
+            generate_authenticated_send_step("delivery-request", send_delivery_request),
+            generate_authenticated_receive_step("delivery-request", receive_delivery_request),
+            generate_authenticated_send_step("delivery", send_delivery),
+            generate_authenticated_receive_step("delivery", receive_delivery),
+            generate_authenticated_send_step("messages-received", send_messages_received),
+            generate_authenticated_receive_step("messages-received", receive_messages_received),
+            generate_authenticated_send_step("live-delivery-change", send_live_delivery_change),
+            generate_authenticated_receive_step(
+                "live-delivery-change",
+                receive_live_delivery_change,
+            ),
+        ],
+    }
+}
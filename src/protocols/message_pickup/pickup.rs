@@ -0,0 +1,138 @@
+//! Storage of Message Pickup protocol states and requests.
+//!
+//! Entries use `pickup` as protocol prefix instead of `message_pickup`, as keys starting with
+//! `message_` are reserved for raw messages.
+
+use crate::{
+    db::{get_queued_messages, DidCommStorage},
+    protocols::{
+        message_pickup::datatypes::{State, StatusData},
+        thread::ThreadState,
+    },
+    utils::get_now,
+};
+
+impl ThreadState for State {
+    const PROTOCOL: &'static str = "pickup";
+}
+
+/// Saves the body of a received status or delivery request for two DIDs (from -> to). Entry key
+/// will be pickup_{from}_{to}_{state}_{thid}.
+///
+/// # Arguments
+/// * `storage` - storage to save the data in
+/// * `from_did` - from DID
+/// * `to_did` - to DID
+/// * `thid` - thread id
+/// * `request` - stringified request body
+/// * `state` - State
+pub fn save_pickup_request(
+    storage: &dyn DidCommStorage,
+    from_did: &str,
+    to_did: &str,
+    thid: &str,
+    request: &str,
+    state: &State,
+) -> Result<(), Box<dyn std::error::Error>> {
+    storage.put(
+        &format!("pickup_{}_{}_{}_{}", from_did, to_did, state, thid),
+        request,
+    )?;
+
+    Ok(())
+}
+
+/// Loads the body of a received status or delivery request for two DIDs (from -> to). Entry key
+/// will be pickup_{from}_{to}_{state}_{thid}.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `from_did` - from DID
+/// * `to_did` - to DID
+/// * `thid` - thread id
+/// * `state` - State
+///
+/// # Returns
+/// * `String` - stringified request body
+pub fn get_pickup_request(
+    storage: &dyn DidCommStorage,
+    from_did: &str,
+    to_did: &str,
+    thid: &str,
+    state: &State,
+) -> Result<String, Box<dyn std::error::Error>> {
+    storage
+        .get(&format!(
+            "pickup_{}_{}_{}_{}",
+            from_did, to_did, state, thid
+        ))
+        .map_err(|_| Box::from(format!("no {} found for thread {}", state, thid)))
+}
+
+/// Saves, if the messages of a recipient are delivered as soon as they arrive. Entry key will be
+/// live_delivery_{recipient_did}.
+///
+/// # Arguments
+/// * `storage` - storage to save the data in
+/// * `recipient_did` - DID of the recipient
+/// * `live_delivery` - true to enable live delivery
+pub fn save_live_delivery(
+    storage: &dyn DidCommStorage,
+    recipient_did: &str,
+    live_delivery: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    storage.put(
+        &format!("live_delivery_{}", recipient_did),
+        &live_delivery.to_string(),
+    )?;
+
+    Ok(())
+}
+
+/// Checks, if the messages of a recipient are delivered as soon as they arrive.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `recipient_did` - DID of the recipient
+///
+/// # Returns
+/// * `bool` - true if live delivery has been enabled by the recipient
+pub fn get_live_delivery(
+    storage: &dyn DidCommStorage,
+    recipient_did: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    match storage.get(&format!("live_delivery_{}", recipient_did)) {
+        Ok(value) => Ok(value.parse()?),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Gets the status of the message queue of a recipient.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `recipient_did` - DID of the recipient
+/// * `next` - only count messages forwarded to this DID or key id, all messages if `None`
+///
+/// # Returns
+/// * `StatusData` - body for a `status` message
+pub fn get_status(
+    storage: &dyn DidCommStorage,
+    recipient_did: &str,
+    next: Option<&str>,
+) -> Result<StatusData, Box<dyn std::error::Error>> {
+    let messages = get_queued_messages(storage, recipient_did, next)?;
+    let oldest_received_time = messages.first().map(|message| message.received_time);
+
+    Ok(StatusData {
+        recipient_did: next.map(String::from),
+        message_count: messages.len(),
+        longest_waited_seconds: oldest_received_time
+            .map(|received_time| get_now().map(|now| now.saturating_sub(received_time)))
+            .transpose()?,
+        newest_received_time: messages.last().map(|message| message.received_time),
+        oldest_received_time,
+        total_bytes: Some(messages.iter().map(|message| message.message.len()).sum()),
+        live_delivery: Some(get_live_delivery(storage, recipient_did)?),
+    })
+}
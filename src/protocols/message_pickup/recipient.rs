@@ -0,0 +1,230 @@
+#[cfg(feature = "state_storage")]
+use crate::{
+    datatypes::HasFromAndTo,
+    protocols::{
+        coordinate_mediation::{
+            datatypes::UserType as MediationUserType,
+            mediation::get_granted_mediation,
+        },
+        message_pickup::datatypes::{State, UserType},
+        thread::{get_thid, transition_state},
+    },
+};
+use crate::{
+    datatypes::MessageWithBody,
+    db::DidCommStorage,
+    protocols::{
+        forward::get_attached_message,
+        message_pickup::datatypes::{
+            DeliveredMessage,
+            DeliveryData,
+            DeliveryRequestData,
+            LiveDeliveryChangeData,
+            MessagesReceivedData,
+            StatusData,
+            StatusRequestData,
+        },
+        protocol::{generate_step_output, StepResult},
+    },
+    utils::add_to_metadata,
+};
+
+/// Protocol handler for direction: `send`, type: `MESSAGE_PICKUP_PROTOCOL_URL/status-request`
+pub fn send_status_request(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
+    let request_message: MessageWithBody<StatusRequestData> = serde_json::from_str(message)?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = request_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.to,
+                &from_to.from,
+                &MediationUserType::Recipient,
+            )?;
+            transition_state(
+                storage,
+                &get_thid(&request_message)?,
+                &UserType::Recipient,
+                &[State::Unknown],
+                &State::SendStatusRequest,
+            )?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `MESSAGE_PICKUP_PROTOCOL_URL/status`
+/// Returns the number of queued messages as `messageCount` metadata.
+pub fn receive_status(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let status_message: MessageWithBody<StatusData> = serde_json::from_str(message)?;
+    let message_count = status_message
+        .body
+        .as_ref()
+        .ok_or("missing status in body")?
+        .message_count;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = status_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &MediationUserType::Recipient,
+            )?;
+            transition_state(
+                storage,
+                &get_thid(&status_message)?,
+                &UserType::Recipient,
+                &[
+                    State::Unknown,
+                    State::SendStatusRequest,
+                    State::SendDeliveryRequest,
+                ],
+                &State::ReceiveStatus,
+            )?;
+        } else { }
+    }
+
+    let metadata = add_to_metadata("{}", "messageCount", &message_count.to_string())?;
+    generate_step_output(message, &metadata)
+}
+
+/// Protocol handler for direction: `send`, type: `MESSAGE_PICKUP_PROTOCOL_URL/delivery-request`
+pub fn send_delivery_request(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let request_message: MessageWithBody<DeliveryRequestData> = serde_json::from_str(message)?;
+    let request = request_message
+        .body
+        .as_ref()
+        .ok_or("missing delivery request in body")?;
+    if request.limit == 0 {
+        return Err(Box::from(
+            "limit of delivery-request must be greater than 0",
+        ));
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = request_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.to,
+                &from_to.from,
+                &MediationUserType::Recipient,
+            )?;
+            transition_state(
+                storage,
+                &get_thid(&request_message)?,
+                &UserType::Recipient,
+                &[State::Unknown],
+                &State::SendDeliveryRequest,
+            )?;
+        } else { }
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `receive`, type: `MESSAGE_PICKUP_PROTOCOL_URL/delivery`
+/// Returns the attached messages as stringified json array of `DeliveredMessage` in the `messages`
+/// metadata.
+pub fn receive_delivery(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let delivery_message: MessageWithBody<DeliveryData> = serde_json::from_str(message)?;
+    let delivered_messages = delivery_message
+        .attachments
+        .iter()
+        .map(|attachment| {
+            Ok(DeliveredMessage {
+                id: attachment
+                    .id
+                    .to_owned()
+                    .ok_or("delivered messages must have an id")?,
+                message: get_attached_message(attachment)?,
+            })
+        })
+        .collect::<Result<Vec<DeliveredMessage>, Box<dyn std::error::Error>>>()?;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "state_storage")] {
+            let from_to = delivery_message.get_from_to()?;
+            get_granted_mediation(
+                storage,
+                &from_to.from,
+                &from_to.to,
+                &MediationUserType::Recipient,
+            )?;
+            transition_state(
+                storage,
+                &get_thid(&delivery_message)?,
+                &UserType::Recipient,
+                &[State::SendDeliveryRequest],
+                &State::ReceiveDelivery,
+            )?;
+        } else { }
+    }
+
+    let metadata = add_to_metadata(
+        "{}",
+        "messages",
+        &serde_json::to_string(&delivered_messages)?,
+    )?;
+    generate_step_output(message, &metadata)
+}
+
+/// Protocol handler for direction: `send`, type: `MESSAGE_PICKUP_PROTOCOL_URL/messages-received`
+pub fn send_messages_received(
+    _storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let received_message: MessageWithBody<MessagesReceivedData> = serde_json::from_str(message)?;
+    let received = received_message
+        .body
+        .as_ref()
+        .ok_or("missing message ids in body")?;
+    if received.message_id_list.is_empty() {
+        return Err(Box::from(
+            "messages-received requires at least one message id",
+        ));
+    }
+
+    generate_step_output(message, "{}")
+}
+
+/// Protocol handler for direction: `send`, type: `MESSAGE_PICKUP_PROTOCOL_URL/live-delivery-change`
+pub fn send_live_delivery_change(
+    _storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
+    let change_message: MessageWithBody<LiveDeliveryChangeData> = serde_json::from_str(message)?;
+    change_message
+        .body
+        .as_ref()
+        .ok_or("missing live_delivery in body")?;
+
+    generate_step_output(message, "{}")
+}
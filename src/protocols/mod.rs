@@ -1,6 +1,13 @@
+pub mod coordinate_mediation;
 pub mod did_exchange;
+pub mod discover_features;
+pub(crate) mod forward;
 pub mod issue_credential;
+pub mod message_pickup;
+pub mod out_of_band;
 pub(crate) mod pingpong;
 pub mod present_proof;
 pub mod presentation_exchange;
 pub(crate) mod protocol;
+#[cfg(feature = "state_storage")]
+pub(crate) mod thread;
@@ -0,0 +1,65 @@
+use std::fmt;
+
+use didcomm_rs::Attachment;
+use serde::{Deserialize, Serialize};
+
+pub const OUT_OF_BAND_PROTOCOL_URL: &str = "https://didcomm.org/out-of-band/2.0";
+
+/// Body of `invitation` messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InvitationBody {
+    /// code of the goal of the interaction, e.g. `aries.vc.issue`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal_code: Option<String>,
+    /// human readable goal of the interaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal: Option<String>,
+    /// media types of the envelopes, that the inviter accepts, e.g. `didcomm/v2`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub accept: Vec<String>,
+}
+
+/// Out-of-band invitation, shared as plain message or encoded in an `_oob` url.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Invitation {
+    pub r#type: String,
+    pub id: String,
+    /// DID of the inviter, that the DID exchange request has to be sent to
+    pub from: String,
+    #[serde(default)]
+    pub body: InvitationBody,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_time: Option<u64>,
+}
+
+/// Invitation as stored by the inviter and the invitee.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InvitationRecord {
+    pub invitation: Invitation,
+    pub user_type: UserType,
+    /// invitation may only be used for a single DID exchange (inviter only)
+    #[serde(default)]
+    pub single_use: bool,
+    /// ids of the DID exchange threads, that have been started with this invitation
+    #[serde(default)]
+    pub thids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum UserType {
+    Inviter,
+    Invitee,
+}
+
+impl fmt::Display for UserType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
@@ -0,0 +1,218 @@
+use data_encoding::BASE64URL_NOPAD;
+use uuid::Uuid;
+
+use crate::{
+    datatypes::CreateInvitationPayload,
+    protocols::out_of_band::datatypes::{Invitation, InvitationBody, OUT_OF_BAND_PROTOCOL_URL},
+    utils::get_now,
+};
+#[cfg(feature = "state_storage")]
+use crate::{
+    db::DidCommStorage,
+    protocols::out_of_band::datatypes::{InvitationRecord, UserType},
+};
+
+/// Media type of DIDComm v2 messages, accepted if the payload does not specify `accept` values.
+const DEFAULT_ACCEPT: &str = "didcomm/v2";
+
+/// Creates a new out-of-band invitation.
+///
+/// # Arguments
+/// * `payload` - inviter, goal, accepted media types and attachments of the invitation
+///
+/// # Returns
+/// * `Invitation` - new invitation with a random id
+pub fn create_invitation(
+    payload: &CreateInvitationPayload,
+) -> Result<Invitation, Box<dyn std::error::Error>> {
+    let created_time = get_now()?;
+
+    Ok(Invitation {
+        r#type: format!("{}/invitation", OUT_OF_BAND_PROTOCOL_URL),
+        id: Uuid::new_v4().to_simple().to_string(),
+        from: payload.from.to_owned(),
+        body: InvitationBody {
+            goal_code: payload.goal_code.to_owned(),
+            goal: payload.goal.to_owned(),
+            accept: payload
+                .accept
+                .to_owned()
+                .unwrap_or_else(|| vec![DEFAULT_ACCEPT.to_string()]),
+        },
+        attachments: payload.attachments.to_owned(),
+        created_time: Some(created_time),
+        expires_time: payload
+            .expires_in
+            .map(|expires_in| created_time + expires_in),
+    })
+}
+
+/// Encodes an invitation as base64url `_oob` query parameter of an url.
+///
+/// # Arguments
+/// * `base_url` - url of the inviter, e.g. `https://example.com/path`
+/// * `invitation` - invitation to encode
+///
+/// # Returns
+/// * `String` - invitation url
+pub fn get_invitation_url(
+    base_url: &str,
+    invitation: &Invitation,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let separator = if base_url.contains('?') { '&' } else { '?' };
+    let encoded = BASE64URL_NOPAD.encode(serde_json::to_string(invitation)?.as_bytes());
+
+    Ok(format!("{}{}_oob={}", base_url, separator, encoded))
+}
+
+/// Parses the invitation encoded in the `_oob` query parameter of an url.
+///
+/// # Arguments
+/// * `invitation_url` - url created with `get_invitation_url`
+///
+/// # Returns
+/// * `Invitation` - decoded invitation
+pub fn parse_invitation_url(
+    invitation_url: &str,
+) -> Result<Invitation, Box<dyn std::error::Error>> {
+    let (_, query) = invitation_url
+        .split_once('?')
+        .ok_or("invitation url has no query")?;
+    let encoded = query
+        .split('#')
+        .next()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("_oob="))
+        .ok_or("invitation url has no _oob parameter")?;
+    let encoded = encoded.trim_end_matches("%3D").trim_end_matches('=');
+    let invitation: Invitation =
+        serde_json::from_slice(&BASE64URL_NOPAD.decode(encoded.as_bytes())?)?;
+    if invitation.r#type != format!("{}/invitation", OUT_OF_BAND_PROTOCOL_URL) {
+        return Err(Box::from(format!(
+            "unsupported invitation type {}",
+            invitation.r#type
+        )));
+    }
+
+    Ok(invitation)
+}
+
+/// Saves an invitation. Entry key will be out_of_band_invitation_{user_type}_{id}.
+///
+/// # Arguments
+/// * `storage` - storage to save the data in
+/// * `record` - invitation with its usage
+#[cfg(feature = "state_storage")]
+pub fn save_invitation(
+    storage: &dyn DidCommStorage,
+    record: &InvitationRecord,
+) -> Result<(), Box<dyn std::error::Error>> {
+    storage.put(
+        &format!(
+            "out_of_band_invitation_{}_{}",
+            record.user_type, record.invitation.id
+        ),
+        &serde_json::to_string(record)?,
+    )?;
+
+    Ok(())
+}
+
+/// Loads an invitation. Entry key will be out_of_band_invitation_{user_type}_{id}.
+///
+/// # Arguments
+/// * `storage` - storage to load the data from
+/// * `id` - invitation id
+/// * `user_type` - UserType
+///
+/// # Returns
+/// * `Option<InvitationRecord>` - invitation with its usage, `None` if it is not known
+#[cfg(feature = "state_storage")]
+pub fn get_invitation(
+    storage: &dyn DidCommStorage,
+    id: &str,
+    user_type: &UserType,
+) -> Result<Option<InvitationRecord>, Box<dyn std::error::Error>> {
+    match storage.get(&format!("out_of_band_invitation_{}_{}", user_type, id)) {
+        Ok(value) => Ok(Some(serde_json::from_str(&value)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Links a DID exchange thread to the invitation, that is referenced as its parent thread. Threads
+/// with a parent thread, that is no known invitation, are not linked.
+///
+/// # Arguments
+/// * `storage` - storage to save the data in
+/// * `pthid` - parent thread id of the DID exchange
+/// * `user_type` - UserType
+/// * `inviter_did` - DID of the inviter taking part in the DID exchange
+/// * `thid` - thread id of the DID exchange
+#[cfg(feature = "state_storage")]
+pub fn use_invitation(
+    storage: &dyn DidCommStorage,
+    pthid: &str,
+    user_type: &UserType,
+    inviter_did: &str,
+    thid: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut record = match get_invitation(storage, pthid, user_type)? {
+        Some(record) => record,
+        None => return Ok(()),
+    };
+    if record.invitation.from != inviter_did {
+        return Err(Box::from(format!(
+            "invitation {} has not been created by {}",
+            pthid, inviter_did
+        )));
+    }
+    if let Some(expires_time) = record.invitation.expires_time {
+        if expires_time < get_now()? {
+            return Err(Box::from(format!("invitation {} has expired", pthid)));
+        }
+    }
+    if record.thids.iter().any(|used_thid| used_thid == thid) {
+        return Ok(());
+    }
+    if record.single_use && !record.thids.is_empty() {
+        return Err(Box::from(format!(
+            "invitation {} has already been used",
+            pthid
+        )));
+    }
+    record.thids.push(thid.to_string());
+
+    save_invitation(storage, &record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_encode_and_parse_invitation_urls() -> Result<(), Box<dyn std::error::Error>> {
+        let invitation = create_invitation(&CreateInvitationPayload {
+            from: "did:example:inviter".to_string(),
+            goal_code: Some("aries.vc.issue".to_string()),
+            goal: Some("issue a credential".to_string()),
+            accept: None,
+            attachments: Vec::new(),
+            expires_in: None,
+            single_use: false,
+            base_url: "https://example.com".to_string(),
+        })?;
+
+        let url = get_invitation_url("https://example.com/path?lang=en", &invitation)?;
+        assert!(url.starts_with("https://example.com/path?lang=en&_oob="));
+        let parsed = parse_invitation_url(&url)?;
+        assert_eq!(parsed.id, invitation.id);
+        assert_eq!(parsed.from, "did:example:inviter");
+        assert_eq!(parsed.body.goal_code, Some("aries.vc.issue".to_string()));
+        assert_eq!(parsed.body.accept, vec![DEFAULT_ACCEPT.to_string()]);
+
+        assert!(parse_invitation_url("https://example.com/path?lang=en").is_err());
+
+        Ok(())
+    }
+}
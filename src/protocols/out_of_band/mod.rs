@@ -0,0 +1,2 @@
+pub mod datatypes;
+pub mod invitation;
@@ -7,7 +7,7 @@ use super::protocol::{
     Protocol,
     StepResult,
 };
-use crate::datatypes::MessageWithBody;
+use crate::{datatypes::MessageWithBody, db::DidCommStorage};
 
 pub const PING_PONG_PROTOCOL_URL: &str = "https://didcomm.org/trust_ping/1.0";
 
@@ -24,6 +24,7 @@ pub struct PingBody {
 pub fn generate_ping_pong_protocol() -> Protocol {
     Protocol {
         name: String::from(PING_PONG_PROTOCOL_URL),
+        roles: vec![String::from("sender"), String::from("receiver")],
         steps: vec![
             generate_send_step("ping", send_ping),
             generate_send_step("ping_response", send_pong),
@@ -34,7 +35,7 @@ pub fn generate_ping_pong_protocol() -> Protocol {
 }
 
 /// Protocol handler for direction: `send`, type: `trust_ping/ping`
-pub fn send_ping(_options: &str, message: &str) -> StepResult {
+pub fn send_ping(_storage: &dyn DidCommStorage, _options: &str, message: &str) -> StepResult {
     let mut parsed_message: MessageWithBody<PingBody> = serde_json::from_str(message)?;
     parsed_message.body = Some(PingBody {
         response_requested: Some(true),
@@ -43,16 +44,16 @@ pub fn send_ping(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `send`, type: `trust_ping/pong`
-pub fn send_pong(_options: &str, message: &str) -> StepResult {
+pub fn send_pong(_storage: &dyn DidCommStorage, _options: &str, message: &str) -> StepResult {
     generate_step_output(message, "{}")
 }
 
 /// Protocol handler for direction: `receive`, type: `trust_ping/ping`
-pub fn receive_ping(_options: &str, message: &str) -> StepResult {
+pub fn receive_ping(_storage: &dyn DidCommStorage, _options: &str, message: &str) -> StepResult {
     generate_step_output(message, "{}")
 }
 
 /// Protocol handler for direction: `receive`, type: `trust_ping/pong`
-pub fn receive_pong(_options: &str, message: &str) -> StepResult {
+pub fn receive_pong(_storage: &dyn DidCommStorage, _options: &str, message: &str) -> StepResult {
     generate_step_output(message, "{}")
 }
@@ -5,6 +5,7 @@ use crate::protocols::present_proof::{
 };
 use crate::{
     datatypes::MessageWithBody,
+    db::DidCommStorage,
     protocols::{
         present_proof::datatypes::AckData,
         protocol::{generate_step_output, StepResult},
@@ -12,7 +13,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `PRESENT_PROOF_PROTOCOL_URL/ack`
-pub fn send_presentation_ack(_options: &str, message: &str) -> StepResult {
+pub fn send_presentation_ack(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let ack_message: MessageWithBody<AckData> = serde_json::from_str(message)?;
 
     cfg_if::cfg_if! {
@@ -22,11 +28,12 @@ pub fn send_presentation_ack(_options: &str, message: &str) -> StepResult {
                 .as_ref()
                 .ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(thid, &UserType::Verifier)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, thid, &UserType::Verifier)?.parse()?;
 
             match current_state {
                 State::PresentationReceived => {
-                    save_state(thid, &State::Acknowledged, &UserType::Verifier)?
+                    save_state(storage, thid, &State::Acknowledged, &UserType::Verifier)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -43,7 +50,12 @@ pub fn send_presentation_ack(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `PRESENT_PROOF_PROTOCOL_URL/ack`
-pub fn receive_presentation_ack(_options: &str, message: &str) -> StepResult {
+pub fn receive_presentation_ack(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let ack_message: MessageWithBody<AckData> = serde_json::from_str(message)?;
 
@@ -51,11 +63,12 @@ pub fn receive_presentation_ack(_options: &str, message: &str) -> StepResult {
         if #[cfg(feature = "state_storage")] {
             let thid = ack_message.thid.ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Prover)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Prover)?.parse()?;
 
             match current_state {
                 State::PresentationSent => {
-                    save_state(&thid, &State::Acknowledged, &UserType::Prover)?;
+                    save_state(storage, &thid, &State::Acknowledged, &UserType::Prover)?;
                 }
                 _ => {
                     return Err(Box::from(format!(
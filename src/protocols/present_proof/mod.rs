@@ -24,6 +24,7 @@ use crate::protocols::{
 pub fn generate_present_proof_protocol() -> Protocol {
     Protocol {
         name: String::from(PRESENT_PROOF_PROTOCOL_URL),
+        roles: vec![String::from("verifier"), String::from("prover")],
         steps: vec![
             generate_send_step("request-presentation", send_request_presentation),
             generate_receive_step("presentation", receive_presentation),
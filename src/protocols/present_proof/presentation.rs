@@ -1,5 +1,5 @@
 use crate::{
-    db::{read_db, write_db},
+    db::DidCommStorage,
     protocols::present_proof::datatypes::{State, UserType},
 };
 
@@ -7,19 +7,21 @@ use crate::{
 /// present_proof_{from}_{to}_{state}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the data in
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 /// * `thid` - thread id
 /// * `presentation` - presentation data
 /// * `state` - State
 pub fn save_presentation(
+    storage: &dyn DidCommStorage,
     from_did: &str,
     to_did: &str,
     thid: &str,
     presentation: &str,
     state: &State,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    write_db(
+    storage.put(
         &format!("present_proof_{}_{}_{}_{}", from_did, to_did, state, thid),
         presentation,
     )?;
@@ -31,15 +33,17 @@ pub fn save_presentation(
 /// present_proof_state_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the data in
 /// * `state` - State
 /// * `thid` - thread id
 /// * `user_type` - UserType
 pub fn save_state(
+    storage: &dyn DidCommStorage,
     thid: &str,
     state: &State,
     user_type: &UserType,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    write_db(
+    storage.put(
         &format!("present_proof_state_{}_{}", user_type, thid),
         &state.to_string(),
     )?;
@@ -51,16 +55,18 @@ pub fn save_state(
 /// present_proof_state_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to load the data from
 /// * `thid` - thread id
 /// * `user_type` - UserType
 ///
 /// # Returns
 /// * `state` - State stored in db.
 pub fn get_current_state(
+    storage: &dyn DidCommStorage,
     thid: &str,
     user_type: &UserType,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let result = read_db(&format!("present_proof_state_{}_{}", user_type, thid));
+    let result = storage.get(&format!("present_proof_state_{}_{}", user_type, thid));
     let state = match result {
         Ok(value) => value,
         Err(_) => "Unknown".to_string(),
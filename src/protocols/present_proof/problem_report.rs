@@ -5,6 +5,7 @@ use crate::protocols::present_proof::{
 };
 use crate::{
     datatypes::MessageWithBody,
+    db::DidCommStorage,
     protocols::{
         present_proof::datatypes::ProblemReportData,
         protocol::{generate_step_output, StepResult},
@@ -12,7 +13,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `PRESENT_PROOF_PROTOCOL_URL/problem-report`
-pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
+pub fn send_problem_report(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let problem_report_message: MessageWithBody<ProblemReportData> = serde_json::from_str(message)?;
 
     cfg_if::cfg_if! {
@@ -25,7 +31,8 @@ pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
                 .thid
                 .as_ref()
                 .ok_or("Thread id can't be empty")?;
-            let current_state: State = get_current_state(thid, &problem_report_data.user_type)?
+            let current_state: State =
+                get_current_state(storage, thid, &problem_report_data.user_type)?
                 .parse()?;
 
             match current_state {
@@ -35,6 +42,7 @@ pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
                 | State::PresentationReceived
                 | State::PresentationProposalReceived
                 | State::PresentationProposed => save_state(
+                    storage,
                     thid,
                     &State::ProblemReported,
                     &problem_report_data.user_type,
@@ -54,7 +62,12 @@ pub fn send_problem_report(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `PRESENT_PROOF_PROTOCOL_URL/problem-report`
-pub fn receive_problem_report(_options: &str, message: &str) -> StepResult {
+pub fn receive_problem_report(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let problem_report_message: MessageWithBody<ProblemReportData> = serde_json::from_str(message)?;
 
@@ -79,7 +92,7 @@ pub fn receive_problem_report(_options: &str, message: &str) -> StepResult {
                 )))
             }
         };
-        let current_state: State = get_current_state(&thid, &current_user_type)?.parse()?;
+        let current_state: State = get_current_state(storage, &thid, &current_user_type)?.parse()?;
 
         match current_state {
             State::PresentationRequested
@@ -88,7 +101,7 @@ pub fn receive_problem_report(_options: &str, message: &str) -> StepResult {
             | State::PresentationReceived
             | State::PresentationProposalReceived
             | State::PresentationProposed => {
-                save_state(&thid, &State::ProblemReported, &current_user_type)?
+                save_state(storage, &thid, &State::ProblemReported, &current_user_type)?
             }
             _ => {
                 return Err(Box::from(format!(
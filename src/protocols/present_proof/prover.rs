@@ -1,6 +1,4 @@
 #[cfg(feature = "state_storage")]
-use super::datatypes::PROPOSAL_PROTOCOL_URL;
-#[cfg(feature = "state_storage")]
 use crate::{
     datatypes::HasFromAndTo,
     protocols::present_proof::{
@@ -10,6 +8,7 @@ use crate::{
 };
 use crate::{
     datatypes::MessageWithBody,
+    db::DidCommStorage,
     protocols::{
         present_proof::datatypes::{PresentationData, ProposalData, RequestData},
         protocol::{generate_step_output, StepResult},
@@ -17,7 +16,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `PRESENT_PROOF_PROTOCOL_URL/presentation`
-pub fn send_presentation(_options: &str, message: &str) -> StepResult {
+pub fn send_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let presentation_message: MessageWithBody<PresentationData> = serde_json::from_str(message)?;
 
     cfg_if::cfg_if! {
@@ -32,10 +36,11 @@ pub fn send_presentation(_options: &str, message: &str) -> StepResult {
                 .as_ref()
                 .ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(thid, &UserType::Prover)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, thid, &UserType::Prover)?.parse()?;
             match current_state {
                 State::PresentationRequestReceived => {
-                    save_state(thid, &State::PresentationSent, &UserType::Prover)?
+                    save_state(storage, thid, &State::PresentationSent, &UserType::Prover)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -47,6 +52,7 @@ pub fn send_presentation(_options: &str, message: &str) -> StepResult {
             };
 
             save_presentation(
+                storage,
                 &from_to.from,
                 &from_to.to,
                 thid,
@@ -60,7 +66,12 @@ pub fn send_presentation(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `PRESENT_PROOF_PROTOCOL_URL/request_presentation`
-pub fn receive_request_presentation(_options: &str, message: &str) -> StepResult {
+pub fn receive_request_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let request_message: MessageWithBody<RequestData> = serde_json::from_str(message)?;
 
@@ -76,9 +87,10 @@ pub fn receive_request_presentation(_options: &str, message: &str) -> StepResult
             .to_owned()
             .ok_or("Thread id can't be empty")?;
 
-        let current_state: State = get_current_state(&thid, &UserType::Prover)?.parse()?;
+        let current_state: State = get_current_state(storage, &thid, &UserType::Prover)?.parse()?;
         match current_state {
             State::PresentationProposed | State::Unknown => save_state(
+                storage,
                 &thid,
                 &State::PresentationRequestReceived,
                 &UserType::Prover,
@@ -93,6 +105,7 @@ pub fn receive_request_presentation(_options: &str, message: &str) -> StepResult
         };
 
         save_presentation(
+            storage,
             &from_to.to,
             &from_to.from,
             &thid,
@@ -106,7 +119,12 @@ pub fn receive_request_presentation(_options: &str, message: &str) -> StepResult
 }
 
 /// Protocol handler for direction: `send`, type: `PRESENT_PROOF_PROTOCOL_URL/propose-presentation`
-pub fn send_propose_presentation(_options: &str, message: &str) -> StepResult {
+pub fn send_propose_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let proposal_message: MessageWithBody<ProposalData> = serde_json::from_str(message)?;
 
     cfg_if::cfg_if! {
@@ -115,11 +133,8 @@ pub fn send_propose_presentation(_options: &str, message: &str) -> StepResult {
                 .body
                 .as_ref()
                 .ok_or("missing proposal data in body")?;
-            if proposal_data.presentation_proposal.r#type != PROPOSAL_PROTOCOL_URL {
-                return Err(Box::from(format!(
-                    r#"invalid type in proposal: "{}", must be "{}"#,
-                    &proposal_data.presentation_proposal.r#type, PROPOSAL_PROTOCOL_URL
-                )));
+            if proposal_data.proposals_attach.is_empty() {
+                return Err(Box::from("missing proposal attachments in body"));
             }
             let from_to = proposal_message.get_from_to()?;
             let thid = proposal_message
@@ -127,11 +142,12 @@ pub fn send_propose_presentation(_options: &str, message: &str) -> StepResult {
                 .as_ref()
                 .ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(thid, &UserType::Prover)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, thid, &UserType::Prover)?.parse()?;
 
             match current_state {
                 State::PresentationRequestReceived | State::Unknown => {
-                    save_state(thid, &State::PresentationProposed, &UserType::Prover)?
+                    save_state(storage, thid, &State::PresentationProposed, &UserType::Prover)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -143,6 +159,7 @@ pub fn send_propose_presentation(_options: &str, message: &str) -> StepResult {
             };
 
             save_presentation(
+                storage,
                 &from_to.from,
                 &from_to.to,
                 thid,
@@ -1,6 +1,4 @@
 #[cfg(feature = "state_storage")]
-use super::datatypes::PROPOSAL_PROTOCOL_URL;
-#[cfg(feature = "state_storage")]
 use crate::{
     datatypes::HasFromAndTo,
     protocols::present_proof::{
@@ -10,6 +8,7 @@ use crate::{
 };
 use crate::{
     datatypes::MessageWithBody,
+    db::DidCommStorage,
     protocols::{
         present_proof::datatypes::{PresentationData, ProposalData, RequestData},
         protocol::{generate_step_output, StepResult},
@@ -17,7 +16,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `PRESENT_PROOF_PROTOCOL_URL/request-presentation`
-pub fn send_request_presentation(_options: &str, message: &str) -> StepResult {
+pub fn send_request_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let request_message: MessageWithBody<RequestData> = serde_json::from_str(message)?;
 
     cfg_if::cfg_if! {
@@ -32,10 +36,10 @@ pub fn send_request_presentation(_options: &str, message: &str) -> StepResult {
             .as_ref()
             .ok_or("Thread id can't be empty")?;
 
-        let current_state: State = get_current_state(thid, &UserType::Verifier)?.parse()?;
+        let current_state: State = get_current_state(storage, thid, &UserType::Verifier)?.parse()?;
         match current_state {
             State::PresentationProposalReceived | State::Unknown => {
-                save_state(thid, &State::PresentationRequested, &UserType::Verifier)?
+                save_state(storage, thid, &State::PresentationRequested, &UserType::Verifier)?
             }
             _ => {
                 return Err(Box::from(format!(
@@ -47,6 +51,7 @@ pub fn send_request_presentation(_options: &str, message: &str) -> StepResult {
         };
 
         save_presentation(
+            storage,
             &from_to.from,
             &from_to.to,
             thid,
@@ -60,7 +65,12 @@ pub fn send_request_presentation(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `PRESENT_PROOF_PROTOCOL_URL/presentation`
-pub fn receive_presentation(_options: &str, message: &str) -> StepResult {
+pub fn receive_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let presentation_message: MessageWithBody<PresentationData> = serde_json::from_str(message)?;
 
@@ -76,10 +86,11 @@ pub fn receive_presentation(_options: &str, message: &str) -> StepResult {
                 .as_ref()
                 .ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(thid, &UserType::Verifier)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, thid, &UserType::Verifier)?.parse()?;
             match current_state {
                 State::PresentationRequested => {
-                    save_state(thid, &State::PresentationReceived, &UserType::Verifier)?
+                    save_state(storage, thid, &State::PresentationReceived, &UserType::Verifier)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -91,6 +102,7 @@ pub fn receive_presentation(_options: &str, message: &str) -> StepResult {
             };
 
             save_presentation(
+                storage,
                 &from_to.from,
                 &from_to.to,
                 thid,
@@ -104,7 +116,12 @@ pub fn receive_presentation(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `PRESENT_PROOF_PROTOCOL_URL/propose-presentation`
-pub fn receive_propose_presentation(_options: &str, message: &str) -> StepResult {
+pub fn receive_propose_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let proposal_message: MessageWithBody<ProposalData> = serde_json::from_str(message)?;
 
@@ -114,11 +131,8 @@ pub fn receive_propose_presentation(_options: &str, message: &str) -> StepResult
                 .body
                 .as_ref()
                 .ok_or("missing proposal data in body")?;
-            if proposal_data.presentation_proposal.r#type != PROPOSAL_PROTOCOL_URL {
-                return Err(Box::from(format!(
-                    r#"invalid type in proposal: "{}", must be "{}"#,
-                    &proposal_data.presentation_proposal.r#type, PROPOSAL_PROTOCOL_URL
-                )));
+            if proposal_data.proposals_attach.is_empty() {
+                return Err(Box::from("missing proposal attachments in body"));
             }
             let from_to = proposal_message.get_from_to()?;
             let thid = proposal_message
@@ -127,9 +141,11 @@ pub fn receive_propose_presentation(_options: &str, message: &str) -> StepResult
                 .to_owned()
                 .ok_or("Thread id can't be empty")?;
 
-            let current_state: State = get_current_state(thid, &UserType::Verifier)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, thid, &UserType::Verifier)?.parse()?;
             match current_state {
                 State::PresentationRequested | State::Unknown => save_state(
+                    storage,
                     thid,
                     &State::PresentationProposalReceived,
                     &UserType::Verifier,
@@ -144,6 +160,7 @@ pub fn receive_propose_presentation(_options: &str, message: &str) -> StepResult
             };
 
             save_presentation(
+                storage,
                 &from_to.from,
                 &from_to.to,
                 thid,
@@ -10,6 +10,7 @@ use crate::protocols::presentation_exchange::{
 };
 use crate::{
     datatypes::{BaseMessage, ExtendedMessage, MessageWithBody},
+    db::DidCommStorage,
     get_from_to_from_message,
     protocols::{
         presentation_exchange::datatypes::PresentationExchangeData,
@@ -18,7 +19,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `PRESENTATION_EXCHANGE_PROTOCOL_URI/propose-presentation`
-pub fn send_propose_presentation(_options: &str, message: &str) -> StepResult {
+pub fn send_propose_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
     let base_message: BaseMessage = BaseMessage {
         body: HashMap::new(),
@@ -39,11 +45,12 @@ pub fn send_propose_presentation(_options: &str, message: &str) -> StepResult {
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let current_state: State = get_current_state(&thid, &UserType::Holder)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Holder)?.parse()?;
 
             match current_state {
                 State::ReceivePresentatonRequest | State::Unknown => {
-                    save_state(&thid, &State::SendProposePresentation, &UserType::Holder)?
+                    save_state(storage, &thid, &State::SendProposePresentation, &UserType::Holder)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -60,13 +67,14 @@ pub fn send_propose_presentation(_options: &str, message: &str) -> StepResult {
         PresentationExchangeType::ProposePresentation,
         &exchange_info.from,
         &exchange_info.to,
-        presentation_exchange_data,
+        presentation_exchange_data.clone(),
         &thid,
     )?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
             save_presentation_exchange(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -80,7 +88,12 @@ pub fn send_propose_presentation(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `PRESENTATION_EXCHANGE_PROTOCOL_URI/request-presentation`
-pub fn receive_request_presentation(_options: &str, message: &str) -> StepResult {
+pub fn receive_request_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: MessageWithBody<PresentationExchangeData> = serde_json::from_str(message)?;
 
@@ -109,11 +122,17 @@ pub fn receive_request_presentation(_options: &str, message: &str) -> StepResult
                 .presentation_exchange_data
                 .ok_or("Presentation exchange data not provided.")?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Holder)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Holder)?.parse()?;
 
             match current_state {
                 State::Unknown | State::SendProposePresentation => {
-                    save_state(&thid, &State::ReceivePresentatonRequest, &UserType::Holder)?
+                    save_state(
+                        storage,
+                        &thid,
+                        &State::ReceivePresentatonRequest,
+                        &UserType::Holder,
+                    )?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -125,6 +144,7 @@ pub fn receive_request_presentation(_options: &str, message: &str) -> StepResult
             };
 
             save_presentation_exchange(
+                storage,
                 &base_info.to,
                 &base_info.from,
                 &thid,
@@ -138,7 +158,12 @@ pub fn receive_request_presentation(_options: &str, message: &str) -> StepResult
 }
 
 /// Protocol handler for direction: `send`, type: `PRESENTATION_EXCHANGE_PROTOCOL_URI/presentation`
-pub fn send_presentation(_options: &str, message: &str) -> StepResult {
+pub fn send_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
     let base_message: BaseMessage = BaseMessage {
         body: HashMap::new(),
@@ -159,11 +184,12 @@ pub fn send_presentation(_options: &str, message: &str) -> StepResult {
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let current_state: State = get_current_state(&thid, &UserType::Holder)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Holder)?.parse()?;
 
             match current_state {
                 State::ReceivePresentatonRequest => {
-                    save_state(&thid, &State::SendPresentation, &UserType::Holder)?
+                    save_state(storage, &thid, &State::SendPresentation, &UserType::Holder)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -180,13 +206,14 @@ pub fn send_presentation(_options: &str, message: &str) -> StepResult {
         PresentationExchangeType::Presentation,
         &exchange_info.from,
         &exchange_info.to,
-        presentation_exchange_data,
+        presentation_exchange_data.clone(),
         &thid,
     )?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
             save_presentation_exchange(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -21,6 +21,7 @@ use crate::protocols::{
 pub fn generate_presentation_exchange_protocol() -> Protocol {
     Protocol {
         name: String::from(PRESENTATION_EXCHANGE_PROTOCOL_URL),
+        roles: vec![String::from("verifier"), String::from("prover")],
         steps: vec![
             generate_send_step("request-presentation", send_request_presentation),
             generate_receive_step("request-presentation", receive_request_presentation),
@@ -1,5 +1,5 @@
 use crate::{
-    db::{read_db, write_db},
+    db::DidCommStorage,
     protocols::presentation_exchange::datatypes::{PresentationExchangeData, State, UserType},
 };
 
@@ -7,19 +7,21 @@ use crate::{
 /// presentation_exchange_{from}_{to}_{state}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the data in
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 /// * `thid` - thread id
 /// * `presentation_exchange` - presentation exchange data
 /// * `state` - State
 pub fn save_presentation_exchange(
+    storage: &dyn DidCommStorage,
     from_did: &str,
     to_did: &str,
     thid: &str,
     presentation_exchange: &str,
     state: &State,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    write_db(
+    storage.put(
         &format!(
             "presentation_exchange_{}_{}_{}_{}",
             from_did, to_did, state, thid
@@ -34,6 +36,7 @@ pub fn save_presentation_exchange(
 /// presentation_exchange_{from}_{to}_{state}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to load the data from
 /// * `from_did` - from DID
 /// * `to_did` - to DID as string
 /// * `thid` - thread id
@@ -42,12 +45,13 @@ pub fn save_presentation_exchange(
 /// # Returns
 /// * `presentation_exchange` - presentation_exchange data stored in db.
 pub fn get_presentation_exchange(
+    storage: &dyn DidCommStorage,
     from_did: &str,
     to_did: &str,
     thid: &str,
     state: &State,
 ) -> Result<PresentationExchangeData, Box<dyn std::error::Error>> {
-    let presentation_exchange = read_db(&format!(
+    let presentation_exchange = storage.get(&format!(
         "presentation_exchange_{}_{}_{}_{}",
         from_did, to_did, state, thid
     ))?;
@@ -60,15 +64,17 @@ pub fn get_presentation_exchange(
 /// presentation_exchange_state_{user_type}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to save the data in
 /// * `state` - State
 /// * `thid` - thread id
 /// * `user_type` - UserType
 pub fn save_state(
+    storage: &dyn DidCommStorage,
     thid: &str,
     state: &State,
     user_type: &UserType,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    write_db(
+    storage.put(
         &format!("presentation_exchange_state_{}_{}", user_type, thid),
         &state.to_string(),
     )?;
@@ -80,16 +86,18 @@ pub fn save_state(
 /// presentation_exchange_state_{user_type}_{thid}.
 ///
 /// # Arguments
+/// * `storage` - storage to load the data from
 /// * `thid` - thread id
 /// * `user_type` - UserType
 ///
 /// # Returns
 /// * `state` - State stored in db.
 pub fn get_current_state(
+    storage: &dyn DidCommStorage,
     thid: &str,
     user_type: &UserType,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let result = read_db(&format!(
+    let result = storage.get(&format!(
         "presentation_exchange_state_{}_{}",
         user_type, thid
     ));
@@ -18,6 +18,7 @@ use crate::protocols::presentation_exchange::{
 };
 use crate::{
     datatypes::{BaseMessage, ExtendedMessage, MessageWithBody},
+    db::DidCommStorage,
     get_from_to_from_message,
     protocols::{
         presentation_exchange::datatypes::PresentationExchangeData,
@@ -26,7 +27,12 @@ use crate::{
 };
 
 /// Protocol handler for direction: `send`, type: `PRESENTATION_EXCHANGE_PROTOCOL_URI/request-presentation`
-pub fn send_request_presentation(_options: &str, message: &str) -> StepResult {
+pub fn send_request_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
     let base_message: BaseMessage = BaseMessage {
         body: HashMap::new(),
@@ -47,11 +53,17 @@ pub fn send_request_presentation(_options: &str, message: &str) -> StepResult {
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
-            let current_state: State = get_current_state(&thid, &UserType::Verifier)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Verifier)?.parse()?;
 
             match current_state {
                 State::ReceiveProposePresentation | State::Unknown => {
-                    save_state(&thid, &State::SendPresentationRequest, &UserType::Verifier)?
+                    save_state(
+                        storage,
+                        &thid,
+                        &State::SendPresentationRequest,
+                        &UserType::Verifier,
+                    )?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -68,13 +80,14 @@ pub fn send_request_presentation(_options: &str, message: &str) -> StepResult {
         PresentationExchangeType::RequestPresentation,
         &exchange_info.from,
         &exchange_info.to,
-        presentation_exchange_data,
+        presentation_exchange_data.clone(),
         &thid,
     )?;
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "state_storage")] {
             save_presentation_exchange(
+                storage,
                 &exchange_info.from,
                 &exchange_info.to,
                 &thid,
@@ -88,7 +101,12 @@ pub fn send_request_presentation(_options: &str, message: &str) -> StepResult {
 }
 
 /// Protocol handler for direction: `receive`, type: `PRESENTATION_EXCHANGE_PROTOCOL_URI/propose-presentation`
-pub fn receive_propose_presentation(_options: &str, message: &str) -> StepResult {
+pub fn receive_propose_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: MessageWithBody<PresentationExchangeData> = serde_json::from_str(message)?;
 
@@ -117,10 +135,12 @@ pub fn receive_propose_presentation(_options: &str, message: &str) -> StepResult
                 .presentation_exchange_data
                 .ok_or("Presentation exchange data not provided.")?;
 
-            let current_state: State = get_current_state(&thid, &UserType::Verifier)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Verifier)?.parse()?;
 
             match current_state {
                 State::Unknown | State::SendPresentationRequest => save_state(
+                    storage,
                     &thid,
                     &State::ReceiveProposePresentation,
                     &UserType::Verifier,
@@ -135,6 +155,7 @@ pub fn receive_propose_presentation(_options: &str, message: &str) -> StepResult
             };
 
             save_presentation_exchange(
+                storage,
                 &base_info.to,
                 &base_info.from,
                 &thid,
@@ -148,7 +169,12 @@ pub fn receive_propose_presentation(_options: &str, message: &str) -> StepResult
 }
 
 /// Protocol handler for direction: `receive`, type: `PRESENTATION_EXCHANGE_PROTOCOL_URI/presentation`
-pub fn receive_presentation(_options: &str, message: &str) -> StepResult {
+pub fn receive_presentation(
+    #[allow(unused_variables)] // may not be used, depending on feature setup
+    storage: &dyn DidCommStorage,
+    _options: &str,
+    message: &str,
+) -> StepResult {
     #[allow(unused_variables)] // may not be used afterwards but call is needed to validate input
     let parsed_message: MessageWithBody<PresentationExchangeData> = serde_json::from_str(message)?;
 
@@ -178,6 +204,7 @@ pub fn receive_presentation(_options: &str, message: &str) -> StepResult {
                 .ok_or("Presentation exchange data not provided.")?;
 
             let req_data_saved = get_presentation_exchange(
+                storage,
                 &base_info.to,
                 &base_info.from,
                 &thid,
@@ -194,11 +221,12 @@ pub fn receive_presentation(_options: &str, message: &str) -> StepResult {
                 Err(err) => return Err(err),
             }
 
-            let current_state: State = get_current_state(&thid, &UserType::Verifier)?.parse()?;
+            let current_state: State =
+                get_current_state(storage, &thid, &UserType::Verifier)?.parse()?;
 
             match current_state {
                 State::SendPresentationRequest => {
-                    save_state(&thid, &State::ReceivePresentation, &UserType::Verifier)?
+                    save_state(storage, &thid, &State::ReceivePresentation, &UserType::Verifier)?
                 }
                 _ => {
                     return Err(Box::from(format!(
@@ -210,6 +238,7 @@ pub fn receive_presentation(_options: &str, message: &str) -> StepResult {
             };
 
             save_presentation_exchange(
+                storage,
                 &base_info.to,
                 &base_info.from,
                 &thid,
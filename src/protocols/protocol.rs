@@ -1,4 +1,4 @@
-use crate::datatypes::MessageDirection;
+use crate::{datatypes::MessageDirection, db::DidCommStorage};
 
 /// Each protocol are constructed by a name and multiple steps. The protocol handler will iterate over
 /// all registered protocols and checks, if the name exists in the DIDComm message type. Afterwards
@@ -9,20 +9,32 @@ use crate::datatypes::MessageDirection;
 ///     - step[0].name  -> request
 ///     - message 1 -> type = trust_ping/ping -> protocol step will not be executed
 ///     - message 2 -> type = https://didcomm.org/didexchange/1.0/request -> protocol step will be executed
+///
+/// The name, roles and step names of a protocol are disclosed to other agents with the Discover
+/// Features protocol.
 pub struct Protocol {
     pub name: String,
+    /// roles of the participants, e.g. `requester` and `responder`
+    pub roles: Vec<String>,
     pub steps: Vec<ProtocolStep>,
 }
 
 /// Each protocol step specifies the direction and the name, when the handler function will be executed.
 /// The step handler can take the incoming message, parse it and can return an adjusted message to be
-/// returned to the user.
+/// returned to the user. Protocol states and keys are persisted with the passed storage.
 pub struct ProtocolStep {
     pub direction: MessageDirection,
-    pub handler: fn(options: &str, message: &str) -> StepResult,
+    pub handler: StepHandler,
     pub name: String,
+    /// `true` if the `from` of the message has to be authenticated, so received messages must be
+    /// encrypted by a sender owning the key of its `from` DID and sent messages must be packed with
+    /// `authcrypt` or `signed`
+    pub authenticated: bool,
 }
 
+/// Handler function of a protocol step, gets the storage to persist states and keys in.
+pub type StepHandler = fn(storage: &dyn DidCommStorage, options: &str, message: &str) -> StepResult;
+
 /// Result of each protocol step. Includes the custom stringified metadata, the modified message and
 /// a bool flag, if the message should be encrypted (ignored for direction == receive).
 pub struct StepOutput {
@@ -38,18 +50,16 @@ pub type StepResult = Result<StepOutput, Box<dyn std::error::Error>>;
 /// # Arguments
 /// * `message` - message string (should match message.rs/ExtendedMessage)
 /// * `handler` - function that will be executed, when the protocol and the step name matches the
-///               message type
+///   message type
 ///
 /// # Returns
 /// * `ProtocolStep` - The new protocol step, that can be pushed to a protocol steps vec.
-pub fn generate_send_step(
-    name: &str,
-    handler: fn(options: &str, message: &str) -> StepResult,
-) -> ProtocolStep {
+pub fn generate_send_step(name: &str, handler: StepHandler) -> ProtocolStep {
     ProtocolStep {
         direction: MessageDirection::Send,
         name: String::from(name),
         handler,
+        authenticated: false,
     }
 }
 
@@ -58,18 +68,50 @@ pub fn generate_send_step(
 /// # Arguments
 /// * `message` - message string (should match message.rs/ExtendedMessage)
 /// * `handler` - function that will be executed, when the protocol and the step name matches the
-///               message type
+///   message type
 ///
 /// # Returns
 /// * `ProtocolStep` - The new protocol step, that can be pushed to a protocol steps vec.
-pub fn generate_receive_step(
-    name: &str,
-    handler: fn(options: &str, message: &str) -> StepResult,
-) -> ProtocolStep {
+pub fn generate_receive_step(name: &str, handler: StepHandler) -> ProtocolStep {
     ProtocolStep {
         direction: MessageDirection::Receive,
         name: String::from(name),
         handler,
+        authenticated: false,
+    }
+}
+
+/// Shorthand generator for a protocol step, with direction send, that is only handled for messages
+/// packed with `authcrypt` or `signed`.
+///
+/// # Arguments
+/// * `message` - message string (should match message.rs/ExtendedMessage)
+/// * `handler` - function that will be executed, when the protocol and the step name matches the
+///   message type
+///
+/// # Returns
+/// * `ProtocolStep` - The new protocol step, that can be pushed to a protocol steps vec.
+pub fn generate_authenticated_send_step(name: &str, handler: StepHandler) -> ProtocolStep {
+    ProtocolStep {
+        authenticated: true,
+        ..generate_send_step(name, handler)
+    }
+}
+
+/// Shorthand generator for a protocol step, with direction receive, that is only handled for
+/// messages of an authenticated sender.
+///
+/// # Arguments
+/// * `message` - message string (should match message.rs/ExtendedMessage)
+/// * `handler` - function that will be executed, when the protocol and the step name matches the
+///   message type
+///
+/// # Returns
+/// * `ProtocolStep` - The new protocol step, that can be pushed to a protocol steps vec.
+pub fn generate_authenticated_receive_step(name: &str, handler: StepHandler) -> ProtocolStep {
+    ProtocolStep {
+        authenticated: true,
+        ..generate_receive_step(name, handler)
     }
 }
 
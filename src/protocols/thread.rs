@@ -0,0 +1,65 @@
+//! Thread ids and thread states of protocols, that store their state with the default key layout.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{datatypes::MessageWithBody, db::DidCommStorage};
+
+/// Protocol state of a thread. Entry key will be {protocol}_state_{user_type}_{thid}, threads
+/// without stored state are in state `Unknown`.
+pub(crate) trait ThreadState: Display + FromStr<Err = String> + PartialEq {
+    /// prefix of the storage keys of the protocol, e.g. `coordinate_mediation`
+    const PROTOCOL: &'static str;
+}
+
+/// Gets the thread id of a message, the first message of a thread uses its own id.
+///
+/// # Arguments
+/// * `message` - parsed message
+///
+/// # Returns
+/// * `String` - thread id
+pub(crate) fn get_thid<T>(
+    message: &MessageWithBody<T>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(message
+        .thid
+        .as_ref()
+        .or(message.id.as_ref())
+        .ok_or("Thread id can't be empty")?
+        .to_owned())
+}
+
+/// Moves the protocol state of a thread to the next state, if the thread is in one of the allowed
+/// states.
+///
+/// # Arguments
+/// * `storage` - storage to save the data in
+/// * `thid` - thread id
+/// * `user_type` - role of the own DID in the thread
+/// * `allowed_states` - states, that the thread may be in
+/// * `next_state` - new state of the thread
+///
+/// # Returns
+/// * `S` - previous state of the thread
+pub(crate) fn transition_state<S: ThreadState>(
+    storage: &dyn DidCommStorage,
+    thid: &str,
+    user_type: &impl Display,
+    allowed_states: &[S],
+    next_state: &S,
+) -> Result<S, Box<dyn std::error::Error>> {
+    let key = format!("{}_state_{}_{}", S::PROTOCOL, user_type, thid);
+    let current_state: S = storage
+        .get(&key)
+        .unwrap_or_else(|_| "Unknown".to_string())
+        .parse()?;
+    if !allowed_states.contains(&current_state) {
+        return Err(Box::from(format!(
+            "Error while processing step: State from {} to {} not allowed",
+            current_state, next_state
+        )));
+    }
+    storage.put(&key, &next_state.to_string())?;
+
+    Ok(current_state)
+}
@@ -0,0 +1,158 @@
+//! Checks of received messages against the receive policy of a plugin instance.
+
+use std::time::Duration;
+
+use crate::datatypes::{ExtendedMessage, MessageRejection, RejectionReason};
+#[cfg(feature = "state_storage")]
+use crate::{db::DidCommStorage, utils::get_now};
+
+/// Clock skew allowed per default between sender and receiver.
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+/// Defines, which received messages are rejected by `didcomm_receive`. Messages with a passed
+/// `expires_time` are always rejected.
+#[derive(Clone, Debug)]
+pub struct ReceivePolicy {
+    /// time the `created_time` of a received message may be in the future
+    pub max_clock_skew: Duration,
+    /// reject messages, whose id has already been received from the same sender (requires
+    /// `state_storage`), enabled per default
+    pub reject_replays: bool,
+}
+impl Default for ReceivePolicy {
+    fn default() -> Self {
+        ReceivePolicy {
+            max_clock_skew: DEFAULT_MAX_CLOCK_SKEW,
+            reject_replays: true,
+        }
+    }
+}
+
+/// Ensures, that a received message is not expired and has not been created in the future.
+///
+/// # Arguments
+/// * `message` - received message
+/// * `policy` - receive policy of the plugin instance
+/// * `now` - current time in seconds since epoch
+pub fn check_timestamps(
+    message: &ExtendedMessage,
+    policy: &ReceivePolicy,
+    now: u64,
+) -> Result<(), MessageRejection> {
+    if let Some(expires_time) = message.expires_time.filter(|time| *time < now) {
+        return Err(MessageRejection {
+            reason: RejectionReason::Expired,
+            message_id: message.id.clone(),
+            description: format!(
+                "message expired at {}, current time is {}",
+                expires_time, now
+            ),
+        });
+    }
+    let latest_created_time = now.saturating_add(policy.max_clock_skew.as_secs());
+    if let Some(created_time) = message
+        .created_time
+        .filter(|time| *time > latest_created_time)
+    {
+        return Err(MessageRejection {
+            reason: RejectionReason::CreatedInFuture,
+            message_id: message.id.clone(),
+            description: format!(
+                "message has been created at {}, which is after the allowed {}",
+                created_time, latest_created_time,
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Gets the key of the entry, that marks a message id as received from a sender. The entries are
+/// not part of the data of a thread, so replays are still detected after the thread has been
+/// purged.
+#[cfg(feature = "state_storage")]
+fn get_received_message_key(sender: &str, id: &str) -> String {
+    format!("received_message_{sender}_{id}")
+}
+
+/// Ensures, that a message with the id of a received message has not been received from its
+/// sender before. Messages without id are not checked.
+///
+/// # Arguments
+/// * `storage` - storage with the ids of received messages
+/// * `message` - received message
+#[cfg(feature = "state_storage")]
+pub fn check_replay(
+    storage: &dyn DidCommStorage,
+    message: &ExtendedMessage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let id = match message.id.as_ref() {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let sender = message.from.as_deref().unwrap_or_default();
+    if storage.get(&get_received_message_key(sender, id)).is_ok() {
+        return Err(Box::new(MessageRejection {
+            reason: RejectionReason::Replayed,
+            message_id: Some(id.to_owned()),
+            description: format!("message {} has already been received from '{}'", id, sender),
+        }));
+    }
+
+    Ok(())
+}
+
+/// Marks the id of a received message as received from its sender. Messages without id are not
+/// marked, as they get a random id on receive.
+///
+/// # Arguments
+/// * `storage` - storage to save the id in
+/// * `message` - received message
+#[cfg(feature = "state_storage")]
+pub fn save_received_message_id(
+    storage: &dyn DidCommStorage,
+    message: &ExtendedMessage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(id) = message.id.as_ref() {
+        let sender = message.from.as_deref().unwrap_or_default();
+        let received_time = get_now()?.to_string();
+        storage.put(&get_received_message_key(sender, id), &received_time)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_check_timestamps() -> Result<(), Box<dyn std::error::Error>> {
+        let policy = ReceivePolicy::default();
+        let mut message: ExtendedMessage =
+            serde_json::from_str(r#"{"type":"test","id":"msg1","created_time":1000}"#)?;
+        check_timestamps(&message, &policy, 1000)?;
+
+        message.expires_time = Some(1100);
+        check_timestamps(&message, &policy, 1100)?;
+        let rejection = check_timestamps(&message, &policy, 1101)
+            .err()
+            .ok_or("not rejected")?;
+        assert_eq!(rejection.reason, RejectionReason::Expired);
+
+        message.expires_time = None;
+        message.created_time = Some(1000 + DEFAULT_MAX_CLOCK_SKEW.as_secs());
+        check_timestamps(&message, &policy, 1000)?;
+        message.created_time = Some(1001 + DEFAULT_MAX_CLOCK_SKEW.as_secs());
+        let rejection = check_timestamps(&message, &policy, 1000)
+            .err()
+            .ok_or("not rejected")?;
+        assert_eq!(rejection.reason, RejectionReason::CreatedInFuture);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&rejection.to_string())?["reason"],
+            "createdInFuture",
+        );
+
+        Ok(())
+    }
+}
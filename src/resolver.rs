@@ -0,0 +1,545 @@
+//! Resolution of the keys and DIDComm services of DIDs.
+//!
+//! `did:key` and `did:peer:2` DIDs encode their keys as multibase (base58btc) encoded multicodec
+//! public keys, so no network access is required to resolve them. DIDs of other methods are
+//! resolved with a `DidResolver`, e.g. a `Vade` instance with resolver plugins.
+
+use std::convert::TryInto;
+
+use async_trait::async_trait;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use data_encoding::{BASE64URL, BASE64URL_NOPAD};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use vade::Vade;
+
+/// Multicodec prefix of X25519 public keys.
+const X25519_CODEC: [u8; 2] = [0xec, 0x01];
+/// Multicodec prefix of Ed25519 public keys.
+const ED25519_CODEC: [u8; 2] = [0xed, 0x01];
+/// Service type of DIDComm v2 endpoints.
+const DIDCOMM_SERVICE_TYPE: &str = "DIDCommMessaging";
+
+/// Resolves DIDs to their DID documents.
+#[async_trait(?Send)]
+pub trait DidResolver {
+    /// Resolves a DID.
+    ///
+    /// # Arguments
+    /// * `did` - DID to resolve
+    ///
+    /// # Returns
+    /// * `Option<String>` - stringified DID document, `None` if the DID could not be resolved
+    async fn resolve_did_document(
+        &mut self,
+        did: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>>;
+}
+
+/// Resolves DIDs with the `did_resolve` function of the registered plugins. The first document
+/// returned by a plugin is used.
+#[async_trait(?Send)]
+impl DidResolver for Vade {
+    async fn resolve_did_document(
+        &mut self,
+        did: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self.did_resolve(did).await?.into_iter().flatten().next())
+    }
+}
+
+/// DIDComm messaging service of a resolved DID.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedService {
+    /// id of the service, may be empty for services of `did:peer` DIDs
+    pub id: String,
+    /// URI to send messages to
+    pub uri: String,
+    /// keys of the mediators, that messages have to be forwarded by
+    #[serde(default)]
+    pub routing_keys: Vec<String>,
+    /// media types accepted by the service
+    #[serde(default)]
+    pub accept: Vec<String>,
+}
+
+/// Public keys and DIDComm services of a DID.
+#[derive(Debug, Default, PartialEq)]
+pub struct ResolvedDid {
+    /// X25519 keys for key agreement
+    pub key_agreement_keys: Vec<[u8; 32]>,
+    /// Ed25519 keys for authentication, used for key agreement if no X25519 keys are available
+    pub verification_keys: Vec<[u8; 32]>,
+    /// DIDComm messaging services
+    pub services: Vec<ResolvedService>,
+}
+
+impl ResolvedDid {
+    /// Gets the X25519 key agreement key of the DID. If the DID does not contain a X25519 key, it
+    /// is derived from its Ed25519 key.
+    ///
+    /// # Returns
+    /// * `[u8; 32]` - X25519 public key
+    pub fn key_agreement_key(&self) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+        match (
+            self.key_agreement_keys.first(),
+            self.verification_keys.first(),
+        ) {
+            (Some(key_agreement_key), _) => Ok(*key_agreement_key),
+            (None, Some(verification_key)) => ed25519_to_x25519(verification_key),
+            (None, None) => Err(Box::from("DID does not contain any key agreement keys")),
+        }
+    }
+}
+
+/// Decodes a multibase encoded multicodec public key.
+fn decode_multibase_key(key: &str) -> Result<([u8; 2], [u8; 32]), Box<dyn std::error::Error>> {
+    let encoded = key
+        .strip_prefix('z')
+        .ok_or_else(|| format!("unsupported multibase encoding of key '{}'", key))?;
+    let decoded = bs58::decode(encoded).into_vec()?;
+    if decoded.len() != 34 {
+        return Err(Box::from(format!(
+            "unsupported key '{}', expected a 32 byte key with multicodec prefix",
+            key
+        )));
+    }
+    let mut codec = [0u8; 2];
+    codec.copy_from_slice(&decoded[..2]);
+    let mut public = [0u8; 32];
+    public.copy_from_slice(&decoded[2..]);
+
+    Ok((codec, public))
+}
+
+/// Adds a multibase encoded key to the keys of a DID.
+fn add_key(resolved: &mut ResolvedDid, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match decode_multibase_key(key)? {
+        (X25519_CODEC, public) => resolved.key_agreement_keys.push(public),
+        (ED25519_CODEC, public) => resolved.verification_keys.push(public),
+        _ => {
+            return Err(Box::from(format!(
+                "unsupported key type of key '{}', only X25519 and Ed25519 keys are supported",
+                key
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the DID of a DID URL by removing its fragment and query parameters.
+///
+/// # Arguments
+/// * `did_url` - DID URL, e.g. the `kid` of a key
+///
+/// # Returns
+/// * `&str` - DID
+pub fn get_did(did_url: &str) -> &str {
+    did_url.split(['#', '?']).next().unwrap_or(did_url)
+}
+
+/// Gets the values of a property, that can either be a single value or an array of values.
+fn as_values(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(values) => values.iter().collect(),
+        Value::Null => Vec::new(),
+        value => vec![value],
+    }
+}
+
+/// Gets the strings of a property, that can either be a single string or an array of strings.
+fn as_strings(value: &Value) -> Vec<String> {
+    as_values(value)
+        .into_iter()
+        .filter_map(|value| value.as_str().map(String::from))
+        .collect()
+}
+
+/// Parses a DIDComm messaging service. Services with abbreviated property names, like the ones
+/// encoded in `did:peer:2` DIDs, are supported as well.
+///
+/// # Arguments
+/// * `service` - service entry of a DID document
+///
+/// # Returns
+/// * `Vec<ResolvedService>` - one service per endpoint, empty if it is no DIDComm service
+fn parse_services(service: &Value) -> Vec<ResolvedService> {
+    let is_didcomm_service = as_strings(&service["type"])
+        .iter()
+        .chain(as_strings(&service["t"]).iter())
+        .any(|service_type| service_type == DIDCOMM_SERVICE_TYPE || service_type == "dm");
+    if !is_didcomm_service {
+        return Vec::new();
+    }
+    let id = service["id"].as_str().unwrap_or_default().to_string();
+    let endpoint = match &service["serviceEndpoint"] {
+        Value::Null => &service["s"],
+        endpoint => endpoint,
+    };
+
+    as_values(endpoint)
+        .into_iter()
+        .filter_map(|endpoint| {
+            // endpoints are either plain URIs or objects with URI, routing keys and media types,
+            // older documents list routing keys and media types next to the endpoint
+            let (uri, properties) = match endpoint {
+                Value::String(uri) => (uri.to_string(), service),
+                endpoint => (endpoint["uri"].as_str()?.to_string(), endpoint),
+            };
+            let get_strings = |long: &str, short: &str| match &properties[long] {
+                Value::Null => as_strings(&properties[short]),
+                value => as_strings(value),
+            };
+
+            Some(ResolvedService {
+                id: id.clone(),
+                uri,
+                routing_keys: get_strings("routingKeys", "r"),
+                accept: get_strings("accept", "a"),
+            })
+        })
+        .collect()
+}
+
+/// Decodes a service element of a `did:peer:2` DID.
+fn decode_peer_service(encoded: &str) -> Result<Vec<ResolvedService>, Box<dyn std::error::Error>> {
+    let decoded = BASE64URL_NOPAD
+        .decode(encoded.trim_end_matches('=').as_bytes())
+        .or_else(|_| BASE64URL.decode(encoded.as_bytes()))?;
+    let services: Value = serde_json::from_slice(&decoded)?;
+
+    Ok(as_values(&services)
+        .into_iter()
+        .flat_map(parse_services)
+        .collect())
+}
+
+/// Resolves the public keys and services encoded in a `did:key` or `did:peer:2` DID without
+/// network access. Fragments and query parameters of DID URLs (e.g. `kid` values) are ignored.
+///
+/// # Arguments
+/// * `did` - DID or DID URL to resolve
+///
+/// # Returns
+/// * `ResolvedDid` - public keys and services of the DID
+pub fn resolve_did_locally(did: &str) -> Result<ResolvedDid, Box<dyn std::error::Error>> {
+    let did = get_did(did);
+    let mut resolved = ResolvedDid::default();
+    if let Some(key) = did.strip_prefix("did:key:") {
+        add_key(&mut resolved, key)?;
+    } else if let Some(elements) = did.strip_prefix("did:peer:2.") {
+        // elements are prefixed with their purpose
+        for element in elements.split('.') {
+            match element.split_at(element.len().min(1)) {
+                ("E", key) | ("V", key) => add_key(&mut resolved, key)?,
+                ("S", service) => resolved.services.extend(decode_peer_service(service)?),
+                _ => (),
+            }
+        }
+    } else {
+        return Err(Box::from(format!(
+            "could not resolve '{}', only did:key and did:peer:2 DIDs can be resolved without \
+             a resolver",
+            did
+        )));
+    }
+
+    Ok(resolved)
+}
+
+/// Decodes the public key of a verification method, that is either encoded as `publicKeyJwk`,
+/// `publicKeyMultibase` or `publicKeyBase58`. Keys of other types than X25519 and Ed25519 are
+/// skipped.
+fn add_verification_method_key(
+    resolved: &mut ResolvedDid,
+    method: &Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let method_type = method["type"].as_str().unwrap_or_default();
+    if let Some(x) = method["publicKeyJwk"]["x"].as_str() {
+        let decoded = BASE64URL_NOPAD.decode(x.trim_end_matches('=').as_bytes())?;
+        let public: [u8; 32] = decoded
+            .as_slice()
+            .try_into()
+            .map_err(|_| format!("invalid key length of '{}'", x))?;
+        match method["publicKeyJwk"]["crv"].as_str() {
+            Some("X25519") => resolved.key_agreement_keys.push(public),
+            Some("Ed25519") => resolved.verification_keys.push(public),
+            _ => (),
+        }
+    } else if let Some(key) = method["publicKeyMultibase"].as_str() {
+        match decode_multibase_key(key) {
+            Ok(_) => add_key(resolved, key)?,
+            // some methods encode the raw key without multicodec prefix
+            Err(_) => add_raw_key(resolved, method_type, key.trim_start_matches('z'))?,
+        }
+    } else if let Some(key) = method["publicKeyBase58"].as_str() {
+        add_raw_key(resolved, method_type, key)?;
+    }
+
+    Ok(())
+}
+
+/// Adds a base58 encoded key without multicodec prefix, its type is taken from the type of its
+/// verification method.
+fn add_raw_key(
+    resolved: &mut ResolvedDid,
+    method_type: &str,
+    key: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let decoded = bs58::decode(key).into_vec()?;
+    let public: [u8; 32] = decoded
+        .as_slice()
+        .try_into()
+        .map_err(|_| format!("invalid key length of '{}'", key))?;
+    if method_type.starts_with("X25519") {
+        resolved.key_agreement_keys.push(public);
+    } else if method_type.starts_with("Ed25519") {
+        resolved.verification_keys.push(public);
+    }
+
+    Ok(())
+}
+
+/// Parses the `keyAgreement` and `authentication` keys and `DIDCommMessaging` services of a DID
+/// document.
+///
+/// # Arguments
+/// * `document` - stringified DID document or DID resolution result containing a `didDocument`
+///
+/// # Returns
+/// * `ResolvedDid` - public keys and services of the DID
+pub fn parse_did_document(document: &str) -> Result<ResolvedDid, Box<dyn std::error::Error>> {
+    let parsed: Value = serde_json::from_str(document)?;
+    let document = match &parsed["didDocument"] {
+        Value::Null => &parsed,
+        did_document => did_document,
+    };
+    let verification_methods = as_values(&document["verificationMethod"]);
+
+    let mut resolved = ResolvedDid::default();
+    let keys = as_values(&document["keyAgreement"])
+        .into_iter()
+        .chain(as_values(&document["authentication"]));
+    for key in keys {
+        // keys are either embedded or reference a verification method
+        let method = match key {
+            Value::String(reference) => verification_methods.iter().copied().find(|method| {
+                method["id"].as_str().is_some_and(|id| {
+                    id == reference || (reference.starts_with('#') && id.ends_with(reference))
+                })
+            }),
+            method => Some(method),
+        };
+        if let Some(method) = method {
+            add_verification_method_key(&mut resolved, method)?;
+        }
+    }
+    resolved.services = as_values(&document["service"])
+        .into_iter()
+        .flat_map(parse_services)
+        .collect();
+
+    Ok(resolved)
+}
+
+/// Resolves the public keys and services of a DID. `did:key` and `did:peer:2` DIDs are resolved
+/// locally, DIDs of other methods with the given resolver.
+///
+/// # Arguments
+/// * `did` - DID or DID URL to resolve
+/// * `resolver` - resolver for DIDs, that can not be resolved locally
+///
+/// # Returns
+/// * `ResolvedDid` - public keys and services of the DID
+pub async fn resolve_did(
+    did: &str,
+    resolver: Option<&mut (dyn DidResolver + '_)>,
+) -> Result<ResolvedDid, Box<dyn std::error::Error>> {
+    match (resolve_did_locally(did), resolver) {
+        (Ok(resolved), _) => Ok(resolved),
+        (Err(_), Some(resolver)) => {
+            let did = get_did(did);
+            let document = resolver
+                .resolve_did_document(did)
+                .await?
+                .ok_or_else(|| format!("could not resolve '{}'", did))?;
+            parse_did_document(&document)
+        }
+        (Err(err), None) => Err(err),
+    }
+}
+
+/// Resolves the X25519 key agreement key of a DID. If the DID does not contain a X25519 key, it
+/// is derived from its Ed25519 key.
+///
+/// # Arguments
+/// * `did` - DID or DID URL to resolve
+/// * `resolver` - resolver for DIDs, that can not be resolved locally
+///
+/// # Returns
+/// * `[u8; 32]` - X25519 public key
+pub async fn resolve_key_agreement_key(
+    did: &str,
+    resolver: Option<&mut (dyn DidResolver + '_)>,
+) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+    resolve_did(did, resolver)
+        .await?
+        .key_agreement_key()
+        .map_err(|err| Box::from(format!("could not resolve key of '{}': {}", did, err)))
+}
+
+/// Checks if a signing public key is one of the Ed25519 verification keys of a DID.
+///
+/// # Arguments
+/// * `did` - DID or DID URL to resolve
+/// * `signing_public` - public key of the signer
+/// * `resolver` - resolver for DIDs, that can not be resolved locally
+///
+/// # Returns
+/// * `bool` - `true` if the DID could be resolved and contains the key
+pub async fn is_verification_key_of(
+    did: &str,
+    signing_public: &[u8],
+    resolver: Option<&mut (dyn DidResolver + '_)>,
+) -> bool {
+    resolve_did(did, resolver)
+        .await
+        .map(|resolved| {
+            resolved
+                .verification_keys
+                .iter()
+                .any(|key| key[..] == *signing_public)
+        })
+        .unwrap_or(false)
+}
+
+/// Converts an Ed25519 public key to the X25519 public key of the same key pair.
+fn ed25519_to_x25519(public: &[u8; 32]) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+    Ok(CompressedEdwardsY(*public)
+        .decompress()
+        .ok_or("invalid Ed25519 public key")?
+        .to_montgomery()
+        .to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use x25519_dalek::{PublicKey, StaticSecret};
+
+    use super::*;
+
+    #[test]
+    fn can_resolve_key_agreement_keys() -> Result<(), Box<dyn std::error::Error>> {
+        let secret = StaticSecret::from([1u8; 32]);
+        let public = PublicKey::from(&secret).to_bytes();
+        let encoded = bs58::encode([&X25519_CODEC[..], &public].concat()).into_string();
+        assert_eq!(
+            resolve_did_locally(&format!("did:key:z{}", encoded))?.key_agreement_key()?,
+            public
+        );
+        assert_eq!(
+            resolve_did_locally(&format!("did:key:z{}#z{}", encoded, encoded))?
+                .key_agreement_key()?,
+            public
+        );
+
+        // ed25519 keys are converted to the x25519 key of the same key pair
+        let signing_secret = ed25519_dalek::SecretKey::from_bytes(&[2u8; 32])?;
+        let signing_public = ed25519_dalek::PublicKey::from(&signing_secret);
+        let expanded = ed25519_dalek::ExpandedSecretKey::from(&signing_secret).to_bytes();
+        let mut derived_secret = [0u8; 32];
+        derived_secret.copy_from_slice(&expanded[..32]);
+        let signing_encoded =
+            bs58::encode([&ED25519_CODEC[..], signing_public.as_bytes()].concat()).into_string();
+        assert_eq!(
+            resolve_did_locally(&format!("did:key:z{}", signing_encoded))?.key_agreement_key()?,
+            PublicKey::from(&StaticSecret::from(derived_secret)).to_bytes(),
+        );
+
+        let service = BASE64URL_NOPAD.encode(
+            br#"{"t":"dm","s":{"uri":"https://example.com","r":["did:example:mediator"]}}"#,
+        );
+        let peer_did = format!(
+            "did:peer:2.Vz{}.Ez{}.S{}",
+            signing_encoded, encoded, service
+        );
+        assert_eq!(
+            resolve_did_locally(&peer_did)?,
+            ResolvedDid {
+                key_agreement_keys: vec![public],
+                verification_keys: vec![signing_public.to_bytes()],
+                services: vec![ResolvedService {
+                    uri: "https://example.com".to_string(),
+                    routing_keys: vec!["did:example:mediator".to_string()],
+                    ..Default::default()
+                }],
+            }
+        );
+        assert!(resolve_did_locally("did:evan:0x1234").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_parse_did_documents() -> Result<(), Box<dyn std::error::Error>> {
+        let public = PublicKey::from(&StaticSecret::from([1u8; 32])).to_bytes();
+        let document = format!(
+            r##"{{
+                "didDocument": {{
+                    "id": "did:example:123",
+                    "verificationMethod": [
+                        {{
+                            "id": "did:example:123#key-1",
+                            "type": "JsonWebKey2020",
+                            "controller": "did:example:123",
+                            "publicKeyJwk": {{ "kty": "OKP", "crv": "X25519", "x": "{}" }}
+                        }},
+                        {{
+                            "id": "did:example:123#key-2",
+                            "type": "X25519KeyAgreementKey2019",
+                            "controller": "did:example:123",
+                            "publicKeyBase58": "{}"
+                        }}
+                    ],
+                    "keyAgreement": [ "did:example:123#key-1", "#key-2" ],
+                    "service": [
+                        {{
+                            "id": "did:example:123#didcomm",
+                            "type": "DIDCommMessaging",
+                            "serviceEndpoint": {{
+                                "uri": "https://example.com/didcomm",
+                                "accept": [ "didcomm/v2" ],
+                                "routingKeys": [ "did:example:mediator#key-1" ]
+                            }}
+                        }},
+                        {{
+                            "id": "did:example:123#linked-domain",
+                            "type": "LinkedDomains",
+                            "serviceEndpoint": "https://example.com"
+                        }}
+                    ]
+                }}
+            }}"##,
+            BASE64URL_NOPAD.encode(&public),
+            bs58::encode(&public).into_string(),
+        );
+
+        assert_eq!(
+            parse_did_document(&document)?,
+            ResolvedDid {
+                key_agreement_keys: vec![public, public],
+                verification_keys: Vec::new(),
+                services: vec![ResolvedService {
+                    id: "did:example:123#didcomm".to_string(),
+                    uri: "https://example.com/didcomm".to_string(),
+                    routing_keys: vec!["did:example:mediator#key-1".to_string()],
+                    accept: vec!["didcomm/v2".to_string()],
+                }],
+            }
+        );
+
+        Ok(())
+    }
+}
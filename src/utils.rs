@@ -6,7 +6,7 @@ use uuid::Uuid;
 
 use crate::datatypes::{BaseMessage, ExtendedMessage, FromTo};
 #[cfg(feature = "state_storage")]
-use crate::db::{read_db, search_db_keys, write_db};
+use crate::db::{search_db_keys, DidCommStorage};
 
 /// Formats an vector into an array dynamically.
 ///
@@ -46,15 +46,19 @@ pub fn get_from_to_from_message(
     })
 }
 
-/// Write a didcomm_send/didcomm_receive raw message to rocks_db
+/// Write a didcomm_send/didcomm_receive raw message to the storage
 ///
 /// # Arguments
+/// * `storage` - storage to write the message to
 /// * `message` - Raw message
 #[cfg(feature = "state_storage")]
-pub fn write_raw_message_to_db(message: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn write_raw_message_to_db(
+    storage: &dyn DidCommStorage,
+    message: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
     let parsed_raw_message: ExtendedMessage = serde_json::from_str(message)?;
 
-    write_db(
+    storage.put(
         &format!(
             "message_{}_{}",
             parsed_raw_message
@@ -66,12 +70,14 @@ pub fn write_raw_message_to_db(message: &str) -> Result<(), Box<dyn std::error::
     )
 }
 
-/// Read a didcomm_send/didcomm_receive raw message from rocks_db
+/// Read a didcomm_send/didcomm_receive raw message from the storage
 ///
 /// # Arguments
+/// * `storage` - storage to read the message from
 /// * `message` - Raw message
 #[cfg(feature = "state_storage")]
 pub fn read_raw_message_from_db(
+    storage: &dyn DidCommStorage,
     prefix: &str,
     thid: &str,
     msg_id: &str,
@@ -80,9 +86,9 @@ pub fn read_raw_message_from_db(
 
     if msg_id == "*" {
         key = format!("{prefix}_{thid}");
-        search_db_keys(&key)
+        search_db_keys(storage, &key)
     } else {
-        let value = read_db(&key)?;
+        let value = storage.get(&key)?;
         Ok(vec![value])
     }
 }
@@ -108,6 +114,27 @@ pub fn fill_message_id_and_timestamps(message: &str) -> Result<String, Box<dyn s
     Ok(serde_json::to_string(&parsed_message)?)
 }
 
+/// Adds an entry to the stringified metadata of a protocol step.
+///
+/// # Arguments
+/// * `metadata` - stringified json object
+/// * `key` - key of the new entry
+/// * `value` - value of the new entry
+///
+/// # Returns
+/// * `string` - stringified metadata including the new entry
+pub fn add_to_metadata(
+    metadata: &str,
+    key: &str,
+    value: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut parsed_metadata: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(metadata)?;
+    parsed_metadata.insert(key.to_string(), serde_json::Value::from(value));
+
+    Ok(serde_json::to_string(&parsed_metadata)?)
+}
+
 pub(crate) mod hex_option {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
@@ -130,13 +157,69 @@ pub(crate) mod hex_option {
     }
 }
 
+pub(crate) mod hex_option_bytes {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        let hex_string = v.as_ref().map(hex::encode);
+        <Option<String>>::serialize(&hex_string, s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        let hex_string = <Option<String>>::deserialize(d)?;
+        hex_string
+            .map(|v| hex::decode(v).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+pub(crate) mod hex_vec {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &[[u8; 32]], s: S) -> Result<S::Ok, S::Error> {
+        let hex_strings: Vec<String> = v.iter().map(hex::encode).collect();
+        <Vec<String>>::serialize(&hex_strings, s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<[u8; 32]>, D::Error> {
+        let hex_strings = <Vec<String>>::deserialize(d)?;
+        hex_strings
+            .iter()
+            .map(|v| {
+                let hex_decoded = hex::decode(v).map_err(serde::de::Error::custom)?;
+                if hex_decoded.len() != 32 {
+                    return Err(serde::de::Error::custom("expected 32 byte keys"));
+                }
+                let mut arr: [u8; 32] = Default::default();
+                arr.copy_from_slice(&hex_decoded);
+                Ok(arr)
+            })
+            .collect()
+    }
+}
+
+/// Serializes string lists as stringified JSON, so they can be returned in string metadata.
+pub(crate) mod json_string_vec {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &[String], s: S) -> Result<S::Ok, S::Error> {
+        let json_string = serde_json::to_string(v).map_err(serde::ser::Error::custom)?;
+        String::serialize(&json_string, s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+        let json_string = String::deserialize(d)?;
+        serde_json::from_str(&json_string).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
-fn get_now() -> Result<u64, Box<dyn std::error::Error>> {
+pub(crate) fn get_now() -> Result<u64, Box<dyn std::error::Error>> {
     Ok(js_sys::Date::new_0().get_time() as u64 / 1000)
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn get_now() -> Result<u64, Box<dyn std::error::Error>> {
+pub(crate) fn get_now() -> Result<u64, Box<dyn std::error::Error>> {
     let start = SystemTime::now();
     let since_the_epoch = start.duration_since(UNIX_EPOCH)?;
 
@@ -1,59 +1,324 @@
+use std::rc::Rc;
+
 use async_trait::async_trait;
 use didcomm_rs::Jwe;
-use ed25519_dalek::{Keypair, PublicKey, SecretKey};
-use rand_core::OsRng;
 use vade::{VadePlugin, VadePluginResultValue};
+#[cfg(feature = "state_storage")]
 use x25519_dalek::StaticSecret;
 
 #[cfg(feature = "state_storage")]
 use crate::{
-    datatypes::{BaseMessage, ExtendedMessage},
+    datatypes::BaseMessage,
     get_from_to_from_message,
-    keypair::{get_com_keypair, get_key_agreement_key},
+    keypair::{ensure_key_owner, get_com_keypair, get_key_agreement_key, rotate_comm_keypairs},
+    protocols::out_of_band::{
+        datatypes::{InvitationRecord, UserType as InvitationUserType},
+        invitation::save_invitation,
+    },
+    receive_policy::{check_replay, save_received_message_id},
+    resolver::get_did,
     utils::{read_raw_message_from_db, write_raw_message_to_db},
-    vec_to_array,
 };
 use crate::{
     datatypes::{
+        CreateInvitationPayload,
+        CreateKeysPayload,
         DidCommOptions,
-        EncryptionKeyPair,
+        EncryptionAlgorithm,
         EncryptionKeys,
+        ExportWalletPayload,
+        ExtendedMessage,
+        ImportWalletPayload,
+        InvitationOutput,
+        KeyType,
         MessageDirection,
+        PackingMode,
         ProtocolHandleOutput,
+        ReceiveInvitationPayload,
+        RotateMasterKeyPayload,
+        SigningKeys,
+    },
+    db::{
+        apply_retention_policy,
+        create_tenant,
+        delete_tenant,
+        export_wallet,
+        get_default_storage,
+        import_wallet,
+        list_tenants,
+        purge_thread,
+        DidCommStorage,
+        EncryptedStorage,
+        MasterKey,
+        RetentionPolicy,
+        StorageConfig,
+        TenantStorage,
     },
     fill_message_id_and_timestamps,
-    message::{decrypt_message, encrypt_message},
+    from_prior::{create_from_prior, verify_from_prior},
+    keypair::create_key_pair,
+    message::{
+        decrypt_message,
+        decrypt_message_anonymously,
+        encrypt_message,
+        encrypt_message_anonymously,
+        get_encryption_algorithm,
+        get_packing_mode,
+        get_signed_message_kid,
+        sign_message,
+        verify_message,
+        SigningKeyPair,
+    },
     protocol_handler::ProtocolHandler,
+    protocols::{
+        forward::wrap_in_forward_message,
+        out_of_band::invitation::{create_invitation, get_invitation_url, parse_invitation_url},
+    },
+    receive_policy::{check_timestamps, ReceivePolicy},
+    resolver::{
+        is_verification_key_of,
+        resolve_did,
+        resolve_key_agreement_key,
+        DidResolver,
+        ResolvedService,
+    },
+    utils::{add_to_metadata, get_now},
+    vec_to_array,
 };
 
 big_array! { BigArray; }
 
-pub struct VadeDidComm {}
+/// Configuration for `VadeDidComm` instances.
+#[derive(Default)]
+pub struct VadeDidCommConfig {
+    /// custom storage for keys, states and messages, defaults to the feature selected one
+    pub storage: Option<Box<dyn DidCommStorage>>,
+    /// location of the feature selected storage, see `StorageConfig` (ignored for custom storages)
+    pub storage_path: Option<String>,
+    /// key namespace of the feature selected storage (ignored for custom storages)
+    pub namespace: Option<String>,
+    /// key to encrypt stored values with, values are stored unencrypted if not set
+    pub master_key: Option<MasterKey>,
+    /// keys to decrypt values with, that have been written before a master key rotation
+    pub previous_master_keys: Vec<MasterKey>,
+    /// policy applied by `apply_retention_policy`, data of finished threads is kept if not set
+    pub retention_policy: Option<RetentionPolicy>,
+    /// policy applied to received messages, `ReceivePolicy::default()` is used if not set
+    pub receive_policy: Option<ReceivePolicy>,
+    /// resolver for keys and services of DIDs, that are not `did:key` or `did:peer:2` DIDs
+    pub resolver: Option<Box<dyn DidResolver>>,
+    /// ids of protocols, that are not disclosed with the Discover Features protocol, ids may end
+    /// with `*` to hide all protocols starting with the given prefix
+    pub hidden_protocols: Vec<String>,
+}
+
+pub struct VadeDidComm {
+    storage: Box<dyn DidCommStorage>,
+    encrypted_storage: Option<Rc<EncryptedStorage>>,
+    retention_policy: Option<RetentionPolicy>,
+    receive_policy: ReceivePolicy,
+    resolver: Option<Box<dyn DidResolver>>,
+    hidden_protocols: Vec<String>,
+}
 impl VadeDidComm {
     /// Creates new instance of `VadeDidComm`.
-    pub fn new() -> Result<VadeDidComm, Box<dyn std::error::Error>> {
+    ///
+    /// # Arguments
+    /// * `config` - storage and its location, defaults are used for fields that are not set
+    ///
+    /// # Returns
+    /// * `VadeDidComm` - new plugin instance
+    pub fn new(config: VadeDidCommConfig) -> Result<VadeDidComm, Box<dyn std::error::Error>> {
         match env_logger::try_init() {
             Ok(_) | Err(_) => (),
         };
-        let vade_didcomm = VadeDidComm {};
+        let storage = match config.storage {
+            Some(storage) => storage,
+            None => get_default_storage(&StorageConfig {
+                path: config.storage_path,
+                namespace: config.namespace,
+            })?,
+        };
+        // keep access to the encrypted storage, so its master key can be rotated
+        let (storage, encrypted_storage): (Box<dyn DidCommStorage>, _) = match config.master_key {
+            Some(master_key) => {
+                let encrypted_storage = Rc::new(EncryptedStorage::new(
+                    storage,
+                    master_key,
+                    config.previous_master_keys,
+                ));
+                (
+                    Box::new(Rc::clone(&encrypted_storage)),
+                    Some(encrypted_storage),
+                )
+            }
+            None => (storage, None),
+        };
+        let vade_didcomm = VadeDidComm {
+            storage,
+            encrypted_storage,
+            retention_policy: config.retention_policy,
+            receive_policy: config.receive_policy.unwrap_or_default(),
+            resolver: config.resolver,
+            hidden_protocols: config.hidden_protocols,
+        };
 
         Ok(vade_didcomm)
     }
 }
 
+/// Gets the tenant id from the options of a custom function, that may be left empty.
+///
+/// # Arguments
+/// * `options` - stringified `DidCommOptions` or an empty string
+///
+/// # Returns
+/// * `Option<String>` - tenant id, if set
+fn get_tenant_id_from_options(options: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if options.trim().is_empty() {
+        return Ok(None);
+    }
+
+    Ok(serde_json::from_str::<DidCommOptions>(options)?.tenant_id)
+}
+
+/// Creates the key pair to sign messages with from the signing keys passed in the options.
+///
+/// # Arguments
+/// * `signing_keys` - signing keys from the options
+/// * `packing` - packing mode, that requires the key pair
+///
+/// # Returns
+/// * `SigningKeyPair` - signing key pair
+fn get_signing_keypair(
+    signing_keys: Option<SigningKeys>,
+    packing: PackingMode,
+) -> Result<SigningKeyPair, Box<dyn std::error::Error>> {
+    let signing_keys = signing_keys
+        .ok_or_else(|| format!("signing_keys are required for packing '{}'", packing))?;
+
+    SigningKeyPair::new(
+        signing_keys.signing_key_type.unwrap_or(KeyType::Ed25519),
+        signing_keys
+            .signing_my_secret
+            .ok_or("No signing secret key provided")?,
+    )
+}
+
+/// Checks if a `jws` message is signed with a verification key of its `from` DID, so that the
+/// receiver can authenticate its sender.
+///
+/// # Arguments
+/// * `message` - stringified message with the sender in `from`
+/// * `signing_keys` - signing keys the message will be signed with
+/// * `resolver` - resolver for DIDs, that can not be resolved locally
+///
+/// # Returns
+/// * `bool` - `true` if the signing key belongs to the `from` DID
+async fn is_signed_by_sender(
+    message: &str,
+    signing_keys: Option<&SigningKeys>,
+    resolver: Option<&mut (dyn DidResolver + '_)>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
+    let (from, signing_keys) = match (parsed_message.from, signing_keys) {
+        (Some(from), Some(signing_keys)) => (from, signing_keys),
+        _ => return Ok(false),
+    };
+    let signing_my_secret = match signing_keys.signing_my_secret {
+        Some(signing_my_secret) => signing_my_secret,
+        None => return Ok(false),
+    };
+    let keypair = SigningKeyPair::new(
+        signing_keys.signing_key_type.unwrap_or(KeyType::Ed25519),
+        signing_my_secret,
+    )?;
+
+    Ok(is_verification_key_of(&from, &keypair.public, resolver).await)
+}
+
+/// Resolves the key agreement keys and DIDComm services of all receivers of a message from their
+/// DIDs. Receivers with DIDs, that cannot be resolved, get no key.
+///
+/// # Arguments
+/// * `message` - stringified message with the receivers in `to`
+/// * `resolver` - resolver for DIDs, that can not be resolved locally
+///
+/// # Returns
+/// * `Vec<Option<Vec<u8>>>` - keys in the order of the `to` DIDs
+/// * `Vec<ResolvedService>` - DIDComm services of all receivers
+async fn resolve_receivers(
+    message: &str,
+    mut resolver: Option<&mut (dyn DidResolver + '_)>,
+) -> Result<(Vec<Option<Vec<u8>>>, Vec<ResolvedService>), Box<dyn std::error::Error>> {
+    let parsed_message: ExtendedMessage = serde_json::from_str(message)?;
+    let mut keys = Vec::new();
+    let mut services = Vec::new();
+    for did in parsed_message.to.unwrap_or_default() {
+        match resolve_did(&did, resolver.as_deref_mut()).await {
+            Ok(resolved) => {
+                keys.push(resolved.key_agreement_key().ok().map(|key| key.to_vec()));
+                services.extend(resolved.services);
+            }
+            Err(err) => {
+                log::debug!("could not resolve {}: {}", did, err);
+                keys.push(None);
+            }
+        }
+    }
+
+    Ok((if keys.is_empty() { vec![None] } else { keys }, services))
+}
+
+/// Ensures, that the given encryption keys can be used for key agreement. DIDComm rs only supports
+/// X25519 for key agreement, so `secp256k1` and `P-256` keys can only be used for signing.
+///
+/// # Arguments
+/// * `encryption_keys` - encryption keys from the options
+fn check_key_agreement_key_type(
+    encryption_keys: &EncryptionKeys,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match encryption_keys.encryption_key_type {
+        None | Some(KeyType::X25519) => Ok(()),
+        Some(key_type) => Err(Box::from(format!(
+            "key type '{}' is not supported for key agreement, only X25519 keys can be used",
+            key_type
+        ))),
+    }
+}
+
 #[async_trait(?Send)]
 impl VadePlugin for VadeDidComm {
     /// Runs a custom function, currently supports
     ///
-    /// - `create_new_keys` to create a new key pair to be used for DIDCOMM communication.
+    /// - `create_new_keys` to create a new key pair to be used for DIDCOMM communication, the
+    ///   optional `CreateKeysPayload` selects the key type (`X25519` by default)
     /// - `query_didcomm_messages` to fetch stored didcomm messaged by thid(e.g: "message_{thid}_*") and complete messageid(e.g: "message_{thid}_{msgid}")
+    /// - `create_tenant` to register a new tenant with the id given as payload
+    /// - `list_tenants` to get all registered tenants
+    /// - `delete_tenant` to delete the tenant with the id given as payload and all of its data
+    /// - `purge_thread` to delete all messages, states and protocol data of the thread id given as
+    ///   payload
+    /// - `apply_retention_policy` to delete data of expired threads of all tenants, returns the
+    ///   ids of the purged threads
+    /// - `export_wallet` to export all stored data into a password encrypted archive
+    /// - `import_wallet` to import the data of an archive created with `export_wallet`
+    /// - `rotate_master_key` to re-encrypt all stored values with a new master key, returns the
+    ///   number of re-encrypted values
     ///
     /// # Arguments
     ///
     /// * `_method` - not required, can be left empty
     /// * `function` - currently supports `create_new_keys`
-    /// * `_options` - not required, can be left empty
-    /// * `_payload` - required only for query_didcomm_messages
+    /// * `options` - of type DidcommOptions, only `tenantId` is used by `query_didcomm_messages`,
+    ///               `purge_thread`, `export_wallet`, `import_wallet`, `create_invitation` and
+    ///               `receive_invitation`, can be left empty
+    /// * `_payload` - required only for query_didcomm_messages, create_tenant, delete_tenant,
+    ///                purge_thread, export_wallet (`ExportWalletPayload`), import_wallet
+    ///                (`ImportWalletPayload`), rotate_master_key (`RotateMasterKeyPayload`),
+    ///                create_invitation (`CreateInvitationPayload`) and
+    ///                receive_invitation (`ReceiveInvitationPayload`), optional for create_keys
+    ///                (`CreateKeysPayload`)
     ///
     /// # Returns
     /// * `Option<String>>` - created key pair
@@ -61,20 +326,19 @@ impl VadePlugin for VadeDidComm {
         &mut self,
         _method: &str,
         function: &str,
-        _options: &str,
+        options: &str,
         _payload: &str,
     ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
         match function {
             "create_keys" => {
-                let secret_key = StaticSecret::new(OsRng);
-                let pub_key = x25519_dalek::PublicKey::from(&secret_key);
-
-                let enc_key_pair = EncryptionKeyPair {
-                    secret: secret_key.to_bytes(),
-                    public: pub_key.to_bytes(),
+                let payload: CreateKeysPayload = if _payload.trim().is_empty() {
+                    CreateKeysPayload::default()
+                } else {
+                    serde_json::from_str(_payload)?
                 };
+                let key_pair = create_key_pair(payload.key_type.unwrap_or(KeyType::X25519))?;
                 Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
-                    &enc_key_pair,
+                    &key_pair,
                 )?)))
             }
             "query_didcomm_messages" => {
@@ -87,13 +351,143 @@ impl VadePlugin for VadeDidComm {
                         let thid = message_values.next().ok_or("Invalid message thid")?;
                         let message_id = message_values.next().ok_or("Invalid message id")?;
 
-                        let db_result = read_raw_message_from_db(prefix, thid, message_id)?;
+                        let tenant_id = get_tenant_id_from_options(options)?;
+                        let storage =
+                            TenantStorage::open(self.storage.as_ref(), tenant_id.as_deref())?;
+                        let db_result = read_raw_message_from_db(
+                            &storage,
+                            prefix,
+                            thid,
+                            message_id,
+                        )?;
                         let result = serde_json::to_string(&db_result)?;
 
                         Ok(VadePluginResultValue::Success(Some(result)))
                     }
                 }
             }
+            "create_tenant" => {
+                let tenant = create_tenant(self.storage.as_ref(), _payload)?;
+                Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+                    &tenant,
+                )?)))
+            }
+            "list_tenants" => {
+                let tenants = list_tenants(self.storage.as_ref())?;
+                Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+                    &tenants,
+                )?)))
+            }
+            "delete_tenant" => {
+                delete_tenant(self.storage.as_ref(), _payload)?;
+                Ok(VadePluginResultValue::Success(None))
+            }
+            "purge_thread" => {
+                let tenant_id = get_tenant_id_from_options(options)?;
+                let storage = TenantStorage::open(self.storage.as_ref(), tenant_id.as_deref())?;
+                purge_thread(&storage, _payload)?;
+                Ok(VadePluginResultValue::Success(None))
+            }
+            "apply_retention_policy" => {
+                let policy = self
+                    .retention_policy
+                    .as_ref()
+                    .ok_or("no retention policy configured")?;
+                let mut tenant_ids = vec![None];
+                tenant_ids.extend(
+                    list_tenants(self.storage.as_ref())?
+                        .into_iter()
+                        .map(|tenant| Some(tenant.tenant_id)),
+                );
+                let mut purged_threads = Vec::new();
+                for tenant_id in tenant_ids.iter() {
+                    let storage = TenantStorage::open(self.storage.as_ref(), tenant_id.as_deref())?;
+                    purged_threads.extend(apply_retention_policy(&storage, policy)?);
+                }
+                Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+                    &purged_threads,
+                )?)))
+            }
+            "export_wallet" => {
+                let payload: ExportWalletPayload = serde_json::from_str(_payload)?;
+                let tenant_id = get_tenant_id_from_options(options)?;
+                let storage = TenantStorage::open(self.storage.as_ref(), tenant_id.as_deref())?;
+                let archive = export_wallet(&storage, &payload.password)?;
+                Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+                    &archive,
+                )?)))
+            }
+            "import_wallet" => {
+                let payload: ImportWalletPayload = serde_json::from_str(_payload)?;
+                let tenant_id = get_tenant_id_from_options(options)?;
+                let storage = TenantStorage::open(self.storage.as_ref(), tenant_id.as_deref())?;
+                import_wallet(&storage, &payload.archive, &payload.password)?;
+                Ok(VadePluginResultValue::Success(None))
+            }
+            "rotate_master_key" => {
+                let payload: RotateMasterKeyPayload = serde_json::from_str(_payload)?;
+                let encrypted_storage = self
+                    .encrypted_storage
+                    .as_ref()
+                    .ok_or("storage is not encrypted, no master_key configured")?;
+                let master_key =
+                    MasterKey::new(&payload.key_id, vec_to_array(hex::decode(payload.key)?)?)?;
+                let rotated = encrypted_storage.rotate_master_key(master_key)?;
+                Ok(VadePluginResultValue::Success(Some(rotated.to_string())))
+            }
+            "create_invitation" => {
+                let payload: CreateInvitationPayload = serde_json::from_str(_payload)?;
+                let invitation = create_invitation(&payload)?;
+                let invitation_url = get_invitation_url(&payload.base_url, &invitation)?;
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "state_storage")] {
+                        let tenant_id = get_tenant_id_from_options(options)?;
+                        let storage =
+                            TenantStorage::open(self.storage.as_ref(), tenant_id.as_deref())?;
+                        save_invitation(
+                            &storage,
+                            &InvitationRecord {
+                                invitation: invitation.clone(),
+                                user_type: InvitationUserType::Inviter,
+                                single_use: payload.single_use,
+                                thids: Vec::new(),
+                            },
+                        )?;
+                    } else { }
+                }
+                Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+                    &InvitationOutput {
+                        invitation,
+                        invitation_url,
+                    },
+                )?)))
+            }
+            "receive_invitation" => {
+                let payload: ReceiveInvitationPayload = serde_json::from_str(_payload)?;
+                let invitation = parse_invitation_url(&payload.invitation_url)?;
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "state_storage")] {
+                        let tenant_id = get_tenant_id_from_options(options)?;
+                        let storage =
+                            TenantStorage::open(self.storage.as_ref(), tenant_id.as_deref())?;
+                        save_invitation(
+                            &storage,
+                            &InvitationRecord {
+                                invitation: invitation.clone(),
+                                user_type: InvitationUserType::Invitee,
+                                single_use: false,
+                                thids: Vec::new(),
+                            },
+                        )?;
+                    } else { }
+                }
+                Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+                    &InvitationOutput {
+                        invitation,
+                        invitation_url: payload.invitation_url,
+                    },
+                )?)))
+            }
             _ => Ok(VadePluginResultValue::Ignored),
         }
     }
@@ -104,6 +498,11 @@ impl VadePlugin for VadeDidComm {
     /// If no key was given and the message should be encrypted (depends on protocol implementation),
     /// the DIDComm keypair from a db will be used.
     ///
+    /// The message is packed as specified with the `packing` option, if the protocol step does not
+    /// require an unencrypted message. Encrypted messages to receivers behind mediators are wrapped
+    /// in a forward message for each routing key, that is passed as `routing_keys` or is known for
+    /// the receiver.
+    ///
     /// # Arguments
     /// * `options` - of type DidcommOptions, used to apply a custom signing_key
     /// * `message` - the plain didcomm message (should be of type datatypes.rs/BaseMessage)
@@ -119,13 +518,36 @@ impl VadePlugin for VadeDidComm {
         log::debug!("preparing DIDComm message for being sent");
 
         let options_parsed = serde_json::from_str::<DidCommOptions>(options)?;
+        let storage =
+            TenantStorage::open(self.storage.as_ref(), options_parsed.tenant_id.as_deref())?;
         let message_with_id = fill_message_id_and_timestamps(message)?;
+        // `authcrypt` and `signed` messages allow the receiver to authenticate the sender, `jws`
+        // messages only if they are signed with a key of the `from` DID
+        let sender_authenticated = match options_parsed.packing {
+            _ if matches!(options_parsed.skip_message_packaging, Some(true)) => false,
+            None | Some(PackingMode::Authcrypt) | Some(PackingMode::Signed) => true,
+            Some(PackingMode::Jws) => {
+                is_signed_by_sender(
+                    &message_with_id,
+                    options_parsed.signing_keys.as_ref(),
+                    self.resolver.as_deref_mut(),
+                )
+                .await?
+            }
+            _ => false,
+        };
 
         #[allow(unused_mut)] // may need to be mutable, depending on feature setup
         let mut protocol_result = match options_parsed.skip_protocol_handling {
             None | Some(false) => {
                 // run protocol specific logic
-                ProtocolHandler::before_send(options, &message_with_id)?
+                ProtocolHandler::before_send(
+                    &storage,
+                    options,
+                    &message_with_id,
+                    sender_authenticated,
+                    &self.hidden_protocols,
+                )?
             }
             _ => ProtocolHandleOutput {
                 direction: MessageDirection::Send,
@@ -143,85 +565,207 @@ impl VadePlugin for VadeDidComm {
         cfg_if::cfg_if! {
             if #[cfg(feature = "state_storage")] {
                 // store unencrypted raw message in db
-                write_raw_message_to_db(message_raw)?;
+                write_raw_message_to_db(&storage, message_raw)?;
             } else {}
         }
 
+        // rotate the DID of the sender with a JWT signed by its prior DID
+        if let Some(from_prior_options) = options_parsed.from_prior.as_ref() {
+            let mut parsed_message: ExtendedMessage =
+                serde_json::from_str(&protocol_result.message)?;
+            let from = parsed_message
+                .from
+                .as_deref()
+                .ok_or("from is required to rotate a DID with from_prior")?;
+            parsed_message.from_prior =
+                Some(create_from_prior(from_prior_options, from, get_now()?)?);
+            protocol_result.message = serde_json::to_string(&parsed_message)?;
+        }
+
+        let packing = match options_parsed.packing {
+            _ if matches!(options_parsed.skip_message_packaging, Some(true)) => {
+                PackingMode::Plaintext
+            }
+            // signing does not require keys from a DID exchange, so it is possible in every step
+            Some(PackingMode::Jws) => PackingMode::Jws,
+            _ if !protocol_result.encrypt => PackingMode::Plaintext,
+            Some(packing) => packing,
+            None if options_parsed.signing_keys.is_some() => PackingMode::Signed,
+            None => PackingMode::Authcrypt,
+        };
+
+        // keys of the mediators, that the encrypted message has to be forwarded by
+        let mut routing_keys = options_parsed.routing_keys.clone().unwrap_or_default();
+        if !routing_keys.is_empty() && matches!(packing, PackingMode::Jws | PackingMode::Plaintext)
+        {
+            return Err(Box::from(format!(
+                "routing_keys can only be used with encrypted messages, not with packing '{}'",
+                packing
+            )));
+        }
+
         // message string, that will be returned
-        let final_message: String;
+        let mut final_message: String;
 
-        if protocol_result.encrypt && !matches!(options_parsed.skip_message_packaging, Some(true)) {
-            let encryption_keys: EncryptionKeys = if options_parsed.encryption_keys.is_some() {
-                options_parsed
-                    .encryption_keys
-                    .ok_or("encryption_keys is missing in options parameter")?
+        if packing == PackingMode::Jws {
+            final_message = sign_message(
+                &protocol_result.message,
+                get_signing_keypair(options_parsed.signing_keys, packing)?,
+            )?;
+        } else if packing != PackingMode::Plaintext {
+            let encryption_keys: Option<EncryptionKeys> = if options_parsed
+                .encryption_keys
+                .is_some()
+            {
+                options_parsed.encryption_keys
             } else {
                 cfg_if::cfg_if! {
                     if #[cfg(not(feature = "state_storage"))] {
-                        return Err(Box::from("encryption_keys must be provided if 'state_storage' is disabled".to_string()));
+                        None
                     } else {
                         // otherwise use keys from DID exchange
                         let parsed_message: BaseMessage = serde_json::from_str(message)?;
                         let from_to = get_from_to_from_message(&parsed_message)?;
-                        let mut encoded_keypair = get_key_agreement_key(&from_to.from);
-                        if encoded_keypair.is_err() {
-                            // when we dont find a  key agreement key, try to get the stored keypair
-                            encoded_keypair = get_com_keypair(&from_to.from, &from_to.to);
-                            if encoded_keypair.is_err() {
-                                return Err(Box::from("No keypair found"));
-                            }
-                            encoded_keypair = get_key_agreement_key(
-                                &encoded_keypair?.key_agreement_key,
-                            );
-                        }
-                        let keypair = encoded_keypair?;
-                        let secret_decoded = vec_to_array(hex::decode(keypair.secret_key)?)?;
-                        let public_decoded = vec_to_array(hex::decode(keypair.target_pub_key)?)?;
-
-                        // when we have a key agreement key, adjust the "to" field to the key agreement
-                        let mut parsed_message: ExtendedMessage =
-                            serde_json::from_str(&protocol_result.message)?;
-
-                        log::debug!(
-                            "adjusting from: {} to:{}",
-                            keypair.key_agreement_key,
-                            keypair.target_key_agreement_key
-                        );
-                        parsed_message.to = Some(vec![keypair.target_key_agreement_key]);
-                        parsed_message.from = Some(keypair.key_agreement_key);
-                        protocol_result.message = serde_json::to_string(&parsed_message)?;
+                        let encoded_keypair = get_key_agreement_key(&storage, &from_to.from)
+                            .or_else(|_| {
+                                // when we dont find a key agreement key, try to get the stored keypair
+                                get_com_keypair(&storage, &from_to.from, &from_to.to).and_then(
+                                    |keypair| {
+                                        get_key_agreement_key(&storage, &keypair.key_agreement_key)
+                                    },
+                                )
+                            });
+                        match encoded_keypair {
+                            Ok(keypair) => {
+                                let secret_decoded =
+                                    vec_to_array(hex::decode(keypair.secret_key)?)?;
+                                let public_decoded =
+                                    vec_to_array(hex::decode(keypair.target_pub_key)?)?;
 
-                        EncryptionKeys {
-                            encryption_my_secret: StaticSecret::from(secret_decoded).to_bytes(),
-                            encryption_others_public: Some(public_decoded),
+                                // when we have a key agreement key, adjust the "to" field to the key agreement
+                                let mut parsed_message: ExtendedMessage =
+                                    serde_json::from_str(&protocol_result.message)?;
+
+                                log::debug!(
+                                    "adjusting from: {} to:{}",
+                                    keypair.key_agreement_key,
+                                    keypair.target_key_agreement_key
+                                );
+                                parsed_message.to = Some(vec![keypair.target_key_agreement_key]);
+                                parsed_message.from = Some(keypair.key_agreement_key);
+                                protocol_result.message = serde_json::to_string(&parsed_message)?;
+                                if routing_keys.is_empty() {
+                                    routing_keys = keypair.target_routing_keys;
+                                }
+
+                                Some(EncryptionKeys {
+                                    encryption_my_secret: StaticSecret::from(secret_decoded)
+                                        .to_bytes(),
+                                    encryption_others_public: Some(public_decoded),
+                                    encryption_others_public_keys: Vec::new(),
+                                    encryption_key_type: None,
+                                })
+                            }
+                            // without DID exchange, keys of the receivers are resolved from their DIDs
+                            Err(_) => None,
                         }
                     }
                 }
             };
 
-            let signing_keypair;
-            if let Some(signing_keys_input) = options_parsed.signing_keys {
-                let secret_key = SecretKey::from_bytes(
-                    &signing_keys_input
-                        .signing_my_secret
-                        .ok_or("No signing secret key provided")?,
-                )?;
-                signing_keypair = Some(Keypair {
-                    public: PublicKey::from(&secret_key),
-                    secret: secret_key,
-                });
-            } else {
-                signing_keypair = None;
+            if let Some(encryption_keys) = encryption_keys.as_ref() {
+                check_key_agreement_key_type(encryption_keys)?;
+            }
+            let signing_keypair = match packing {
+                PackingMode::Signed => {
+                    Some(get_signing_keypair(options_parsed.signing_keys, packing)?)
+                }
+                _ => None,
+            };
+            let mut encryption_others_publics: Vec<Option<Vec<u8>>> = match encryption_keys.as_ref()
+            {
+                Some(keys) if !keys.encryption_others_public_keys.is_empty() => keys
+                    .encryption_others_public_keys
+                    .iter()
+                    .map(|v| Some(v.to_vec()))
+                    .collect(),
+                Some(keys) => vec![keys.encryption_others_public.as_ref().map(|v| v.to_vec())],
+                None => vec![None],
+            };
+            if encryption_others_publics.iter().all(Option::is_none) {
+                let (resolved_keys, services) =
+                    resolve_receivers(&protocol_result.message, self.resolver.as_deref_mut())
+                        .await?;
+                encryption_others_publics = resolved_keys;
+                if routing_keys.is_empty() {
+                    routing_keys = services
+                        .iter()
+                        .find(|service| !service.routing_keys.is_empty())
+                        .map(|service| service.routing_keys.clone())
+                        .unwrap_or_default();
+                }
+                if !services.is_empty() {
+                    protocol_result.metadata = add_to_metadata(
+                        &protocol_result.metadata,
+                        "services",
+                        &serde_json::to_string(&services)?,
+                    )?;
+                }
+            }
+            let encryption_algorithm = options_parsed
+                .encryption_algorithm
+                .unwrap_or(EncryptionAlgorithm::Xc20p);
+            final_message = match packing {
+                PackingMode::Anoncrypt => encrypt_message_anonymously(
+                    &protocol_result.message,
+                    encryption_others_publics
+                        .into_iter()
+                        .collect::<Option<Vec<Vec<u8>>>>()
+                        .ok_or(
+                            "public keys of the receivers are required for packing 'anoncrypt'",
+                        )?,
+                    encryption_algorithm,
+                )?,
+                _ => encrypt_message(
+                    &protocol_result.message,
+                    &encryption_keys
+                        .ok_or(
+                            "No keypair found, pass encryption_keys or use packing 'anoncrypt' to \
+                             send messages without DID exchange",
+                        )?
+                        .encryption_my_secret,
+                    encryption_others_publics,
+                    signing_keypair,
+                    encryption_algorithm,
+                )?,
+            };
+
+            // wrap the message in a forward message for each mediator, starting with the last one
+            if !routing_keys.is_empty() {
+                let receivers = serde_json::from_str::<ExtendedMessage>(&protocol_result.message)?
+                    .to
+                    .unwrap_or_default();
+                let mut next = match receivers.as_slice() {
+                    [receiver] => receiver.to_owned(),
+                    _ => return Err(Box::from("routing requires exactly one receiver in `to`")),
+                };
+                for routing_key in routing_keys.iter().rev() {
+                    let routing_public_key =
+                        resolve_key_agreement_key(routing_key, self.resolver.as_deref_mut())
+                            .await
+                            .map_err(|err| {
+                                format!("could not resolve routing key {}: {}", routing_key, err)
+                            })?;
+                    final_message = wrap_in_forward_message(
+                        &final_message,
+                        &next,
+                        routing_key,
+                        routing_public_key.to_vec(),
+                        encryption_algorithm,
+                    )?;
+                    next = routing_key.to_owned();
+                }
             }
-            final_message = encrypt_message(
-                &protocol_result.message,
-                &encryption_keys.encryption_my_secret,
-                encryption_keys
-                    .encryption_others_public
-                    .as_ref()
-                    .map(|v| v.to_vec()),
-                signing_keypair,
-            )?;
         } else {
             final_message = protocol_result.message;
         }
@@ -241,6 +785,17 @@ impl VadePlugin for VadeDidComm {
     /// Receive a plain DIDComm json message, including decryption and protocol specific message parsing.
     /// The DIDComm options can include a shared secret to encrypt the message with a specific key.
     /// If no key was given and the message is encrypted the DIDComm keypair from a db will be used.
+    /// The packing mode of the received message is returned as `packing` in the metadata, the
+    /// hex encoded public key of the signer of a verified `jws` or `signed` message as `signer` and
+    /// the content encryption algorithm of an encrypted message as `encryptionAlgorithm`.
+    /// `signerAuthenticated` is `true`, if the signer has been verified with the passed
+    /// `signingOthersPublic` or its key is a verification key of the `from` DID, the key in the
+    /// `kid` of the message is only claimed by the sender otherwise. `senderAuthenticated`
+    /// is `true`, if the `from` DID of the message owns the key, that the message has been
+    /// encrypted with or that a `jws` message has been signed with, protocol steps requiring an
+    /// authenticated sender are rejected otherwise.
+    /// Expired messages, messages created in the future and replayed messages are rejected according
+    /// to the `ReceivePolicy` of the instance with a `MessageRejection` error.
     ///
     /// # Arguments
     /// * `options` - of type DidcommOptions, used to apply a custom signing_key
@@ -258,15 +813,49 @@ impl VadePlugin for VadeDidComm {
         log::debug!("handling incoming DIDComm message");
 
         let options_parsed = serde_json::from_str::<DidCommOptions>(options)?;
+        let storage =
+            TenantStorage::open(self.storage.as_ref(), options_parsed.tenant_id.as_deref())?;
         let parsed_message = serde_json::from_str::<Jwe>(message);
+        let packing = get_packing_mode(message);
+
+        // hex encoded public key of the signer of a verified `jws` or `signed` message
+        let mut signer = None;
+        // whether the signer has been verified with the key of the expected signer
+        let mut signer_expected = false;
+        // content encryption algorithm of a decrypted message
+        let mut encryption_algorithm = None;
+        // recipient kid, `skid` and stored keypair of a message decrypted with stored keys
+        #[cfg(feature = "state_storage")]
+        let mut sender_keypair = None;
+        // key of the sender, that an `authcrypt` or `signed` message has been decrypted with
+        let mut sender_public_key = None;
 
         // message string, that will be returned
-        let decrypted: String = if parsed_message.is_ok()
+        let decrypted: String = if packing == PackingMode::Jws
+            && !matches!(options_parsed.skip_message_packaging, Some(true))
+        {
+            // verify with the key of the expected signer, if given
+            let signing_others_public = options_parsed
+                .signing_keys
+                .as_ref()
+                .and_then(|keys| keys.signing_others_public.clone());
+            signer_expected = signing_others_public.is_some();
+            let (verified, verified_signer) =
+                verify_message(message, signing_others_public.as_ref().map(|v| &v[..]))?;
+            signer = Some(verified_signer);
+            verified
+        } else if parsed_message.is_ok()
             && !matches!(options_parsed.skip_message_packaging, Some(true))
         {
+            // DID of the sender, used to resolve its key if it is not known
+            let skid = parsed_message
+                .as_ref()
+                .ok()
+                .and_then(|jwe| jwe.protected.as_ref()?.skid.clone())
+                .unwrap_or_default();
             // if the message is encrypted, try to decrypt it
             // if shared secret was passed to the options, use this one
-            let decryption_keys: EncryptionKeys = if options_parsed.encryption_keys.is_some() {
+            let mut decryption_keys: EncryptionKeys = if options_parsed.encryption_keys.is_some() {
                 options_parsed
                     .encryption_keys
                     .ok_or("encryption_keys is missing")?
@@ -276,67 +865,238 @@ impl VadePlugin for VadeDidComm {
                         return Err(Box::from("encryption_keys must be provided if 'state_storage' is disabled".to_string()));
                     } else {
                         let parsed_message = parsed_message?;
-                        let from = parsed_message
-                        .protected
-                        .unwrap_or_default()
-                        .skid
-                        .unwrap_or_default();
-                        let recipient = &parsed_message.recipients.unwrap_or_default()[0];
-                        let to = recipient.header.kid.as_ref().unwrap();
-                        log::debug!("fetching kak for from: {} to: {}", to, from);
-                        let mut encoded_keypair = get_key_agreement_key(to);
-                        if encoded_keypair.is_err() {
-                            // when we don't find a stored keypair, try to get the key agreement key
-                            log::debug!("fetching kak for {}", to);
-                            encoded_keypair = get_com_keypair(to, &from);
-                            if encoded_keypair.is_err() {
-                                return Err(Box::from("No keypair found"));
-                            }
-                        }
-                        let keypair = encoded_keypair?;
+                        let from = skid.clone();
+                        // messages can have multiple recipients, use the first one we have a key for
+                        let (to, keypair) = parsed_message
+                            .recipients
+                            .unwrap_or_default()
+                            .iter()
+                            .filter_map(|recipient| recipient.header.kid.as_ref())
+                            .find_map(|to| {
+                                log::debug!("fetching kak for from: {} to: {}", to, from);
+                                get_key_agreement_key(&storage, to)
+                                    .or_else(|_| {
+                                        // when we don't find a key agreement key, try to get the
+                                        // stored keypair
+                                        get_com_keypair(&storage, to, &from)
+                                    })
+                                    .ok()
+                                    .map(|keypair| (to.to_owned(), keypair))
+                            })
+                            .ok_or("No keypair found")?;
                         let mut target_pub_key = None;
                         if !keypair.target_pub_key.is_empty() {
                             target_pub_key = Some(
-                                vec_to_array(hex::decode(keypair.target_pub_key)?)?
+                                vec_to_array(hex::decode(&keypair.target_pub_key)?)?
                             );
                         }
+                        sender_keypair = Some((to, from, keypair.clone()));
                         EncryptionKeys {
                             encryption_my_secret: vec_to_array(hex::decode(keypair.secret_key)?)?,
                             encryption_others_public: target_pub_key,
+                            encryption_others_public_keys: Vec::new(),
+                            encryption_key_type: None,
                         }
                     }
                 }
             };
-            let signing_others_public = options_parsed
-                .signing_keys
-                .and_then(|keys| keys.signing_others_public);
-            decrypt_message(
-                message,
-                Some(&decryption_keys.encryption_my_secret),
-                decryption_keys
-                    .encryption_others_public
-                    .as_ref()
-                    .map(|v| v.to_vec()),
-                signing_others_public.as_ref().map(|v| &v[..]),
-            )?
+            check_key_agreement_key_type(&decryption_keys)?;
+            if decryption_keys.encryption_others_public.is_none() && !skid.is_empty() {
+                // without a known key of the sender, resolve it from its DID
+                let resolved = resolve_key_agreement_key(&skid, self.resolver.as_deref_mut())
+                    .await
+                    .ok();
+                decryption_keys.encryption_others_public = resolved;
+                #[cfg(feature = "state_storage")]
+                if let (Some((_, _, keypair)), Some(resolved)) = (sender_keypair.as_mut(), resolved)
+                {
+                    keypair.target_pub_key = hex::encode(resolved);
+                    keypair.target_key_agreement_key = get_did(&skid).to_string();
+                }
+            }
+            // signed messages are verified with the key of the expected signer, if given, or with
+            // the key in their `kid`
+            let signing_others_public = match packing {
+                PackingMode::Signed => {
+                    let expected_signer = options_parsed
+                        .signing_keys
+                        .and_then(|keys| keys.signing_others_public);
+                    signer_expected = expected_signer.is_some();
+                    match expected_signer {
+                        Some(expected_signer) => Some(expected_signer),
+                        None => Some(get_signed_message_kid(message)?),
+                    }
+                }
+                _ => None,
+            };
+            signer = signing_others_public.as_ref().map(hex::encode);
+            encryption_algorithm = Some(get_encryption_algorithm(message)?);
+            #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+            let mut decrypted = match packing {
+                // anonymous senders are identified by the ephemeral key of the message only
+                PackingMode::Anoncrypt => {
+                    decrypt_message_anonymously(message, &decryption_keys.encryption_my_secret)
+                }
+                _ => decrypt_message(
+                    message,
+                    Some(&decryption_keys.encryption_my_secret),
+                    decryption_keys
+                        .encryption_others_public
+                        .as_ref()
+                        .map(|v| v.to_vec()),
+                    signing_others_public.as_ref().map(|v| &v[..]),
+                ),
+            };
+            // a sender, that rotated its DID with `from_prior`, encrypts with the key of its new
+            // DID, the rotation is verified after decryption
+            #[cfg(feature = "state_storage")]
+            if decrypted.is_err()
+                && sender_keypair.is_some()
+                && packing != PackingMode::Anoncrypt
+                && !skid.is_empty()
+            {
+                if let Ok(resolved) =
+                    resolve_key_agreement_key(&skid, self.resolver.as_deref_mut()).await
+                {
+                    decrypted = decrypt_message(
+                        message,
+                        Some(&decryption_keys.encryption_my_secret),
+                        Some(resolved.to_vec()),
+                        signing_others_public.as_ref().map(|v| &v[..]),
+                    );
+                    decryption_keys.encryption_others_public = Some(resolved);
+                }
+            }
+            if matches!(packing, PackingMode::Authcrypt | PackingMode::Signed) {
+                sender_public_key = decryption_keys.encryption_others_public;
+            }
+
+            decrypted?
         } else {
             String::from(message)
         };
 
+        // reject expired, future and replayed messages before handling them
+        let received_message: ExtendedMessage = serde_json::from_str(&decrypted)?;
+        let now = get_now()?;
+        check_timestamps(&received_message, &self.receive_policy, now)?;
+        #[cfg(feature = "state_storage")]
+        if self.receive_policy.reject_replays {
+            check_replay(&storage, &received_message)?;
+        }
+
+        // a sender, that rotated its DID, proves it with a JWT signed by its prior DID
+        #[allow(unused_variables)] // may not be used, depending on feature setup
+        let rotation = match received_message.from_prior.as_deref() {
+            Some(from_prior) => Some(
+                verify_from_prior(
+                    from_prior,
+                    received_message.from.as_deref(),
+                    self.resolver.as_deref_mut(),
+                    &self.receive_policy,
+                    now,
+                )
+                .await?,
+            ),
+            None => None,
+        };
+
+        // the signer is only authenticated by the key of the expected signer or a key of `from`
+        let signed_by_sender = match (signer.as_deref(), received_message.from.as_deref()) {
+            (Some(signer), Some(from)) => {
+                is_verification_key_of(from, &hex::decode(signer)?, self.resolver.as_deref_mut())
+                    .await
+            }
+            _ => false,
+        };
+        let signer_authenticated = signer_expected || signed_by_sender;
+
+        // the sender of encrypted messages must own the key the message was encrypted with, the
+        // sender of `jws` messages the key the message was signed with, senders of `anoncrypt` and
+        // `plaintext` messages can not be authenticated
+        #[allow(unused_mut)] // may need to be mutable, depending on feature setup
+        let mut sender_authenticated = false;
+        #[cfg(feature = "state_storage")]
+        {
+            let authenticated_sender = sender_keypair
+                .filter(|_| packing != PackingMode::Anoncrypt)
+                .map(|(to, skid, keypair)| {
+                    let receivers: Vec<String> = std::iter::once(to)
+                        .chain(received_message.to.clone().unwrap_or_default())
+                        .collect();
+                    (receivers, skid, keypair)
+                });
+            if let Some(rotation) = rotation.as_ref() {
+                // only the comm partner can rotate its own DID
+                if let Some((receivers, _, keypair)) = authenticated_sender.as_ref() {
+                    ensure_key_owner(&storage, keypair, receivers, &rotation.iss)?;
+                }
+                let target_pub_key =
+                    resolve_key_agreement_key(&rotation.sub, self.resolver.as_deref_mut())
+                        .await
+                        .ok()
+                        .map(hex::encode);
+                let rotated = rotate_comm_keypairs(
+                    &storage,
+                    &rotation.iss,
+                    &rotation.sub,
+                    target_pub_key.as_deref(),
+                )?;
+                log::debug!(
+                    "rotated {} keypairs from {} to {}",
+                    rotated,
+                    rotation.iss,
+                    rotation.sub
+                );
+            }
+            if let Some((receivers, skid, mut keypair)) = authenticated_sender {
+                if let Some(rotation) = rotation.as_ref() {
+                    keypair.target_key_agreement_key = rotation.sub.clone();
+                }
+                let senders = std::iter::once(get_did(&skid).to_string())
+                    .chain(received_message.from.clone())
+                    .filter(|sender| !sender.is_empty());
+                for sender in senders {
+                    ensure_key_owner(&storage, &keypair, &receivers, &sender)?;
+                }
+                sender_authenticated = received_message.from.is_some();
+            }
+        }
+        // keys passed with the options are only trusted for the `from` DID, that they belong to
+        if let (false, Some(sender_public_key), Some(from)) = (
+            sender_authenticated,
+            sender_public_key,
+            received_message.from.as_deref(),
+        ) {
+            sender_authenticated = resolve_key_agreement_key(from, self.resolver.as_deref_mut())
+                .await
+                .ok()
+                == Some(sender_public_key);
+        }
+        if packing == PackingMode::Jws {
+            sender_authenticated = signed_by_sender;
+        }
+
         // run protocol specific logic
         let message_with_id = fill_message_id_and_timestamps(&decrypted)?;
 
         cfg_if::cfg_if! {
             if #[cfg(feature = "state_storage")] {
                 // store unencrypted raw message in db
-                write_raw_message_to_db(&message_with_id)?;
+                write_raw_message_to_db(&storage, &message_with_id)?;
             } else {}
         }
 
         let protocol_result = match options_parsed.skip_protocol_handling {
             None | Some(false) => {
                 // run protocol specific logic
-                ProtocolHandler::after_receive(options, &message_with_id)?
+                ProtocolHandler::after_receive(
+                    &storage,
+                    options,
+                    &message_with_id,
+                    sender_authenticated,
+                    &self.hidden_protocols,
+                )?
             }
             _ => ProtocolHandleOutput {
                 direction: MessageDirection::Receive,
@@ -348,12 +1108,41 @@ impl VadePlugin for VadeDidComm {
             },
         };
 
+        // only mark handled messages as received, so failed messages can be received again
+        #[cfg(feature = "state_storage")]
+        if self.receive_policy.reject_replays {
+            save_received_message_id(&storage, &received_message)?;
+        }
+
+        let mut metadata =
+            add_to_metadata(&protocol_result.metadata, "packing", &packing.to_string())?;
+        metadata = add_to_metadata(
+            &metadata,
+            "senderAuthenticated",
+            &sender_authenticated.to_string(),
+        )?;
+        if let Some(signer) = signer {
+            metadata = add_to_metadata(&metadata, "signer", &signer)?;
+            metadata = add_to_metadata(
+                &metadata,
+                "signerAuthenticated",
+                &signer_authenticated.to_string(),
+            )?;
+        }
+        if let Some(encryption_algorithm) = encryption_algorithm {
+            metadata = add_to_metadata(
+                &metadata,
+                "encryptionAlgorithm",
+                &encryption_algorithm.to_string(),
+            )?;
+        }
+
         let receive_result = format!(
             r#"{{
                 "message": {},
                 "metadata": {}
             }}"#,
-            protocol_result.message, protocol_result.metadata,
+            protocol_result.message, metadata,
         );
 
         return Ok(VadePluginResultValue::Success(Some(receive_result)));
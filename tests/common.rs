@@ -1,10 +1,24 @@
-#[cfg(feature = "state_storage")]
-use rocksdb::{DBWithThreadMode, MultiThreaded, DB};
 use vade::Vade;
-use vade_didcomm::VadeDidComm;
+#[cfg(feature = "state_storage")]
+use vade_didcomm::db::{get_default_storage, StorageConfig};
+use vade_didcomm::{VadeDidComm, VadeDidCommConfig};
 
-#[allow(dead_code)] // usage depends on integration test, so prevent false positives on unused code
-const ROCKS_DB_PATH: &str = "./.didcomm_rocks_db";
+/// Points the redis storage to a stand-in server, if no redis server has been configured.
+#[cfg(feature = "redis_storage")]
+fn use_redis_stand_in() -> Result<(), Box<dyn std::error::Error>> {
+    static START: std::sync::Once = std::sync::Once::new();
+    let mut result = Ok(());
+    START.call_once(|| {
+        if std::env::var("VADE_DIDCOMM_REDIS_URL").is_err() {
+            match utilities::redis_stand_in::start_redis_stand_in() {
+                Ok(url) => std::env::set_var("VADE_DIDCOMM_REDIS_URL", url),
+                Err(e) => result = Err(e.to_string()),
+            }
+        }
+    });
+
+    Ok(result?)
+}
 
 #[allow(dead_code)] // usage depends on integration test, so prevent false positives on unused code
 pub fn read_db(_key: &str) -> Result<String, Box<dyn std::error::Error>> {
@@ -12,20 +26,18 @@ pub fn read_db(_key: &str) -> Result<String, Box<dyn std::error::Error>> {
         if #[cfg(not(feature = "state_storage"))] {
                 return Err(Box::from("read_db cannot be used if 'state_storage' is disabled".to_string()));
         } else {
-            let db: DBWithThreadMode<MultiThreaded> = DB::open_default(ROCKS_DB_PATH)?;
-
-            match db.get(_key) {
-                Ok(Some(result)) => Ok(String::from_utf8(result)?),
-                Ok(None) => Err(format!("{0} not found", _key).into()),
-                Err(e) => Err(format!("Error while loading key: {0}, {1}", _key, e).into()),
-            }
+            #[cfg(feature = "redis_storage")]
+            use_redis_stand_in()?;
+            get_default_storage(&StorageConfig::default())?.get(_key)
         }
     }
 }
 
 pub async fn get_vade() -> Result<Vade, Box<dyn std::error::Error>> {
+    #[cfg(feature = "redis_storage")]
+    use_redis_stand_in()?;
     let mut vade = Vade::new();
-    let vade_didcomm = VadeDidComm::new()?;
+    let vade_didcomm = VadeDidComm::new(VadeDidCommConfig::default())?;
     vade.register_plugin(Box::from(vade_didcomm));
 
     Ok(vade)
@@ -0,0 +1,243 @@
+#[cfg(feature = "state_storage")]
+mod common;
+
+#[cfg(feature = "state_storage")]
+use common::get_vade;
+#[cfg(feature = "state_storage")]
+use rand_core::OsRng;
+#[cfg(feature = "state_storage")]
+use serial_test::serial;
+#[cfg(feature = "state_storage")]
+use vade::Vade;
+#[cfg(feature = "state_storage")]
+use vade_didcomm::{
+    datatypes::{VadeDidCommPluginReceiveOutput, VadeDidCommPluginSendOutput},
+    db::MemoryStorage,
+    protocols::coordinate_mediation::datatypes::{
+        KeylistData,
+        KeylistUpdateResponseData,
+        KeylistUpdateResult,
+        KeylistUpdated,
+        COORDINATE_MEDIATION_PROTOCOL_URL,
+    },
+    VadeDidComm,
+    VadeDidCommConfig,
+};
+#[cfg(feature = "state_storage")]
+use x25519_dalek::{PublicKey, StaticSecret};
+
+#[cfg(feature = "state_storage")]
+fn get_did_key(secret: &StaticSecret) -> String {
+    let mut key = vec![0xec, 0x01];
+    key.extend_from_slice(PublicKey::from(secret).as_bytes());
+    format!("did:key:z{}", bs58::encode(key).into_string())
+}
+
+#[cfg(feature = "state_storage")]
+fn get_options(secret: &StaticSecret) -> String {
+    format!(
+        r#"{{ "encryptionKeys": {{ "encryptionMySecret": "{}" }} }}"#,
+        hex::encode(secret.to_bytes()),
+    )
+}
+
+#[cfg(feature = "state_storage")]
+async fn send(
+    vade: &mut Vade,
+    secret: &StaticSecret,
+    message: serde_json::Value,
+) -> Result<VadeDidCommPluginSendOutput<serde_json::Value>, Box<dyn std::error::Error>> {
+    let results = vade
+        .didcomm_send(&get_options(secret), &serde_json::to_string(&message)?)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+
+    Ok(serde_json::from_str(result)?)
+}
+
+#[cfg(feature = "state_storage")]
+async fn receive(
+    vade: &mut Vade,
+    secret: &StaticSecret,
+    message: &serde_json::Value,
+) -> Result<VadeDidCommPluginReceiveOutput<serde_json::Value>, Box<dyn std::error::Error>> {
+    let results = vade
+        .didcomm_receive(&get_options(secret), &serde_json::to_string(message)?)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+
+    Ok(serde_json::from_str(result)?)
+}
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_coordinate_mediation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut mediator = get_vade().await?;
+    let mut recipient = Vade::new();
+    recipient.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        ..Default::default()
+    })?));
+    let mediator_secret = StaticSecret::new(OsRng);
+    let recipient_secret = StaticSecret::new(OsRng);
+    let mediator_did = get_did_key(&mediator_secret);
+    let recipient_did = get_did_key(&recipient_secret);
+    let routed_did = get_did_key(&StaticSecret::new(OsRng));
+    let new_message = |step: &str, thid: Option<&str>, body: serde_json::Value| {
+        let (from, to) = match step {
+            "mediate-request" | "keylist-update" | "keylist-query" => {
+                (&recipient_did, &mediator_did)
+            }
+            _ => (&mediator_did, &recipient_did),
+        };
+        serde_json::json!({
+            "type": format!("{}/{}", COORDINATE_MEDIATION_PROTOCOL_URL, step),
+            "from": from,
+            "to": [to],
+            "thid": thid,
+            "body": body,
+        })
+    };
+
+    // keys can't be registered before mediation has been granted
+    let update_body = serde_json::json!({
+        "updates": [{ "recipient_did": routed_did, "action": "add" }],
+    });
+    assert!(send(
+        &mut recipient,
+        &recipient_secret,
+        new_message("keylist-update", None, update_body.clone()),
+    )
+    .await
+    .is_err());
+
+    // request and grant mediation
+    let request = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message("mediate-request", None, serde_json::json!({})),
+    )
+    .await?;
+    let thid = request.message_raw["id"]
+        .as_str()
+        .ok_or("missing id")?
+        .to_owned();
+    receive(&mut mediator, &mediator_secret, &request.message).await?;
+    let grant = send(
+        &mut mediator,
+        &mediator_secret,
+        new_message(
+            "mediate-grant",
+            Some(&thid),
+            serde_json::json!({ "routing_did": mediator_did }),
+        ),
+    )
+    .await?;
+    let received = receive(&mut recipient, &recipient_secret, &grant.message).await?;
+    assert_eq!(received.metadata.get("routingDid"), Some(&mediator_did));
+
+    // register a key and get its result as body for the response
+    let update = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message("keylist-update", None, update_body),
+    )
+    .await?;
+    let received = receive(&mut mediator, &mediator_secret, &update.message).await?;
+    let updated: Vec<KeylistUpdated> =
+        serde_json::from_str(received.metadata.get("updated").ok_or("missing updated")?)?;
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].result, KeylistUpdateResult::Success);
+    let update_thid = update.message_raw["id"].as_str().ok_or("missing id")?;
+    let response = send(
+        &mut mediator,
+        &mediator_secret,
+        new_message(
+            "keylist-update-response",
+            Some(update_thid),
+            serde_json::json!({ "updated": updated }),
+        ),
+    )
+    .await?;
+    let received = receive(&mut recipient, &recipient_secret, &response.message).await?;
+    let response_body: KeylistUpdateResponseData =
+        serde_json::from_value(received.message["body"].clone())?;
+    assert_eq!(response_body.updated, updated);
+
+    // query the keylist, the mediator fills in the registered keys
+    let query = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message("keylist-query", None, serde_json::json!({})),
+    )
+    .await?;
+    receive(&mut mediator, &mediator_secret, &query.message).await?;
+    let query_thid = query.message_raw["id"].as_str().ok_or("missing id")?;
+    let keylist = send(
+        &mut mediator,
+        &mediator_secret,
+        new_message(
+            "keylist",
+            Some(query_thid),
+            serde_json::json!({ "keys": [] }),
+        ),
+    )
+    .await?;
+    let received = receive(&mut recipient, &recipient_secret, &keylist.message).await?;
+    let keylist_body: KeylistData = serde_json::from_value(received.message["body"].clone())?;
+    assert_eq!(keylist_body.keys.len(), 1);
+    assert_eq!(keylist_body.keys[0].recipient_did, routed_did);
+
+    // keys can't be registered with anonymous messages, that only claim to be from the recipient
+    let forged_update = new_message(
+        "keylist-update",
+        None,
+        serde_json::json!({
+            "updates": [{ "recipient_did": get_did_key(&StaticSecret::new(OsRng)), "action": "add" }],
+        }),
+    );
+    let results = recipient
+        .didcomm_send(
+            r#"{ "packing": "anoncrypt", "skipProtocolHandling": true }"#,
+            &serde_json::to_string(&forged_update)?,
+        )
+        .await?;
+    let forged: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(
+        results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?,
+    )?;
+    let rejected = receive(&mut mediator, &mediator_secret, &forged.message)
+        .await
+        .err()
+        .ok_or("forged keylist update has been accepted")?;
+    assert!(rejected
+        .to_string()
+        .contains("requires an authenticated sender"));
+
+    // mediation can only be granted once per thread
+    assert!(send(
+        &mut mediator,
+        &mediator_secret,
+        new_message(
+            "mediate-grant",
+            Some(&thid),
+            serde_json::json!({ "routing_did": mediator_did }),
+        ),
+    )
+    .await
+    .is_err());
+
+    Ok(())
+}
@@ -2,6 +2,8 @@ mod common;
 
 use common::{get_vade, read_db};
 use didcomm_rs::Jwe;
+#[cfg(feature = "state_storage")]
+use rand_core::OsRng;
 use serial_test::serial;
 use utilities::keypair::get_keypair_set;
 use uuid::Uuid;
@@ -20,7 +22,7 @@ use vade_didcomm::{
 };
 #[cfg(feature = "state_storage")]
 use vade_didcomm::{
-    datatypes::{DidCommOptions, EncryptionKeyPair, EncryptionKeys},
+    datatypes::{DidCommOptions, EncryptionKeyPair, EncryptionKeys, FromPriorOptions},
     protocols::did_exchange::datatypes::{ProblemReport, ProblemReportData, UserType},
 };
 
@@ -526,20 +528,20 @@ async fn can_do_key_exchange_with_create_keys() -> Result<(), Box<dyn std::error
         encryption_keys: Some(EncryptionKeys {
             encryption_my_secret: alice_keys.secret,
             encryption_others_public: Some(bob_keys.public),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
         }),
-        signing_keys: None,
-        skip_message_packaging: Some(false),
-        skip_protocol_handling: Some(false),
+        ..Default::default()
     };
 
     let didcomm_options_bob = DidCommOptions {
         encryption_keys: Some(EncryptionKeys {
             encryption_my_secret: bob_keys.secret,
             encryption_others_public: Some(alice_keys.public),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
         }),
-        signing_keys: None,
-        skip_message_packaging: Some(false),
-        skip_protocol_handling: Some(false),
+        ..Default::default()
     };
 
     let sender_options_stringified =
@@ -596,20 +598,20 @@ async fn can_report_problem() -> Result<(), Box<dyn std::error::Error>> {
         encryption_keys: Some(EncryptionKeys {
             encryption_my_secret: alice_keys.secret,
             encryption_others_public: Some(bob_keys.public),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
         }),
-        signing_keys: None,
-        skip_message_packaging: Some(false),
-        skip_protocol_handling: Some(false),
+        ..Default::default()
     };
 
     let didcomm_options_bob = DidCommOptions {
         encryption_keys: Some(EncryptionKeys {
             encryption_my_secret: bob_keys.secret,
             encryption_others_public: Some(alice_keys.public),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
         }),
-        signing_keys: None,
-        skip_message_packaging: Some(false),
-        skip_protocol_handling: Some(false),
+        ..Default::default()
     };
 
     let sender_options_stringified =
@@ -661,3 +663,366 @@ async fn can_report_problem() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_reject_messages_with_forged_sender() -> Result<(), Box<dyn std::error::Error>> {
+    use std::convert::TryInto;
+
+    let mut vade = get_vade().await?;
+    let test_setup = get_keypair_set();
+    let id = Uuid::new_v4().to_simple().to_string();
+
+    let request_message = send_request(
+        &mut vade,
+        &test_setup.user1_did,
+        &test_setup.user2_did,
+        &test_setup.sender_options_stringified,
+        &id,
+    )
+    .await?;
+    receive_request(
+        &mut vade,
+        request_message,
+        &test_setup.receiver_options_stringified,
+    )
+    .await?;
+    let response_message = send_response(
+        &mut vade,
+        &test_setup.user2_did,
+        &test_setup.user1_did,
+        &test_setup.receiver_signing_options_stringified,
+        &id,
+    )
+    .await?;
+    receive_response(
+        &mut vade,
+        response_message,
+        &test_setup.sender_signing_options_stringified,
+    )
+    .await?;
+
+    // encrypt messages with the comm keys of the sender, but claim different sender DIDs
+    let db_result = read_db(&format!(
+        "comm_keypair_{}_{}",
+        test_setup.user1_did, test_setup.user2_did
+    ))?;
+    let comm_keypair: CommKeyPair = serde_json::from_str(&db_result)?;
+    let options = DidCommOptions {
+        encryption_keys: Some(EncryptionKeys {
+            encryption_my_secret: hex::decode(&comm_keypair.secret_key)?
+                .try_into()
+                .map_err(|_| "invalid secret key")?,
+            encryption_others_public: Some(
+                hex::decode(&comm_keypair.target_pub_key)?
+                    .try_into()
+                    .map_err(|_| "invalid public key")?,
+            ),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
+        }),
+        skip_protocol_handling: Some(true),
+        ..Default::default()
+    };
+    let options = serde_json::to_string(&options)?;
+
+    for (from, is_valid) in [
+        (comm_keypair.key_agreement_key.as_str(), true),
+        (test_setup.user1_did.as_str(), true),
+        (
+            "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG",
+            false,
+        ),
+    ] {
+        let message = format!(
+            r#"{{
+                "type": "https://didcomm.org/trust_ping/1.0/ping",
+                "from": "{}",
+                "to": ["{}"],
+                "body": {{}}
+            }}"#,
+            from, comm_keypair.target_key_agreement_key,
+        );
+        let results = vade.didcomm_send(&options, &message).await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let prepared: VadeDidCommPluginSendOutput<Jwe> = serde_json::from_str(result)?;
+
+        // receive with the keys stored during the DID exchange
+        let received = vade
+            .didcomm_receive(
+                r#"{ "skipProtocolHandling": true }"#,
+                &serde_json::to_string(&prepared.message)?,
+            )
+            .await;
+        assert_eq!(received.is_ok(), is_valid, "unexpected result for {}", from);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_rotate_did_with_from_prior() -> Result<(), Box<dyn std::error::Error>> {
+    use std::convert::TryInto;
+
+    let mut vade = get_vade().await?;
+    let test_setup = get_keypair_set();
+    let id = Uuid::new_v4().to_simple().to_string();
+
+    // use DIDs with known Ed25519 keys for the sender, so it can sign its DID rotation
+    let get_did = |secret: &ed25519_dalek::SecretKey| {
+        let public = ed25519_dalek::PublicKey::from(secret);
+        format!(
+            "did:key:z{}",
+            bs58::encode([&[0xed, 0x01][..], public.as_bytes()].concat()).into_string()
+        )
+    };
+    let prior_secret = ed25519_dalek::SecretKey::generate(&mut OsRng);
+    let prior_did = get_did(&prior_secret);
+    let new_secret = ed25519_dalek::SecretKey::generate(&mut OsRng);
+    let new_did = get_did(&new_secret);
+
+    let request_message = send_request(
+        &mut vade,
+        &prior_did,
+        &test_setup.user2_did,
+        &test_setup.sender_options_stringified,
+        &id,
+    )
+    .await?;
+    receive_request(
+        &mut vade,
+        request_message,
+        &test_setup.receiver_options_stringified,
+    )
+    .await?;
+    let response_message = send_response(
+        &mut vade,
+        &test_setup.user2_did,
+        &prior_did,
+        &test_setup.receiver_signing_options_stringified,
+        &id,
+    )
+    .await?;
+    receive_response(
+        &mut vade,
+        response_message,
+        &test_setup.sender_signing_options_stringified,
+    )
+    .await?;
+
+    // the new DID encrypts with the X25519 key of its Ed25519 key pair
+    let db_result = read_db(&format!(
+        "comm_keypair_{}_{}",
+        prior_did, test_setup.user2_did
+    ))?;
+    let comm_keypair: CommKeyPair = serde_json::from_str(&db_result)?;
+    let expanded_secret = ed25519_dalek::ExpandedSecretKey::from(&new_secret).to_bytes();
+    let message = format!(
+        r#"{{
+            "type": "https://didcomm.org/trust_ping/1.0/ping",
+            "from": "{}",
+            "to": ["{}"],
+            "body": {{}}
+        }}"#,
+        new_did, comm_keypair.target_key_agreement_key,
+    );
+
+    for (prior_signing_secret, is_valid) in [
+        // the new DID is unknown before the rotation
+        (None, false),
+        // only the prior DID can rotate itself
+        (Some([7u8; 32]), false),
+        (Some(prior_secret.to_bytes()), true),
+        // the stored keypairs now belong to the new DID
+        (None, true),
+    ] {
+        let options = DidCommOptions {
+            encryption_keys: Some(EncryptionKeys {
+                encryption_my_secret: expanded_secret[..32]
+                    .try_into()
+                    .map_err(|_| "invalid secret key")?,
+                encryption_others_public: Some(
+                    hex::decode(&comm_keypair.target_pub_key)?
+                        .try_into()
+                        .map_err(|_| "invalid public key")?,
+                ),
+                encryption_others_public_keys: Vec::new(),
+                encryption_key_type: None,
+            }),
+            skip_protocol_handling: Some(true),
+            from_prior: prior_signing_secret.map(|secret| FromPriorOptions {
+                prior_did: prior_did.clone(),
+                prior_kid: None,
+                prior_signing_secret: secret,
+            }),
+            ..Default::default()
+        };
+        let options = serde_json::to_string(&options)?;
+        let results = vade.didcomm_send(&options, &message).await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let prepared: VadeDidCommPluginSendOutput<Jwe> = serde_json::from_str(result)?;
+
+        // receive with the keys stored during the DID exchange
+        let received = vade
+            .didcomm_receive(
+                r#"{ "skipProtocolHandling": true }"#,
+                &serde_json::to_string(&prepared.message)?,
+            )
+            .await;
+        assert_eq!(
+            received.is_ok(),
+            is_valid,
+            "unexpected result for {:?}",
+            prior_signing_secret.map(hex::encode),
+        );
+    }
+
+    assert!(read_db(&format!(
+        "comm_keypair_{}_{}",
+        test_setup.user2_did, new_did
+    ))
+    .is_ok());
+    assert!(read_db(&format!(
+        "comm_keypair_{}_{}",
+        test_setup.user2_did, prior_did
+    ))
+    .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_forward_messages_to_partners_behind_mediators(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = get_vade().await?;
+    let test_setup = get_keypair_set();
+    let id = Uuid::new_v4().to_simple().to_string();
+
+    let mediator_secret = x25519_dalek::StaticSecret::new(OsRng);
+    let mediator_did = format!(
+        "did:key:z{}",
+        bs58::encode(
+            [
+                &[0xec, 0x01][..],
+                x25519_dalek::PublicKey::from(&mediator_secret).as_bytes(),
+            ]
+            .concat()
+        )
+        .into_string()
+    );
+    let mediator_key = format!("{}#{}", mediator_did, &mediator_did[8..]);
+
+    // the invitee announces its mediator in the DID document of its response
+    let mut response_options: serde_json::Value =
+        serde_json::from_str(&test_setup.receiver_signing_options_stringified)?;
+    response_options["serviceRoutingKeys"] = serde_json::json!([mediator_key]);
+
+    let request_message = send_request(
+        &mut vade,
+        &test_setup.user1_did,
+        &test_setup.user2_did,
+        &test_setup.sender_options_stringified,
+        &id,
+    )
+    .await?;
+    receive_request(
+        &mut vade,
+        request_message,
+        &test_setup.receiver_options_stringified,
+    )
+    .await?;
+    let response_message = send_response(
+        &mut vade,
+        &test_setup.user2_did,
+        &test_setup.user1_did,
+        &serde_json::to_string(&response_options)?,
+        &id,
+    )
+    .await?;
+    receive_response(
+        &mut vade,
+        response_message,
+        &test_setup.sender_signing_options_stringified,
+    )
+    .await?;
+
+    let db_result = read_db(&format!(
+        "comm_keypair_{}_{}",
+        test_setup.user1_did, test_setup.user2_did
+    ))?;
+    let comm_keypair: CommKeyPair = serde_json::from_str(&db_result)?;
+    assert_eq!(comm_keypair.target_routing_keys, vec![mediator_key]);
+
+    // messages to the invitee are wrapped in a forward message for its mediator
+    let message = format!(
+        r#"{{
+            "type": "https://didcomm.org/trust_ping/1.0/ping",
+            "from": "{}",
+            "to": ["{}"],
+            "body": {{}}
+        }}"#,
+        test_setup.user1_did, test_setup.user2_did,
+    );
+    let results = vade.didcomm_send("{}", &message).await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let sent: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(result)?;
+
+    let mediator_options = format!(
+        r#"{{ "encryptionKeys": {{ "encryptionMySecret": "{}" }} }}"#,
+        hex::encode(mediator_secret.to_bytes()),
+    );
+    let results = vade
+        .didcomm_receive(&mediator_options, &serde_json::to_string(&sent.message)?)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let forwarded: VadeDidCommPluginReceiveOutput<serde_json::Value> =
+        serde_json::from_str(result)?;
+    assert_eq!(
+        forwarded.metadata.get("next"),
+        Some(&comm_keypair.target_key_agreement_key)
+    );
+
+    // the invitee decrypts the forwarded message with its stored keys
+    let results = vade
+        .didcomm_receive(
+            "{}",
+            forwarded
+                .metadata
+                .get("forwardedMessage")
+                .ok_or("no forwarded message")?,
+        )
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let received: VadeDidCommPluginReceiveOutput<serde_json::Value> = serde_json::from_str(result)?;
+    assert_eq!(
+        received.message["type"],
+        "https://didcomm.org/trust_ping/1.0/ping"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+#[cfg(feature = "state_storage")]
+use rand_core::OsRng;
+#[cfg(feature = "state_storage")]
+use serial_test::serial;
+#[cfg(feature = "state_storage")]
+use vade::Vade;
+#[cfg(feature = "state_storage")]
+use vade_didcomm::{
+    datatypes::{VadeDidCommPluginReceiveOutput, VadeDidCommPluginSendOutput},
+    db::MemoryStorage,
+    protocols::discover_features::datatypes::{Disclosure, DISCOVER_FEATURES_PROTOCOL_URL},
+    VadeDidComm,
+    VadeDidCommConfig,
+};
+#[cfg(feature = "state_storage")]
+use x25519_dalek::{PublicKey, StaticSecret};
+
+#[cfg(feature = "state_storage")]
+fn get_did_key(secret: &StaticSecret) -> String {
+    let mut key = vec![0xec, 0x01];
+    key.extend_from_slice(PublicKey::from(secret).as_bytes());
+    format!("did:key:z{}", bs58::encode(key).into_string())
+}
+
+#[cfg(feature = "state_storage")]
+fn get_vade_with_memory_storage(
+    hidden_protocols: &[&str],
+) -> Result<Vade, Box<dyn std::error::Error>> {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        hidden_protocols: hidden_protocols.iter().map(|id| id.to_string()).collect(),
+        ..Default::default()
+    })?));
+
+    Ok(vade)
+}
+
+#[cfg(feature = "state_storage")]
+fn get_options(secret: &StaticSecret) -> String {
+    serde_json::json!({
+        "encryptionKeys": { "encryptionMySecret": hex::encode(secret.to_bytes()) },
+    })
+    .to_string()
+}
+
+#[cfg(feature = "state_storage")]
+async fn send(
+    vade: &mut Vade,
+    options: &str,
+    message: serde_json::Value,
+) -> Result<VadeDidCommPluginSendOutput<serde_json::Value>, Box<dyn std::error::Error>> {
+    let results = vade
+        .didcomm_send(options, &serde_json::to_string(&message)?)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+
+    Ok(serde_json::from_str(result)?)
+}
+
+#[cfg(feature = "state_storage")]
+async fn receive(
+    vade: &mut Vade,
+    options: &str,
+    message: &serde_json::Value,
+) -> Result<VadeDidCommPluginReceiveOutput<serde_json::Value>, Box<dyn std::error::Error>> {
+    let results = vade
+        .didcomm_receive(options, &serde_json::to_string(message)?)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+
+    Ok(serde_json::from_str(result)?)
+}
+
+#[cfg(feature = "state_storage")]
+fn get_disclosures(
+    received: &VadeDidCommPluginReceiveOutput<serde_json::Value>,
+) -> Result<Vec<Disclosure>, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_str(
+        received
+            .metadata
+            .get("disclosures")
+            .ok_or("missing disclosures")?,
+    )?)
+}
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_discover_features() -> Result<(), Box<dyn std::error::Error>> {
+    let mut requester = get_vade_with_memory_storage(&[])?;
+    let mut responder = get_vade_with_memory_storage(&["https://didcomm.org/messagepickup/*"])?;
+    let requester_secret = StaticSecret::new(OsRng);
+    let responder_secret = StaticSecret::new(OsRng);
+    let requester_did = get_did_key(&requester_secret);
+    let responder_did = get_did_key(&responder_secret);
+    let requester_options = get_options(&requester_secret);
+    let responder_options = get_options(&responder_secret);
+    let new_message = |step: &str, thid: Option<&str>, body: serde_json::Value| {
+        let (from, to) = match step {
+            "queries" => (&requester_did, &responder_did),
+            _ => (&responder_did, &requester_did),
+        };
+        serde_json::json!({
+            "type": format!("{}/{}", DISCOVER_FEATURES_PROTOCOL_URL, step),
+            "from": from,
+            "to": [to],
+            "thid": thid,
+            "body": body,
+        })
+    };
+
+    // query protocols by prefix and id, other feature types are not answered
+    let queries = send(
+        &mut requester,
+        &requester_options,
+        new_message(
+            "queries",
+            None,
+            serde_json::json!({
+                "queries": [
+                    {
+                        "feature-type": "protocol",
+                        "match": "https://didcomm.org/coordinate-mediation/*",
+                    },
+                    { "feature-type": "protocol", "match": DISCOVER_FEATURES_PROTOCOL_URL },
+                    { "feature-type": "goal-code", "match": "*" },
+                ],
+            }),
+        ),
+    )
+    .await?;
+    let thid = queries.message_raw["id"].as_str().ok_or("missing id")?;
+    let received = receive(&mut responder, &responder_options, &queries.message).await?;
+    let disclosures = get_disclosures(&received)?;
+    assert_eq!(disclosures.len(), 2);
+    assert_eq!(
+        disclosures[0].id,
+        "https://didcomm.org/coordinate-mediation/2.0"
+    );
+    assert_eq!(disclosures[0].roles, vec!["mediator", "recipient"]);
+    assert!(disclosures[0].steps.contains(&"keylist-update".to_string()));
+    assert_eq!(disclosures[1].id, DISCOVER_FEATURES_PROTOCOL_URL);
+
+    // the responder answers the queries of the thread automatically
+    let disclose = send(
+        &mut responder,
+        &responder_options,
+        new_message("disclose", Some(thid), serde_json::json!({})),
+    )
+    .await?;
+    let received = receive(&mut requester, &requester_options, &disclose.message).await?;
+    assert_eq!(
+        received.message["body"]["disclosures"]
+            .as_array()
+            .map(Vec::len),
+        Some(2)
+    );
+    assert_eq!(get_disclosures(&received)?, disclosures);
+
+    // protocols hidden in the config are not disclosed, even if the options don't hide them
+    let unhiding_options = serde_json::json!({
+        "encryptionKeys": { "encryptionMySecret": hex::encode(responder_secret.to_bytes()) },
+        "hiddenProtocols": [],
+    })
+    .to_string();
+    let queries = send(
+        &mut requester,
+        &requester_options,
+        new_message(
+            "queries",
+            None,
+            serde_json::json!({ "queries": [{ "feature-type": "protocol", "match": "*" }] }),
+        ),
+    )
+    .await?;
+    let thid = queries.message_raw["id"].as_str().ok_or("missing id")?;
+    let received = receive(&mut responder, &unhiding_options, &queries.message).await?;
+    let disclosures = get_disclosures(&received)?;
+    assert!(disclosures
+        .iter()
+        .any(|disclosure| disclosure.id == "https://didcomm.org/didexchange/1.0"));
+    assert!(!disclosures.iter().any(|disclosure| disclosure
+        .id
+        .starts_with("https://didcomm.org/messagepickup/")));
+    let disclose = send(
+        &mut responder,
+        &unhiding_options,
+        new_message("disclose", Some(thid), serde_json::json!({})),
+    )
+    .await?;
+    let received = receive(&mut requester, &requester_options, &disclose.message).await?;
+    assert_eq!(get_disclosures(&received)?, disclosures);
+
+    Ok(())
+}
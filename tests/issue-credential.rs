@@ -757,13 +757,14 @@ async fn send_wrong_ack_state() -> Result<String, Box<dyn std::error::Error>> {
     Ok(complete_message)
 }
 
+// `#[serial]` drops `#[should_panic]` from async tests, so check the error instead of panicking
 #[tokio::test]
-#[should_panic]
 #[serial]
 #[cfg(feature = "state_storage")]
-async fn will_panic_and_fail_to_process_wrong_state() {
+async fn will_fail_to_process_wrong_state() {
     let result = send_wrong_ack_state().await;
-    if let Err(e) = result {
-        panic!("Error : {:?}", e)
+    match result {
+        Err(e) => assert!(e.to_string().contains("not allowed"), "Error : {:?}", e),
+        Ok(_) => panic!("ack in wrong state should not be processed"),
     }
 }
@@ -0,0 +1,446 @@
+#[cfg(feature = "state_storage")]
+use rand_core::OsRng;
+#[cfg(feature = "state_storage")]
+use serial_test::serial;
+#[cfg(feature = "state_storage")]
+use vade::Vade;
+#[cfg(feature = "state_storage")]
+use vade_didcomm::{
+    datatypes::{VadeDidCommPluginReceiveOutput, VadeDidCommPluginSendOutput},
+    db::MemoryStorage,
+    protocols::{
+        coordinate_mediation::datatypes::COORDINATE_MEDIATION_PROTOCOL_URL,
+        message_pickup::datatypes::{DeliveredMessage, StatusData, MESSAGE_PICKUP_PROTOCOL_URL},
+    },
+    VadeDidComm,
+    VadeDidCommConfig,
+};
+#[cfg(feature = "state_storage")]
+use x25519_dalek::{PublicKey, StaticSecret};
+
+#[cfg(feature = "state_storage")]
+fn get_did_key(secret: &StaticSecret) -> String {
+    let mut key = vec![0xec, 0x01];
+    key.extend_from_slice(PublicKey::from(secret).as_bytes());
+    format!("did:key:z{}", bs58::encode(key).into_string())
+}
+
+#[cfg(feature = "state_storage")]
+fn get_vade_with_memory_storage() -> Result<Vade, Box<dyn std::error::Error>> {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        ..Default::default()
+    })?));
+
+    Ok(vade)
+}
+
+#[cfg(feature = "state_storage")]
+async fn send(
+    vade: &mut Vade,
+    secret: &StaticSecret,
+    message: serde_json::Value,
+) -> Result<VadeDidCommPluginSendOutput<serde_json::Value>, Box<dyn std::error::Error>> {
+    let options = serde_json::json!({
+        "encryptionKeys": { "encryptionMySecret": hex::encode(secret.to_bytes()) },
+    });
+    let results = vade
+        .didcomm_send(
+            &serde_json::to_string(&options)?,
+            &serde_json::to_string(&message)?,
+        )
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+
+    Ok(serde_json::from_str(result)?)
+}
+
+#[cfg(feature = "state_storage")]
+async fn receive(
+    vade: &mut Vade,
+    secret: &StaticSecret,
+    message: &str,
+) -> Result<VadeDidCommPluginReceiveOutput<serde_json::Value>, Box<dyn std::error::Error>> {
+    let options = serde_json::json!({
+        "encryptionKeys": { "encryptionMySecret": hex::encode(secret.to_bytes()) },
+    });
+    let results = vade
+        .didcomm_receive(&serde_json::to_string(&options)?, message)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+
+    Ok(serde_json::from_str(result)?)
+}
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_pick_up_queued_messages() -> Result<(), Box<dyn std::error::Error>> {
+    let mut sender = get_vade_with_memory_storage()?;
+    let mut mediator = get_vade_with_memory_storage()?;
+    let mut recipient = get_vade_with_memory_storage()?;
+    let sender_secret = StaticSecret::new(OsRng);
+    let mediator_secret = StaticSecret::new(OsRng);
+    let recipient_secret = StaticSecret::new(OsRng);
+    let mediator_did = get_did_key(&mediator_secret);
+    let recipient_did = get_did_key(&recipient_secret);
+    let new_message = |protocol: &str, step: &str, thid: Option<&str>, body: serde_json::Value| {
+        let (from, to) = match step {
+            "mediate-grant" | "keylist-update-response" | "status" | "delivery" => {
+                (&mediator_did, &recipient_did)
+            }
+            _ => (&recipient_did, &mediator_did),
+        };
+        serde_json::json!({
+            "type": format!("{}/{}", protocol, step),
+            "from": from,
+            "to": [to],
+            "thid": thid,
+            "body": body,
+        })
+    };
+
+    // messages can only be picked up after mediation has been granted
+    let status_request = new_message(
+        MESSAGE_PICKUP_PROTOCOL_URL,
+        "status-request",
+        None,
+        serde_json::json!({}),
+    );
+    assert!(
+        send(&mut recipient, &recipient_secret, status_request.clone())
+            .await
+            .is_err()
+    );
+
+    // let the mediator route messages for the DID of the recipient
+    let request = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message(
+            COORDINATE_MEDIATION_PROTOCOL_URL,
+            "mediate-request",
+            None,
+            serde_json::json!({}),
+        ),
+    )
+    .await?;
+    let thid = request.message_raw["id"].as_str().ok_or("missing id")?;
+    receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&request.message)?,
+    )
+    .await?;
+    let grant = send(
+        &mut mediator,
+        &mediator_secret,
+        new_message(
+            COORDINATE_MEDIATION_PROTOCOL_URL,
+            "mediate-grant",
+            Some(thid),
+            serde_json::json!({ "routing_did": mediator_did }),
+        ),
+    )
+    .await?;
+    receive(
+        &mut recipient,
+        &recipient_secret,
+        &serde_json::to_string(&grant.message)?,
+    )
+    .await?;
+    let update = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message(
+            COORDINATE_MEDIATION_PROTOCOL_URL,
+            "keylist-update",
+            None,
+            serde_json::json!({ "updates": [{ "recipient_did": recipient_did, "action": "add" }] }),
+        ),
+    )
+    .await?;
+    receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&update.message)?,
+    )
+    .await?;
+
+    // the mediator queues a forwarded message for the recipient
+    let options = serde_json::json!({
+        "encryptionKeys": { "encryptionMySecret": hex::encode(sender_secret.to_bytes()) },
+        "routingKeys": [format!("{}#{}", mediator_did, &mediator_did[8..])],
+    });
+    let payload = serde_json::json!({
+        "type": "https://didcomm.org/trust_ping/1.0/ping",
+        "from": get_did_key(&sender_secret),
+        "to": [recipient_did],
+        "body": {},
+    });
+    let results = sender
+        .didcomm_send(
+            &serde_json::to_string(&options)?,
+            &serde_json::to_string(&payload)?,
+        )
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let sent: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(result)?;
+    let forwarded = receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&sent.message)?,
+    )
+    .await?;
+    assert_eq!(forwarded.metadata.get("queuedFor"), Some(&recipient_did));
+
+    // request the status of the queue
+    let status_request = send(&mut recipient, &recipient_secret, status_request).await?;
+    let thid = status_request.message_raw["id"]
+        .as_str()
+        .ok_or("missing id")?;
+    receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&status_request.message)?,
+    )
+    .await?;
+    let status = send(
+        &mut mediator,
+        &mediator_secret,
+        new_message(
+            MESSAGE_PICKUP_PROTOCOL_URL,
+            "status",
+            Some(thid),
+            serde_json::json!({}),
+        ),
+    )
+    .await?;
+    let received = receive(
+        &mut recipient,
+        &recipient_secret,
+        &serde_json::to_string(&status.message)?,
+    )
+    .await?;
+    let status_body: StatusData = serde_json::from_value(received.message["body"].clone())?;
+    assert_eq!(status_body.message_count, 1);
+    assert_eq!(status_body.live_delivery, Some(false));
+    assert_eq!(
+        received.metadata.get("messageCount"),
+        Some(&"1".to_string())
+    );
+
+    // queued messages are only delivered to an authenticated recipient, anonymous messages only
+    // claim to be from the recipient
+    let results = sender
+        .didcomm_send(
+            r#"{ "packing": "anoncrypt", "skipProtocolHandling": true }"#,
+            &serde_json::to_string(&new_message(
+                MESSAGE_PICKUP_PROTOCOL_URL,
+                "delivery-request",
+                None,
+                serde_json::json!({ "limit": 10 }),
+            ))?,
+        )
+        .await?;
+    let forged: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(
+        results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?,
+    )?;
+    let rejected = receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&forged.message)?,
+    )
+    .await
+    .err()
+    .ok_or("forged delivery request has been accepted")?;
+    assert!(rejected
+        .to_string()
+        .contains("requires an authenticated sender"));
+    // and the mediator can't send them anonymously
+    let rejected = mediator
+        .didcomm_send(
+            r#"{ "packing": "anoncrypt" }"#,
+            &serde_json::to_string(&new_message(
+                MESSAGE_PICKUP_PROTOCOL_URL,
+                "delivery",
+                Some("unknown"),
+                serde_json::json!({}),
+            ))?,
+        )
+        .await
+        .err()
+        .ok_or("anonymous delivery has been sent")?;
+    assert!(rejected
+        .to_string()
+        .contains("requires an authenticated sender"));
+
+    // get the queued message delivered
+    let delivery_request = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message(
+            MESSAGE_PICKUP_PROTOCOL_URL,
+            "delivery-request",
+            None,
+            serde_json::json!({ "limit": 10 }),
+        ),
+    )
+    .await?;
+    let thid = delivery_request.message_raw["id"]
+        .as_str()
+        .ok_or("missing id")?;
+    let received = receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&delivery_request.message)?,
+    )
+    .await?;
+    assert_eq!(
+        received.metadata.get("messageCount"),
+        Some(&"1".to_string())
+    );
+    let delivery = send(
+        &mut mediator,
+        &mediator_secret,
+        new_message(
+            MESSAGE_PICKUP_PROTOCOL_URL,
+            "delivery",
+            Some(thid),
+            serde_json::json!({}),
+        ),
+    )
+    .await?;
+    let received = receive(
+        &mut recipient,
+        &recipient_secret,
+        &serde_json::to_string(&delivery.message)?,
+    )
+    .await?;
+    let delivered: Vec<DeliveredMessage> = serde_json::from_str(
+        received
+            .metadata
+            .get("messages")
+            .ok_or("missing messages")?,
+    )?;
+    assert_eq!(delivered.len(), 1);
+    let ping = receive(&mut recipient, &recipient_secret, &delivered[0].message).await?;
+    assert_eq!(
+        ping.message["type"],
+        "https://didcomm.org/trust_ping/1.0/ping"
+    );
+
+    // acknowledge the delivery, so the message is removed from the queue
+    let messages_received = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message(
+            MESSAGE_PICKUP_PROTOCOL_URL,
+            "messages-received",
+            None,
+            serde_json::json!({ "message_id_list": [delivered[0].id] }),
+        ),
+    )
+    .await?;
+    let received = receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&messages_received.message)?,
+    )
+    .await?;
+    assert_eq!(
+        received.metadata.get("messageCount"),
+        Some(&"0".to_string())
+    );
+
+    // empty queues are answered with a status instead of a delivery
+    let delivery_request = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message(
+            MESSAGE_PICKUP_PROTOCOL_URL,
+            "delivery-request",
+            None,
+            serde_json::json!({ "limit": 10 }),
+        ),
+    )
+    .await?;
+    let thid = delivery_request.message_raw["id"]
+        .as_str()
+        .ok_or("missing id")?;
+    receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&delivery_request.message)?,
+    )
+    .await?;
+    let delivery = new_message(
+        MESSAGE_PICKUP_PROTOCOL_URL,
+        "delivery",
+        Some(thid),
+        serde_json::json!({}),
+    );
+    assert!(send(&mut mediator, &mediator_secret, delivery)
+        .await
+        .is_err());
+    let status = send(
+        &mut mediator,
+        &mediator_secret,
+        new_message(
+            MESSAGE_PICKUP_PROTOCOL_URL,
+            "status",
+            Some(thid),
+            serde_json::json!({}),
+        ),
+    )
+    .await?;
+    let received = receive(
+        &mut recipient,
+        &recipient_secret,
+        &serde_json::to_string(&status.message)?,
+    )
+    .await?;
+    assert_eq!(received.message["body"]["message_count"], 0);
+
+    // enable live delivery
+    let live_delivery_change = send(
+        &mut recipient,
+        &recipient_secret,
+        new_message(
+            MESSAGE_PICKUP_PROTOCOL_URL,
+            "live-delivery-change",
+            None,
+            serde_json::json!({ "live_delivery": true }),
+        ),
+    )
+    .await?;
+    let received = receive(
+        &mut mediator,
+        &mediator_secret,
+        &serde_json::to_string(&live_delivery_change.message)?,
+    )
+    .await?;
+    assert_eq!(
+        received.metadata.get("liveDelivery"),
+        Some(&"true".to_string())
+    );
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+#[cfg(feature = "state_storage")]
+use rand_core::OsRng;
+#[cfg(feature = "state_storage")]
+use serial_test::serial;
+#[cfg(feature = "state_storage")]
+use uuid::Uuid;
+#[cfg(feature = "state_storage")]
+use vade::Vade;
+#[cfg(feature = "state_storage")]
+use vade_didcomm::{
+    datatypes::{InvitationOutput, VadeDidCommPluginReceiveOutput, VadeDidCommPluginSendOutput},
+    db::MemoryStorage,
+    protocols::out_of_band::datatypes::OUT_OF_BAND_PROTOCOL_URL,
+    VadeDidComm,
+    VadeDidCommConfig,
+};
+#[cfg(feature = "state_storage")]
+use x25519_dalek::{PublicKey, StaticSecret};
+
+#[cfg(feature = "state_storage")]
+const DID_EXCHANGE_PROTOCOL_URL: &str = "https://didcomm.org/didexchange/1.0";
+
+#[cfg(feature = "state_storage")]
+fn get_did_key(secret: &StaticSecret) -> String {
+    let mut key = vec![0xec, 0x01];
+    key.extend_from_slice(PublicKey::from(secret).as_bytes());
+    format!("did:key:z{}", bs58::encode(key).into_string())
+}
+
+#[cfg(feature = "state_storage")]
+fn get_vade_with_memory_storage() -> Result<Vade, Box<dyn std::error::Error>> {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        ..Default::default()
+    })?));
+
+    Ok(vade)
+}
+
+#[cfg(feature = "state_storage")]
+async fn run_invitation_function(
+    vade: &mut Vade,
+    function: &str,
+    payload: serde_json::Value,
+) -> Result<InvitationOutput, Box<dyn std::error::Error>> {
+    let results = vade
+        .run_custom_function("{}", function, "{}", &serde_json::to_string(&payload)?)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+
+    Ok(serde_json::from_str(result)?)
+}
+
+#[cfg(feature = "state_storage")]
+async fn send_request(
+    vade: &mut Vade,
+    secret: &StaticSecret,
+    to_secret: &StaticSecret,
+    pthid: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let request = serde_json::json!({
+        "type": format!("{}/request", DID_EXCHANGE_PROTOCOL_URL),
+        "from": get_did_key(secret),
+        "to": [get_did_key(to_secret)],
+        "thid": Uuid::new_v4().to_simple().to_string(),
+        "pthid": pthid,
+        "body": {},
+    });
+    let options = serde_json::json!({
+        "serviceEndpoint": "https://evan.network",
+        "encryptionKeys": {
+            "encryptionMySecret": hex::encode(secret.to_bytes()),
+            "encryptionOthersPublic": hex::encode(PublicKey::from(to_secret).as_bytes()),
+        },
+    });
+    let results = vade
+        .didcomm_send(
+            &serde_json::to_string(&options)?,
+            &serde_json::to_string(&request)?,
+        )
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let prepared: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(result)?;
+
+    Ok(serde_json::to_string(&prepared.message)?)
+}
+
+#[cfg(feature = "state_storage")]
+async fn receive_request(
+    vade: &mut Vade,
+    secret: &StaticSecret,
+    message: &str,
+) -> Result<VadeDidCommPluginReceiveOutput<serde_json::Value>, Box<dyn std::error::Error>> {
+    let options = serde_json::json!({
+        "encryptionKeys": { "encryptionMySecret": hex::encode(secret.to_bytes()) },
+    });
+    let results = vade
+        .didcomm_receive(&serde_json::to_string(&options)?, message)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+
+    Ok(serde_json::from_str(result)?)
+}
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_start_did_exchange_with_single_use_invitation(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut inviter = get_vade_with_memory_storage()?;
+    let mut invitee = get_vade_with_memory_storage()?;
+    let mut other_invitee = get_vade_with_memory_storage()?;
+    let inviter_secret = StaticSecret::new(OsRng);
+    let invitee_secret = StaticSecret::new(OsRng);
+    let other_invitee_secret = StaticSecret::new(OsRng);
+    let inviter_did = get_did_key(&inviter_secret);
+
+    // create an invitation and share it as url
+    let created = run_invitation_function(
+        &mut inviter,
+        "create_invitation",
+        serde_json::json!({
+            "from": inviter_did,
+            "goalCode": "aries.vc.issue",
+            "goal": "issue a credential",
+            "singleUse": true,
+            "baseUrl": "https://example.com/invite",
+        }),
+    )
+    .await?;
+    assert_eq!(
+        created.invitation.r#type,
+        format!("{}/invitation", OUT_OF_BAND_PROTOCOL_URL)
+    );
+    assert_eq!(created.invitation.body.accept, vec!["didcomm/v2"]);
+    assert!(created
+        .invitation_url
+        .starts_with("https://example.com/invite?_oob="));
+
+    // parse the invitation from the url
+    let payload = serde_json::json!({ "invitationUrl": created.invitation_url });
+    let received =
+        run_invitation_function(&mut invitee, "receive_invitation", payload.clone()).await?;
+    assert_eq!(received.invitation.id, created.invitation.id);
+    assert_eq!(received.invitation.from, inviter_did);
+    assert_eq!(
+        received.invitation.body.goal_code,
+        Some("aries.vc.issue".to_string())
+    );
+
+    // the DID exchange has to be started with the inviter of the invitation
+    assert!(send_request(
+        &mut invitee,
+        &invitee_secret,
+        &other_invitee_secret,
+        &received.invitation.id
+    )
+    .await
+    .is_err());
+
+    // the DID exchange references the invitation as parent thread
+    let request = send_request(
+        &mut invitee,
+        &invitee_secret,
+        &inviter_secret,
+        &received.invitation.id,
+    )
+    .await?;
+    let received_request = receive_request(&mut inviter, &inviter_secret, &request).await?;
+    assert_eq!(
+        received_request.message["pthid"],
+        serde_json::json!(created.invitation.id)
+    );
+
+    // single-use invitations can't be used for a second DID exchange
+    run_invitation_function(&mut other_invitee, "receive_invitation", payload).await?;
+    let request = send_request(
+        &mut other_invitee,
+        &other_invitee_secret,
+        &inviter_secret,
+        &created.invitation.id,
+    )
+    .await?;
+    assert!(receive_request(&mut inviter, &inviter_secret, &request)
+        .await
+        .is_err());
+
+    Ok(())
+}
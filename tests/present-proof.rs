@@ -316,14 +316,11 @@ async fn receive_presentation_proposal(
         if #[cfg(feature = "state_storage")] {
             let proposal_data_saved: ProposalData =
                 get_presentation_data(sender, receiver, thid, State::PresentationProposed)?;
-            let attribute_data_saved = proposal_data_saved
-                .presentation_proposal
-                .attribute
-                .ok_or("Attributes not saved in db")?;
-            let attribute_saved = attribute_data_saved
+            let attachment_saved = proposal_data_saved
+                .proposals_attach
                 .get(0)
-                .ok_or("Saved Attribute is invalid")?;
-            assert_eq!(attribute.value, attribute_saved.value);
+                .ok_or("Saved proposal attachment is invalid")?;
+            assert_eq!(received_proposal.proposals_attach[0].id, attachment_saved.id);
         } else {}
     }
 
@@ -632,13 +629,14 @@ async fn send_wrong_ack_state() -> Result<String, Box<dyn std::error::Error>> {
     Ok(complete_message)
 }
 
+// `#[serial]` drops `#[should_panic]` from async tests, so check the error instead of panicking
 #[tokio::test]
-#[should_panic]
 #[serial]
 #[cfg(feature = "state_storage")]
-async fn will_panic_and_fail_to_process_wrong_state() {
+async fn will_fail_to_process_wrong_state() {
     let result = send_wrong_ack_state().await;
-    if let Err(e) = result {
-        panic!("Error : {:?}", e)
+    match result {
+        Err(e) => assert!(e.to_string().contains("not allowed"), "Error : {:?}", e),
+        Ok(_) => panic!("ack in wrong state should not be processed"),
     }
 }
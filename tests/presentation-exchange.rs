@@ -737,7 +737,7 @@ async fn can_do_presentation_exchange_for_presentation_exchange(
         &mut vade,
         &test_setup.user2_did,
         &test_setup.user1_did,
-        &test_setup.sender_options_stringified,
+        &test_setup.receiver_options_stringified,
         &id,
     )
     .await?;
@@ -746,7 +746,7 @@ async fn can_do_presentation_exchange_for_presentation_exchange(
         &test_setup.user2_did,
         &test_setup.user1_did,
         response_message,
-        &test_setup.receiver_options_stringified,
+        &test_setup.sender_options_stringified,
         &id,
     )
     .await?;
@@ -783,7 +783,7 @@ async fn can_do_proposal_exchange_for_presentation_exchange(
         &mut vade,
         &test_setup.user2_did,
         &test_setup.user1_did,
-        &test_setup.sender_options_stringified,
+        &test_setup.receiver_options_stringified,
         &id,
     )
     .await?;
@@ -792,7 +792,7 @@ async fn can_do_proposal_exchange_for_presentation_exchange(
         &test_setup.user2_did,
         &test_setup.user1_did,
         response_message,
-        &test_setup.receiver_options_stringified,
+        &test_setup.sender_options_stringified,
         &id,
     )
     .await?;
@@ -831,7 +831,7 @@ async fn can_do_presentation_exchange_and_fetch_all_messages_from_didcomm_by_thi
         &mut vade,
         &test_setup.user2_did,
         &test_setup.user1_did,
-        &test_setup.sender_options_stringified,
+        &test_setup.receiver_options_stringified,
         &id,
     )
     .await?;
@@ -840,7 +840,7 @@ async fn can_do_presentation_exchange_and_fetch_all_messages_from_didcomm_by_thi
         &test_setup.user2_did,
         &test_setup.user1_did,
         response_message,
-        &test_setup.receiver_options_stringified,
+        &test_setup.sender_options_stringified,
         &id,
     )
     .await?;
@@ -853,7 +853,7 @@ async fn can_do_presentation_exchange_and_fetch_all_messages_from_didcomm_by_thi
     // Total 4 messages should be fetched
     assert!(
         messages.len() == 4,
-        "Invalid message count for thid {} found in rocks db",
+        "Invalid message count for thid {} found in db",
         id
     );
 
@@ -1,23 +1,43 @@
 mod common;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
 
+use async_trait::async_trait;
 use common::get_vade;
 #[cfg(feature = "state_storage")]
 use common::read_db;
 use didcomm_rs::{Attachment, AttachmentData, Jwe};
+use rand_core::OsRng;
 use serde::{Deserialize, Serialize};
 use serial_test::serial;
 use utilities::keypair::get_keypair_set;
 use uuid::Uuid;
-use vade_didcomm::datatypes::{
-    BaseMessage,
-    DidCommOptions,
-    ExtendedMessage,
-    MessageWithBody,
-    VadeDidCommPluginReceiveOutput,
-    VadeDidCommPluginSendOutput,
+use vade::Vade;
+#[cfg(feature = "state_storage")]
+use vade_didcomm::datatypes::Tenant;
+use vade_didcomm::{
+    datatypes::{
+        BaseMessage,
+        DidCommOptions,
+        EncryptionAlgorithm,
+        ExtendedMessage,
+        KeyPair,
+        KeyType,
+        MessageRejection,
+        MessageWithBody,
+        PackingMode,
+        RejectionReason,
+        SigningKeys,
+        VadeDidCommPluginReceiveOutput,
+        VadeDidCommPluginSendOutput,
+    },
+    db::{DidCommStorage, MasterKey, MemoryStorage},
+    resolver::{DidResolver, ResolvedService},
+    ReceivePolicy,
+    VadeDidComm,
+    VadeDidCommConfig,
 };
+use x25519_dalek::{PublicKey, StaticSecret};
 
 const DID_EXCHANGE_PROTOCOL_URL: &str = "https://didcomm.org/didexchange/1.0";
 
@@ -113,6 +133,730 @@ async fn can_decrypt_received_messages() -> Result<(), Box<dyn std::error::Error
     Ok(())
 }
 
+#[tokio::test]
+#[serial]
+async fn can_send_messages_with_packing_modes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = get_vade().await?;
+
+    let sign_keypair = get_keypair_set();
+    let payload = r#"{
+        "type": "https://didcomm.org/trust_ping/1.0/ping",
+        "from": "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+        "to": [ "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG" ],
+        "body": {}
+    }"#;
+
+    for packing in [
+        PackingMode::Anoncrypt,
+        PackingMode::Authcrypt,
+        PackingMode::Signed,
+        PackingMode::Jws,
+        PackingMode::Plaintext,
+    ] {
+        let mut options = sign_keypair.sender_options_stringified.clone();
+        let mut options_object: DidCommOptions = serde_json::from_str(&options)?;
+        options_object.packing = Some(packing);
+        options = serde_json::to_string(&options_object)?;
+
+        let results = vade.didcomm_send(&options, payload).await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let sent: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(result)?;
+        let sent_message = serde_json::to_string(&sent.message)?;
+        if packing == PackingMode::Anoncrypt {
+            let jwe: Jwe = serde_json::from_str(&sent_message)?;
+            assert!(jwe.protected.and_then(|header| header.skid).is_none());
+        }
+
+        let results = vade
+            .didcomm_receive(&sign_keypair.receiver_options_stringified, &sent_message)
+            .await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let received: VadeDidCommPluginReceiveOutput<MessageWithBody<PingBody>> =
+            serde_json::from_str(result)?;
+        assert_eq!(
+            "https://didcomm.org/trust_ping/1.0/ping",
+            received.message.r#type,
+        );
+        assert_eq!(received.metadata.get("packing"), Some(&packing.to_string()));
+        // only the sender of encrypted messages with its own key can be authenticated
+        let sender_authenticated = matches!(packing, PackingMode::Authcrypt | PackingMode::Signed);
+        assert_eq!(
+            received.metadata.get("senderAuthenticated"),
+            Some(&sender_authenticated.to_string()),
+        );
+
+        if matches!(packing, PackingMode::Signed | PackingMode::Jws) {
+            // the receiver passed the key of the expected signer
+            let signer = hex::encode(sign_keypair.sign_keypair.public.to_bytes());
+            assert_eq!(received.metadata.get("signer"), Some(&signer));
+            assert_eq!(
+                received.metadata.get("signerAuthenticated"),
+                Some(&"true".to_string()),
+            );
+        }
+        if packing == PackingMode::Jws {
+            let signer = hex::encode(sign_keypair.sign_keypair.public.to_bytes());
+
+            // third parties can verify signed messages without any keys
+            let mut third_party = Vade::new();
+            third_party.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+                storage: Some(Box::new(MemoryStorage::new())),
+                ..Default::default()
+            })?));
+            let results = third_party.didcomm_receive("{}", &sent_message).await?;
+            let result = results
+                .get(0)
+                .ok_or("no result")?
+                .as_ref()
+                .ok_or("no value in result")?;
+            let received: VadeDidCommPluginReceiveOutput<serde_json::Value> =
+                serde_json::from_str(result)?;
+            assert_eq!(received.metadata.get("signer"), Some(&signer));
+            // but the key in the kid is only claimed and does not belong to the `from` DID
+            assert_eq!(
+                received.metadata.get("signerAuthenticated"),
+                Some(&"false".to_string()),
+            );
+
+            // signers are authenticated, if their key belongs to the `from` DID
+            let signer_did = format!(
+                "did:key:z{}",
+                bs58::encode(
+                    [
+                        &[0xed, 0x01],
+                        sign_keypair.sign_keypair.public.as_bytes().as_slice()
+                    ]
+                    .concat()
+                )
+                .into_string(),
+            );
+            let results = vade
+                .didcomm_send(
+                    &options,
+                    &payload.replace(
+                        "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                        &signer_did,
+                    ),
+                )
+                .await?;
+            let result = results
+                .get(0)
+                .ok_or("no result")?
+                .as_ref()
+                .ok_or("no value in result")?;
+            let sent: VadeDidCommPluginSendOutput<serde_json::Value> =
+                serde_json::from_str(result)?;
+            let results = third_party
+                .didcomm_receive("{}", &serde_json::to_string(&sent.message)?)
+                .await?;
+            let result = results
+                .get(0)
+                .ok_or("no result")?
+                .as_ref()
+                .ok_or("no value in result")?;
+            let received: VadeDidCommPluginReceiveOutput<serde_json::Value> =
+                serde_json::from_str(result)?;
+            assert_eq!(
+                received.metadata.get("signerAuthenticated"),
+                Some(&"true".to_string()),
+            );
+            assert_eq!(
+                received.metadata.get("senderAuthenticated"),
+                Some(&"true".to_string()),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn can_reject_forged_senders_of_anoncrypt_messages() -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut vade = get_vade().await?;
+    let mut attacker = Vade::new();
+    attacker.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        ..Default::default()
+    })?));
+
+    let sign_keypair = get_keypair_set();
+    // the attacker does not know the keys of the `from` DID, but anoncrypt does not need them
+    let get_message = |r#type: &str| {
+        format!(
+            r#"{{
+                "type": "{}",
+                "from": "{}",
+                "to": [ "{}" ],
+                "body": {{ "queries": [] }}
+            }}"#,
+            r#type, sign_keypair.user1_did, sign_keypair.user2_did,
+        )
+    };
+    let mut received = Vec::new();
+    for r#type in [
+        "https://didcomm.org/trust_ping/1.0/ping",
+        "https://didcomm.org/coordinate-mediation/2.0/keylist-query",
+    ] {
+        let results = attacker
+            .didcomm_send(
+                r#"{ "packing": "anoncrypt", "skipProtocolHandling": true }"#,
+                &get_message(r#type),
+            )
+            .await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let sent: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(result)?;
+
+        received.push(
+            vade.didcomm_receive(
+                &sign_keypair.receiver_options_stringified,
+                &serde_json::to_string(&sent.message)?,
+            )
+            .await,
+        );
+    }
+
+    // stateless steps are handled, but the sender is marked as not authenticated
+    let results = received.remove(0)?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let ping: VadeDidCommPluginReceiveOutput<serde_json::Value> = serde_json::from_str(result)?;
+    assert_eq!(
+        ping.metadata.get("senderAuthenticated"),
+        Some(&"false".to_string())
+    );
+    // steps, that store data for the sender, are rejected
+    let rejected = received
+        .remove(0)
+        .err()
+        .ok_or("forged sender has been accepted")?;
+    assert!(rejected
+        .to_string()
+        .contains("requires an authenticated sender"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn can_send_messages_with_encryption_algorithms() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = get_vade().await?;
+
+    let sign_keypair = get_keypair_set();
+    let payload = r#"{
+        "type": "https://didcomm.org/trust_ping/1.0/ping",
+        "from": "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+        "to": [ "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG" ],
+        "body": {}
+    }"#;
+
+    for encryption_algorithm in [
+        EncryptionAlgorithm::Xc20p,
+        EncryptionAlgorithm::A256Gcm,
+        EncryptionAlgorithm::A256CbcHs512,
+    ] {
+        let mut options_object: DidCommOptions =
+            serde_json::from_str(&sign_keypair.sender_options_stringified)?;
+        options_object.encryption_algorithm = Some(encryption_algorithm);
+        let options = serde_json::to_string(&options_object)?;
+
+        let results = vade.didcomm_send(&options, payload).await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let sent: VadeDidCommPluginSendOutput<Jwe> = serde_json::from_str(result)?;
+
+        let results = vade
+            .didcomm_receive(
+                &sign_keypair.receiver_options_stringified,
+                &serde_json::to_string(&sent.message)?,
+            )
+            .await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let received: VadeDidCommPluginReceiveOutput<serde_json::Value> =
+            serde_json::from_str(result)?;
+        assert_eq!(
+            received.metadata.get("encryptionAlgorithm"),
+            Some(&encryption_algorithm.to_string()),
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn can_resolve_keys_of_did_key_dids() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = get_vade().await?;
+
+    let sender_secret = StaticSecret::new(OsRng);
+    let receiver_secret = StaticSecret::new(OsRng);
+    let to_did_key = |secret: &StaticSecret| {
+        let mut key = vec![0xec, 0x01];
+        key.extend_from_slice(PublicKey::from(secret).as_bytes());
+        format!("did:key:z{}", bs58::encode(key).into_string())
+    };
+    let payload = format!(
+        r#"{{
+            "type": "https://didcomm.org/trust_ping/1.0/ping",
+            "from": "{}",
+            "to": [ "{}" ],
+            "body": {{}}
+        }}"#,
+        to_did_key(&sender_secret),
+        to_did_key(&receiver_secret),
+    );
+    let receiver_options = format!(
+        r#"{{ "encryptionKeys": {{ "encryptionMySecret": "{}" }} }}"#,
+        hex::encode(receiver_secret.to_bytes()),
+    );
+
+    // only the own secret is passed, public keys are resolved from the DIDs
+    for (options, packing) in [
+        (
+            format!(
+                r#"{{ "encryptionKeys": {{ "encryptionMySecret": "{}" }} }}"#,
+                hex::encode(sender_secret.to_bytes()),
+            ),
+            PackingMode::Authcrypt,
+        ),
+        (
+            r#"{ "packing": "anoncrypt" }"#.to_string(),
+            PackingMode::Anoncrypt,
+        ),
+    ] {
+        let results = vade.didcomm_send(&options, &payload).await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let sent: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(result)?;
+
+        let results = vade
+            .didcomm_receive(&receiver_options, &serde_json::to_string(&sent.message)?)
+            .await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let received: VadeDidCommPluginReceiveOutput<serde_json::Value> =
+            serde_json::from_str(result)?;
+        assert_eq!(received.metadata.get("packing"), Some(&packing.to_string()));
+    }
+
+    Ok(())
+}
+
+struct TestResolver {
+    documents: HashMap<String, String>,
+}
+
+#[async_trait(?Send)]
+impl DidResolver for TestResolver {
+    async fn resolve_did_document(
+        &mut self,
+        did: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self.documents.get(did).cloned())
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn can_resolve_keys_and_services_with_resolver() -> Result<(), Box<dyn std::error::Error>> {
+    let sender_secret = StaticSecret::new(OsRng);
+    let receiver_secret = StaticSecret::new(OsRng);
+    let get_document = |did: &str, secret: &StaticSecret| {
+        format!(
+            r#"{{
+                "id": "{did}",
+                "verificationMethod": [{{
+                    "id": "{did}#key-1",
+                    "type": "X25519KeyAgreementKey2019",
+                    "controller": "{did}",
+                    "publicKeyBase58": "{}"
+                }}],
+                "keyAgreement": [ "{did}#key-1" ],
+                "service": [{{
+                    "id": "{did}#didcomm",
+                    "type": "DIDCommMessaging",
+                    "serviceEndpoint": {{ "uri": "https://example.com/{did}" }}
+                }}]
+            }}"#,
+            bs58::encode(PublicKey::from(secret).as_bytes()).into_string(),
+        )
+    };
+    let mut documents = HashMap::new();
+    documents.insert(
+        "did:example:sender".to_string(),
+        get_document("did:example:sender", &sender_secret),
+    );
+    documents.insert(
+        "did:example:receiver".to_string(),
+        get_document("did:example:receiver", &receiver_secret),
+    );
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        resolver: Some(Box::new(TestResolver { documents })),
+        ..Default::default()
+    })?));
+
+    let payload = r#"{
+        "type": "https://didcomm.org/trust_ping/1.0/ping",
+        "from": "did:example:sender",
+        "to": [ "did:example:receiver" ],
+        "body": {}
+    }"#;
+    let options = format!(
+        r#"{{ "encryptionKeys": {{ "encryptionMySecret": "{}" }} }}"#,
+        hex::encode(sender_secret.to_bytes()),
+    );
+    let results = vade.didcomm_send(&options, payload).await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let sent: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(result)?;
+    let services: Vec<ResolvedService> =
+        serde_json::from_str(sent.metadata.get("services").ok_or("no services")?)?;
+    assert_eq!(services.len(), 1);
+    assert_eq!(services[0].uri, "https://example.com/did:example:receiver");
+
+    // the key of the sender is resolved from the `skid` of the message
+    let receiver_options = format!(
+        r#"{{ "encryptionKeys": {{ "encryptionMySecret": "{}" }} }}"#,
+        hex::encode(receiver_secret.to_bytes()),
+    );
+    let results = vade
+        .didcomm_receive(&receiver_options, &serde_json::to_string(&sent.message)?)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let received: VadeDidCommPluginReceiveOutput<serde_json::Value> = serde_json::from_str(result)?;
+    assert_eq!(received.message["from"], "did:example:sender");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn can_forward_messages_over_mediators() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = get_vade().await?;
+
+    let sender_secret = StaticSecret::new(OsRng);
+    let receiver_secret = StaticSecret::new(OsRng);
+    let mediator_secrets = [StaticSecret::new(OsRng), StaticSecret::new(OsRng)];
+    let to_did_key = |secret: &StaticSecret| {
+        let mut key = vec![0xec, 0x01];
+        key.extend_from_slice(PublicKey::from(secret).as_bytes());
+        format!("did:key:z{}", bs58::encode(key).into_string())
+    };
+    let routing_keys: Vec<String> = mediator_secrets
+        .iter()
+        .map(|secret| {
+            let did = to_did_key(secret);
+            format!("{}#{}", did, &did[8..])
+        })
+        .collect();
+    let payload = format!(
+        r#"{{
+            "type": "https://didcomm.org/trust_ping/1.0/ping",
+            "from": "{}",
+            "to": [ "{}" ],
+            "body": {{}}
+        }}"#,
+        to_did_key(&sender_secret),
+        to_did_key(&receiver_secret),
+    );
+    let options = serde_json::json!({
+        "encryptionKeys": { "encryptionMySecret": hex::encode(sender_secret.to_bytes()) },
+        "routingKeys": routing_keys,
+    });
+    let results = vade
+        .didcomm_send(&serde_json::to_string(&options)?, &payload)
+        .await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let sent: VadeDidCommPluginSendOutput<serde_json::Value> = serde_json::from_str(result)?;
+
+    // each mediator unwraps one forward message, starting with the one of the first routing key
+    let mut message = serde_json::to_string(&sent.message)?;
+    let next_receivers = [routing_keys[1].clone(), to_did_key(&receiver_secret)];
+    for (mediator_secret, next_receiver) in mediator_secrets.iter().zip(next_receivers) {
+        let mediator_options = format!(
+            r#"{{ "encryptionKeys": {{ "encryptionMySecret": "{}" }} }}"#,
+            hex::encode(mediator_secret.to_bytes()),
+        );
+        let results = vade.didcomm_receive(&mediator_options, &message).await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let received: VadeDidCommPluginReceiveOutput<serde_json::Value> =
+            serde_json::from_str(result)?;
+        assert_eq!(
+            received.message["type"],
+            "https://didcomm.org/routing/2.0/forward"
+        );
+        assert_eq!(
+            received.metadata.get("packing"),
+            Some(&PackingMode::Anoncrypt.to_string())
+        );
+        assert_eq!(received.metadata.get("next"), Some(&next_receiver));
+        message = received
+            .metadata
+            .get("forwardedMessage")
+            .ok_or("no forwarded message")?
+            .to_owned();
+    }
+
+    // the receiver gets the message as sent by the sender
+    let receiver_options = format!(
+        r#"{{ "encryptionKeys": {{ "encryptionMySecret": "{}" }} }}"#,
+        hex::encode(receiver_secret.to_bytes()),
+    );
+    let results = vade.didcomm_receive(&receiver_options, &message).await?;
+    let result = results
+        .get(0)
+        .ok_or("no result")?
+        .as_ref()
+        .ok_or("no value in result")?;
+    let received: VadeDidCommPluginReceiveOutput<serde_json::Value> = serde_json::from_str(result)?;
+    assert_eq!(
+        received.message["type"],
+        "https://didcomm.org/trust_ping/1.0/ping"
+    );
+    assert_eq!(
+        received.metadata.get("packing"),
+        Some(&PackingMode::Authcrypt.to_string())
+    );
+
+    // forward messages have to be encrypted
+    let options = serde_json::json!({ "packing": "plaintext", "routingKeys": routing_keys });
+    assert!(vade
+        .didcomm_send(&serde_json::to_string(&options)?, &payload)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn can_send_messages_signed_with_ecdsa_keys() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = get_vade().await?;
+
+    let sign_keypair = get_keypair_set();
+    let payload = r#"{
+        "type": "https://didcomm.org/trust_ping/1.0/ping",
+        "from": "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+        "to": [ "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG" ],
+        "body": {}
+    }"#;
+
+    for key_type in [KeyType::Secp256k1, KeyType::P256] {
+        let results = vade
+            .run_custom_function(
+                "{}",
+                "create_keys",
+                "{}",
+                &format!(r#"{{ "keyType": "{}" }}"#, key_type),
+            )
+            .await?;
+        let result = results
+            .get(0)
+            .ok_or("no result")?
+            .as_ref()
+            .ok_or("no value in result")?;
+        let key_pair: KeyPair = serde_json::from_str(result)?;
+        assert_eq!(key_pair.key_type, key_type);
+
+        for packing in [PackingMode::Signed, PackingMode::Jws] {
+            let mut sender_options: DidCommOptions =
+                serde_json::from_str(&sign_keypair.sender_options_stringified)?;
+            sender_options.packing = Some(packing);
+            sender_options.signing_keys = Some(SigningKeys {
+                signing_my_secret: Some(key_pair.secret),
+                signing_others_public: None,
+                signing_key_type: Some(key_type),
+            });
+            let mut receiver_options: DidCommOptions =
+                serde_json::from_str(&sign_keypair.receiver_options_stringified)?;
+            receiver_options.signing_keys = Some(SigningKeys {
+                signing_my_secret: None,
+                signing_others_public: Some(key_pair.public.clone()),
+                signing_key_type: Some(key_type),
+            });
+
+            let results = vade
+                .didcomm_send(&serde_json::to_string(&sender_options)?, payload)
+                .await?;
+            let result = results
+                .get(0)
+                .ok_or("no result")?
+                .as_ref()
+                .ok_or("no value in result")?;
+            let sent: VadeDidCommPluginSendOutput<serde_json::Value> =
+                serde_json::from_str(result)?;
+
+            let results = vade
+                .didcomm_receive(
+                    &serde_json::to_string(&receiver_options)?,
+                    &serde_json::to_string(&sent.message)?,
+                )
+                .await?;
+            let result = results
+                .get(0)
+                .ok_or("no result")?
+                .as_ref()
+                .ok_or("no value in result")?;
+            let received: VadeDidCommPluginReceiveOutput<serde_json::Value> =
+                serde_json::from_str(result)?;
+            assert_eq!(received.metadata.get("packing"), Some(&packing.to_string()));
+        }
+
+        // key agreement is only supported with X25519 keys
+        for packing in [PackingMode::Anoncrypt, PackingMode::Authcrypt] {
+            let mut sender_options: DidCommOptions =
+                serde_json::from_str(&sign_keypair.sender_options_stringified)?;
+            sender_options.packing = Some(packing);
+            if let Some(encryption_keys) = sender_options.encryption_keys.as_mut() {
+                encryption_keys.encryption_key_type = Some(key_type);
+            }
+            let rejected = vade
+                .didcomm_send(&serde_json::to_string(&sender_options)?, payload)
+                .await
+                .err()
+                .ok_or("key agreement key type has been accepted")?;
+            assert!(rejected
+                .to_string()
+                .contains("is not supported for key agreement"));
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn can_reject_expired_and_replayed_messages() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        ..Default::default()
+    })?));
+    let options = r#"{ "skipProtocolHandling": true }"#;
+    let get_message = |timestamps: &str| {
+        format!(
+            r#"{{
+                "id": "{}",
+                "type": "https://didcomm.org/trust_ping/1.0/ping",
+                "from": "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                "to": [ "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG" ],
+                "body": {{}}{}
+            }}"#,
+            Uuid::new_v4(),
+            timestamps,
+        )
+    };
+    let get_rejection_reason = |error: Box<dyn std::error::Error>| {
+        error
+            .downcast_ref::<MessageRejection>()
+            .map(|rejection| rejection.reason)
+    };
+
+    let expired = get_message(r#", "expires_time": 1"#);
+    let error = vade.didcomm_receive(options, &expired).await.err();
+    assert_eq!(
+        error.and_then(get_rejection_reason),
+        Some(RejectionReason::Expired),
+    );
+
+    let created_in_future = get_message(r#", "created_time": 99999999999"#);
+    let error = vade
+        .didcomm_receive(options, &created_in_future)
+        .await
+        .err();
+    assert_eq!(
+        error.and_then(get_rejection_reason),
+        Some(RejectionReason::CreatedInFuture),
+    );
+
+    let message = get_message("");
+    vade.didcomm_receive(options, &message).await?;
+    let replayed = vade.didcomm_receive(options, &message).await;
+    if cfg!(feature = "state_storage") {
+        assert_eq!(
+            replayed.err().and_then(get_rejection_reason),
+            Some(RejectionReason::Replayed),
+        );
+
+        // received message ids are kept, if the thread of the message is purged
+        let id = serde_json::from_str::<serde_json::Value>(&message)?["id"]
+            .as_str()
+            .ok_or("missing id")?
+            .to_owned();
+        vade.run_custom_function("{}", "purge_thread", "{}", &id)
+            .await?;
+        let replayed = vade.didcomm_receive(options, &message).await;
+        assert_eq!(
+            replayed.err().and_then(get_rejection_reason),
+            Some(RejectionReason::Replayed),
+        );
+    } else {
+        // received message ids can only be stored with `state_storage`
+        assert!(replayed.is_ok());
+    }
+
+    // replays are accepted, if disabled in the receive policy
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        receive_policy: Some(ReceivePolicy {
+            reject_replays: false,
+            ..Default::default()
+        }),
+        ..Default::default()
+    })?));
+    vade.didcomm_receive(options, &message).await?;
+    vade.didcomm_receive(options, &message).await?;
+
+    Ok(())
+}
+
 #[tokio::test]
 #[serial]
 async fn can_send_attachments_and_decrypt_received_messages(
@@ -150,6 +894,7 @@ async fn can_send_attachments_and_decrypt_received_messages(
         thid: None,
         body: None,
         attachments: attachment.clone(),
+        from_prior: None,
         other: HashMap::new(),
     };
     let payload = serde_json::to_string(&payload)?;
@@ -433,7 +1178,7 @@ async fn can_prepare_unencrypted_didcomm_messages() -> Result<(), Box<dyn std::e
 #[tokio::test]
 #[serial]
 #[cfg(feature = "state_storage")]
-async fn should_store_messages_in_rocks_db() -> Result<(), Box<dyn std::error::Error>> {
+async fn should_store_messages_in_db() -> Result<(), Box<dyn std::error::Error>> {
     let mut vade = get_vade().await?;
 
     let sign_keypair = get_keypair_set();
@@ -499,3 +1244,213 @@ async fn should_store_messages_in_rocks_db() -> Result<(), Box<dyn std::error::E
 
     Ok(())
 }
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_separate_messages_of_tenants() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = get_vade().await?;
+    let tenant_1 = Uuid::new_v4().to_simple().to_string();
+    let tenant_2 = Uuid::new_v4().to_simple().to_string();
+    vade.run_custom_function("{}", "create_tenant", "{}", &tenant_1)
+        .await?;
+    vade.run_custom_function("{}", "create_tenant", "{}", &tenant_2)
+        .await?;
+
+    let id = Uuid::new_v4().to_simple().to_string();
+    let sign_keypair = get_keypair_set();
+    let payload = format!(
+        r#"{{
+            "type": "https://didcomm.org/trust_ping/1.0/ping",
+            "from": "{}",
+            "to": ["{}"],
+            "thid": "{}",
+            "body": {{}}
+        }}"#,
+        &sign_keypair.user1_did, &sign_keypair.user2_did, id,
+    );
+    let mut options_object: DidCommOptions =
+        serde_json::from_str(&sign_keypair.sender_options_stringified)?;
+    options_object.tenant_id = Some(tenant_1.clone());
+    vade.didcomm_send(&serde_json::to_string(&options_object)?, &payload)
+        .await?;
+
+    let query = format!("message_{}_*", id);
+    let tenant_options = |tenant_id: &str| format!(r#"{{ "tenantId": "{}" }}"#, tenant_id);
+    let results = vade
+        .run_custom_function(
+            "{}",
+            "query_didcomm_messages",
+            &tenant_options(&tenant_1),
+            &query,
+        )
+        .await?;
+    let messages: Vec<String> =
+        serde_json::from_str(results[0].as_ref().ok_or("no value in result")?)?;
+    assert_eq!(messages.len(), 1);
+
+    let results = vade
+        .run_custom_function(
+            "{}",
+            "query_didcomm_messages",
+            &tenant_options(&tenant_2),
+            &query,
+        )
+        .await?;
+    let messages: Vec<String> =
+        serde_json::from_str(results[0].as_ref().ok_or("no value in result")?)?;
+    assert_eq!(messages.len(), 0);
+
+    let results = vade
+        .run_custom_function("{}", "list_tenants", "{}", "")
+        .await?;
+    let tenants: Vec<Tenant> =
+        serde_json::from_str(results[0].as_ref().ok_or("no value in result")?)?;
+    assert!(tenants.iter().any(|tenant| tenant.tenant_id == tenant_1));
+
+    vade.run_custom_function("{}", "delete_tenant", "{}", &tenant_1)
+        .await?;
+    vade.run_custom_function("{}", "delete_tenant", "{}", &tenant_2)
+        .await?;
+    let result = vade
+        .run_custom_function(
+            "{}",
+            "query_didcomm_messages",
+            &tenant_options(&tenant_1),
+            &query,
+        )
+        .await;
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+#[cfg(feature = "state_storage")]
+async fn can_export_and_import_wallet() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vade = get_vade().await?;
+    let source_tenant = Uuid::new_v4().to_simple().to_string();
+    let target_tenant = Uuid::new_v4().to_simple().to_string();
+    let tenant_options = |tenant_id: &str| format!(r#"{{ "tenantId": "{}" }}"#, tenant_id);
+    vade.run_custom_function("{}", "create_tenant", "{}", &source_tenant)
+        .await?;
+    vade.run_custom_function("{}", "create_tenant", "{}", &target_tenant)
+        .await?;
+
+    let id = Uuid::new_v4().to_simple().to_string();
+    let sign_keypair = get_keypair_set();
+    let payload = format!(
+        r#"{{
+            "type": "https://didcomm.org/trust_ping/1.0/ping",
+            "from": "{}",
+            "to": ["{}"],
+            "thid": "{}",
+            "body": {{}}
+        }}"#,
+        &sign_keypair.user1_did, &sign_keypair.user2_did, id,
+    );
+    let mut options_object: DidCommOptions =
+        serde_json::from_str(&sign_keypair.sender_options_stringified)?;
+    options_object.tenant_id = Some(source_tenant.clone());
+    vade.didcomm_send(&serde_json::to_string(&options_object)?, &payload)
+        .await?;
+
+    let results = vade
+        .run_custom_function(
+            "{}",
+            "export_wallet",
+            &tenant_options(&source_tenant),
+            r#"{ "password": "secret" }"#,
+        )
+        .await?;
+    let archive: serde_json::Value =
+        serde_json::from_str(results[0].as_ref().ok_or("no value in result")?)?;
+    assert_eq!(archive["version"], 1);
+
+    let import_payload = serde_json::json!({ "password": "secret", "archive": archive });
+    vade.run_custom_function(
+        "{}",
+        "import_wallet",
+        &tenant_options(&target_tenant),
+        &import_payload.to_string(),
+    )
+    .await?;
+
+    let results = vade
+        .run_custom_function(
+            "{}",
+            "query_didcomm_messages",
+            &tenant_options(&target_tenant),
+            &format!("message_{}_*", id),
+        )
+        .await?;
+    let messages: Vec<String> =
+        serde_json::from_str(results[0].as_ref().ok_or("no value in result")?)?;
+    assert_eq!(messages.len(), 1);
+
+    vade.run_custom_function("{}", "delete_tenant", "{}", &source_tenant)
+        .await?;
+    vade.run_custom_function("{}", "delete_tenant", "{}", &target_tenant)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_rotate_master_key_of_plugin() -> Result<(), Box<dyn std::error::Error>> {
+    let storage = Rc::new(MemoryStorage::new());
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(Rc::clone(&storage))),
+        master_key: Some(MasterKey::new("key1", [1; 32])?),
+        ..Default::default()
+    })?));
+    let tenant_id = Uuid::new_v4().to_simple().to_string();
+    vade.run_custom_function("{}", "create_tenant", "{}", &tenant_id)
+        .await?;
+    let stored = storage.scan_prefix("")?;
+    assert!(!stored.is_empty());
+    assert!(stored
+        .iter()
+        .all(|(_, value)| value.starts_with("enc:key1:")));
+
+    let payload = format!(
+        r#"{{ "keyId": "key2", "key": "{}" }}"#,
+        hex::encode([2u8; 32])
+    );
+    let results = vade
+        .run_custom_function("{}", "rotate_master_key", "{}", &payload)
+        .await?;
+    assert_eq!(
+        results[0].as_deref(),
+        Some(stored.len().to_string().as_str())
+    );
+    assert!(storage
+        .scan_prefix("")?
+        .iter()
+        .all(|(_, value)| value.starts_with("enc:key2:")));
+
+    // values written before the rotation can still be read
+    let results = vade
+        .run_custom_function("{}", "list_tenants", "{}", "")
+        .await?;
+    let tenants: Vec<serde_json::Value> =
+        serde_json::from_str(results[0].as_ref().ok_or("no value in result")?)?;
+    assert!(tenants
+        .iter()
+        .any(|tenant| tenant["tenantId"] == tenant_id.as_str()));
+
+    // plugins without master key can not rotate it
+    let mut unencrypted = Vade::new();
+    unencrypted.register_plugin(Box::from(VadeDidComm::new(VadeDidCommConfig {
+        storage: Some(Box::new(MemoryStorage::new())),
+        ..Default::default()
+    })?));
+    assert!(unencrypted
+        .run_custom_function("{}", "rotate_master_key", "{}", &payload)
+        .await
+        .is_err());
+
+    Ok(())
+}
@@ -45,62 +45,70 @@ pub fn get_keypair_set() -> KeyPairSet {
     let sign_keypair: ed25519_dalek::Keypair = ed25519_dalek::Keypair::generate(&mut OsRng);
     let sign_keypair2: ed25519_dalek::Keypair = ed25519_dalek::Keypair::generate(&mut OsRng);
 
-    let sender_options: DidCommOptions = DidCommOptions {
+    let sender_options = DidCommOptions {
         encryption_keys: Some(EncryptionKeys {
             encryption_my_secret: alice_secret_key.to_bytes(),
             encryption_others_public: Some(bob_public.to_bytes()),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
         }),
         signing_keys: Some(SigningKeys {
             signing_my_secret: Some(sign_keypair.secret.to_bytes()),
-            signing_others_public: Some(sign_keypair2.public.to_bytes()),
+            signing_others_public: Some(sign_keypair2.public.to_bytes().to_vec()),
+            signing_key_type: None,
         }),
-        skip_message_packaging: Some(false),
-        skip_protocol_handling: Some(false),
+        ..Default::default()
     };
     let sender_options_stringified =
         serde_json::to_string(&sender_options).unwrap_or_else(|_| "{}".to_string());
 
-    let sender_signing_options: DidCommOptions = DidCommOptions {
+    let sender_signing_options = DidCommOptions {
         encryption_keys: Some(EncryptionKeys {
             encryption_my_secret: alice_secret_key.to_bytes(),
             encryption_others_public: Some(bob_public.to_bytes()),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
         }),
         signing_keys: Some(SigningKeys {
             signing_my_secret: Some(sign_keypair.secret.to_bytes()),
-            signing_others_public: Some(sign_keypair2.public.to_bytes()),
+            signing_others_public: Some(sign_keypair2.public.to_bytes().to_vec()),
+            signing_key_type: None,
         }),
-        skip_message_packaging: Some(false),
-        skip_protocol_handling: Some(false),
+        ..Default::default()
     };
     let sender_signing_options_stringified =
         serde_json::to_string(&sender_signing_options).unwrap_or_else(|_| "{}".to_string());
 
-    let receiver_options: DidCommOptions = DidCommOptions {
+    let receiver_options = DidCommOptions {
         encryption_keys: Some(EncryptionKeys {
             encryption_my_secret: bob_secret_key.to_bytes(),
             encryption_others_public: Some(alice_public.to_bytes()),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
         }),
         signing_keys: Some(SigningKeys {
             signing_my_secret: Some(sign_keypair2.secret.to_bytes()),
-            signing_others_public: Some(sign_keypair.public.to_bytes()),
+            signing_others_public: Some(sign_keypair.public.to_bytes().to_vec()),
+            signing_key_type: None,
         }),
-        skip_message_packaging: Some(false),
-        skip_protocol_handling: Some(false),
+        ..Default::default()
     };
     let receiver_options_stringified =
         serde_json::to_string(&receiver_options).unwrap_or_else(|_| "{}".to_string());
 
-    let receiver_signing_options: DidCommOptions = DidCommOptions {
+    let receiver_signing_options = DidCommOptions {
         encryption_keys: Some(EncryptionKeys {
             encryption_my_secret: bob_secret_key.to_bytes(),
             encryption_others_public: Some(alice_public.to_bytes()),
+            encryption_others_public_keys: Vec::new(),
+            encryption_key_type: None,
         }),
         signing_keys: Some(SigningKeys {
             signing_my_secret: Some(sign_keypair2.secret.to_bytes()),
-            signing_others_public: Some(sign_keypair.public.to_bytes()),
+            signing_others_public: Some(sign_keypair.public.to_bytes().to_vec()),
+            signing_key_type: None,
         }),
-        skip_message_packaging: Some(false),
-        skip_protocol_handling: Some(false),
+        ..Default::default()
     };
     let receiver_signing_options_stringified =
         serde_json::to_string(&receiver_signing_options).unwrap_or_else(|_| "{}".to_string());
@@ -4,3 +4,4 @@ extern crate k256;
 extern crate x25519_dalek;
 
 pub mod keypair;
+pub mod redis_stand_in;
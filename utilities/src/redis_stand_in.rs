@@ -0,0 +1,164 @@
+//! Minimal in process stand-in for a redis server, that supports the commands used by the redis
+//! storage of vade-didcomm (`PING`, `GET`, `SET`, `DEL`, `MGET`, `SCAN` and `MULTI`/`EXEC`
+//! transactions). Allows to test the redis storage without a running redis server.
+
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+type Entries = Arc<Mutex<BTreeMap<String, String>>>;
+
+/// Starts a new redis stand-in with empty data, listening on a random local port.
+///
+/// # Returns
+/// * `String` - redis url to connect to the stand-in
+pub fn start_redis_stand_in() -> Result<String, Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("redis://{}/", listener.local_addr()?);
+    let entries: Entries = Arc::new(Mutex::new(BTreeMap::new()));
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let entries = entries.clone();
+            thread::spawn(move || {
+                // connection errors only end the current connection
+                let _ = handle_connection(stream, entries);
+            });
+        }
+    });
+
+    Ok(url)
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    entries: Entries,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    // commands queued between `MULTI` and `EXEC`
+    let mut transaction: Option<Vec<Vec<String>>> = None;
+    while let Some(command) = read_command(&mut reader)? {
+        let response = match (command[0].to_uppercase().as_str(), transaction.as_mut()) {
+            ("MULTI", None) => {
+                transaction = Some(Vec::new());
+                "+OK\r\n".to_string()
+            }
+            ("EXEC", Some(_)) => {
+                let queued = transaction.take().unwrap_or_default();
+                // execute all commands with a single lock, so they are applied atomically
+                let mut entries = entries.lock().expect("could not lock stand-in entries");
+                array(
+                    queued
+                        .iter()
+                        .map(|command| execute(command, &mut entries))
+                        .collect(),
+                )
+            }
+            ("MULTI", Some(_)) => "-ERR MULTI calls can not be nested\r\n".to_string(),
+            ("EXEC", None) => "-ERR EXEC without MULTI\r\n".to_string(),
+            (_, Some(queued)) => {
+                queued.push(command);
+                "+QUEUED\r\n".to_string()
+            }
+            (_, None) => execute(
+                &command,
+                &mut entries.lock().expect("could not lock stand-in entries"),
+            ),
+        };
+        writer.write_all(response.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn read_line(reader: &mut impl BufRead) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches("\r\n").to_string()))
+}
+
+fn read_command(
+    reader: &mut impl BufRead,
+) -> Result<Option<Vec<String>>, Box<dyn std::error::Error>> {
+    let header = match read_line(reader)? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let count: usize = header.trim_start_matches('*').parse()?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let length: usize = read_line(reader)?
+            .ok_or("unexpected end of stream")?
+            .trim_start_matches('$')
+            .parse()?;
+        let mut value = vec![0; length + 2];
+        reader.read_exact(&mut value)?;
+        value.truncate(length);
+        args.push(String::from_utf8(value)?);
+    }
+
+    Ok(Some(args))
+}
+
+fn bulk(value: Option<&String>) -> String {
+    match value {
+        Some(value) => format!("${}\r\n{}\r\n", value.len(), value),
+        None => "$-1\r\n".to_string(),
+    }
+}
+
+fn array(values: Vec<String>) -> String {
+    format!("*{}\r\n{}", values.len(), values.concat())
+}
+
+fn execute(command: &[String], entries: &mut BTreeMap<String, String>) -> String {
+    let args = &command[1..];
+    match command[0].to_uppercase().as_str() {
+        "PING" => "+PONG\r\n".to_string(),
+        "GET" => bulk(entries.get(&args[0])),
+        "SET" => {
+            entries.insert(args[0].to_owned(), args[1].to_owned());
+            "+OK\r\n".to_string()
+        }
+        "DEL" => {
+            let removed = args.iter().filter(|key| entries.remove(*key).is_some());
+            format!(":{}\r\n", removed.count())
+        }
+        "MGET" => array(args.iter().map(|key| bulk(entries.get(key))).collect()),
+        "SCAN" => {
+            let pattern = args
+                .iter()
+                .position(|arg| arg.eq_ignore_ascii_case("MATCH"))
+                .map(|index| args[index + 1].as_str())
+                .unwrap_or("*");
+            let keys = entries
+                .keys()
+                .filter(|key| glob_match(pattern.as_bytes(), key.as_bytes()))
+                .map(|key| bulk(Some(key)))
+                .collect();
+            // return all keys at once, so the cursor is always finished
+            array(vec![bulk(Some(&"0".to_string())), array(keys)])
+        }
+        other => format!("-ERR unknown command '{}'\r\n", other),
+    }
+}
+
+/// Matches a key against a redis glob pattern, supporting `*`, `?` and `\` escapes.
+fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    match pattern.first() {
+        None => key.is_empty(),
+        Some(b'*') => (0..=key.len()).any(|skip| glob_match(&pattern[1..], &key[skip..])),
+        Some(b'?') => !key.is_empty() && glob_match(&pattern[1..], &key[1..]),
+        Some(b'\\') if pattern.len() > 1 => {
+            key.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &key[1..])
+        }
+        Some(character) => key.first() == Some(character) && glob_match(&pattern[1..], &key[1..]),
+    }
+}